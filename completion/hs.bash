@@ -991,4 +991,18 @@ _hs() {
     esac
 }
 
-complete -F _hs -o bashdefault -o default hs
+_hs_script_args() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local list
+    if list=$(command hs completion script-args -- "${COMP_WORDS[@]:0:COMP_CWORD}" "${cur}" 2>/dev/null); then
+        # Arguments of a script. Fall back to files if nothing is suggested.
+        COMPREPLY=()
+        if [[ -n "${list}" ]]; then
+            mapfile -t COMPREPLY <<< "${list}"
+        fi
+        return 0
+    fi
+    _hs "$@"
+}
+
+complete -F _hs_script_args -o bashdefault -o default hs
@@ -193,6 +193,18 @@ complete -k -c hs -n "__hs_is_alias" -x -a "(__hs_alias_completion)"
 
 complete -k -c hs -n "__hs_not_run_arg_or_alias" -x -a "(__hs_list_scripts)"
 
+function __hs_list_script_args
+    set orig_cmd (commandline -j)
+    set cmd_arr (string split ' ' $orig_cmd)
+    if [ -z "$cmd_arr[-1]" ]
+        # pad an empty word for the argument being typed
+        set trailing "''"
+    end
+    eval "command hs completion script-args -- $orig_cmd $trailing" 2>/dev/null
+end
+
+complete -k -c hs -n "not __hs_not_run_arg_or_alias; and not __hs_is_alias" -a "(__hs_list_script_args)"
+
 function __hs_use_subcommand
     set cmd (commandline -j)
     set cmd_arr (string split ' ' $cmd)
@@ -0,0 +1,5 @@
+ALTER TABLE events ADD COLUMN elapsed_ms integer;
+ALTER TABLE events ADD COLUMN signal integer;
+ALTER TABLE events ADD COLUMN user_time_ms integer;
+ALTER TABLE events ADD COLUMN sys_time_ms integer;
+ALTER TABLE events ADD COLUMN max_rss_kb integer;
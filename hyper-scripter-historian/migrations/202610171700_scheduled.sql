@@ -0,0 +1 @@
+ALTER TABLE events ADD COLUMN scheduled boolean NOT NULL DEFAULT false;
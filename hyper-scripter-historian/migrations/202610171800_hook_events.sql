@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS hook_events (
+    id integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    main_event_id integer NOT NULL,
+    script_id integer NOT NULL,
+    hook text NOT NULL,
+    cmd text NOT NULL,
+    code integer NOT NULL,
+    elapsed_ms integer NOT NULL,
+    time datetime NOT NULL
+);
@@ -9,6 +9,17 @@ pub enum EventType {
     Write,
 }
 
+/// 一次執行的耗時與資源用量，除了耗時以外，取決於平台不一定拿得到
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct ExecUsage {
+    pub elapsed_ms: i64,
+    /// 若子程序是被信號終止的
+    pub signal: Option<i32>,
+    pub user_time_ms: Option<i64>,
+    pub sys_time_ms: Option<i64>,
+    pub max_rss_kb: Option<i64>,
+}
+
 #[derive(Debug)]
 pub enum EventData<'a> {
     Exec {
@@ -20,9 +31,13 @@ pub enum EventData<'a> {
     ExecDone {
         code: i32,
         main_event_id: i64,
+        usage: ExecUsage,
     },
     Read,
-    Write,
+    /// 寫入後的內容，沒讀到內容（例如只是移動腳本）時為 `None`
+    Write {
+        content: Option<&'a str>,
+    },
 }
 
 impl EventData<'_> {
@@ -31,7 +46,7 @@ impl EventData<'_> {
             EventData::Exec { .. } => EventType::Exec,
             EventData::ExecDone { .. } => EventType::ExecDone,
             EventData::Read => EventType::Read,
-            EventData::Write => EventType::Write,
+            EventData::Write { .. } => EventType::Write,
         }
     }
 }
@@ -54,4 +69,6 @@ pub struct Event<'a> {
     pub script_id: i64,
     pub time: NaiveDateTime,
     pub humble: bool,
+    /// 由排程器觸發的執行
+    pub scheduled: bool,
 }
@@ -1,6 +1,7 @@
 use chrono::NaiveDateTime;
 use sqlx::migrate::MigrateError;
 use sqlx::{error::Error as DBError, Pool, Sqlite, SqlitePool};
+use std::collections::HashMap;
 use std::num::NonZeroU64;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
@@ -15,6 +16,7 @@ const EMPTY_STR: &str = "";
 
 const EXEC_CODE: i8 = EventType::Exec.get_code();
 const EXEC_DONE_CODE: i8 = EventType::ExecDone.get_code();
+const WRITE_CODE: i8 = EventType::Write.get_code();
 
 #[derive(Debug, Clone)]
 pub struct Historian {
@@ -23,11 +25,18 @@ pub struct Historian {
 }
 
 async fn raw_record_event(pool: &Pool<Sqlite>, event: DBEvent<'_>) -> Result<i64, DBError> {
+    let usage = event.usage.as_ref();
+    let elapsed_ms = usage.map(|u| u.elapsed_ms);
+    let signal = usage.and_then(|u| u.signal);
+    let user_time_ms = usage.and_then(|u| u.user_time_ms);
+    let sys_time_ms = usage.and_then(|u| u.sys_time_ms);
+    let max_rss_kb = usage.and_then(|u| u.max_rss_kb);
     let res = sqlx::query!(
         "
         INSERT INTO events
-        (script_id, type, cmd, args, content, time, main_event_id, dir, envs, humble)
-        VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        (script_id, type, cmd, args, content, time, main_event_id, dir, envs, humble,
+        elapsed_ms, signal, user_time_ms, sys_time_ms, max_rss_kb, scheduled)
+        VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         RETURNING id
         ",
         event.script_id,
@@ -39,7 +48,13 @@ async fn raw_record_event(pool: &Pool<Sqlite>, event: DBEvent<'_>) -> Result<i64
         event.main_event_id,
         event.dir,
         event.envs,
-        event.humble
+        event.humble,
+        elapsed_ms,
+        signal,
+        user_time_ms,
+        sys_time_ms,
+        max_rss_kb,
+        event.scheduled
     )
     .fetch_one(pool)
     .await?;
@@ -57,7 +72,9 @@ struct DBEvent<'a> {
     envs: Option<&'a str>,
     content: Option<&'a str>,
     humble: bool,
+    scheduled: bool,
     main_event_id: i64,
+    usage: Option<ExecUsage>,
 }
 impl<'a> DBEvent<'a> {
     fn new(script_id: i64, time: NaiveDateTime, ty: i8, cmd: &'a str, humble: bool) -> Self {
@@ -67,11 +84,13 @@ impl<'a> DBEvent<'a> {
             ty,
             cmd,
             humble,
+            scheduled: false,
             main_event_id: ZERO,
             envs: None,
             content: None,
             args: None,
             dir: None,
+            usage: None,
         }
     }
     fn args(mut self, value: &'a str) -> Self {
@@ -98,6 +117,10 @@ impl<'a> DBEvent<'a> {
         self.main_event_id = value;
         self
     }
+    fn usage(mut self, value: ExecUsage) -> Self {
+        self.usage = Some(value);
+        self
+    }
 }
 
 macro_rules! last_arg {
@@ -114,8 +137,8 @@ macro_rules! last_arg {
                 " GROUP BY script_id " + $group_by + " ORDER BY time DESC LIMIT ? OFFSET ?
             ) SELECT "
                 + $select
-                + " FROM args
-            ",
+                + r#", time as "time!: NaiveDateTime" FROM args
+            "#,
             EXEC_CODE,
             $($var, )*
             $limit,
@@ -184,6 +207,45 @@ macro_rules! ignore_or_humble_arg {
     };
 }
 
+/// 一次執行，若已執行完畢則帶有完畢的時間和回傳值
+#[derive(Debug)]
+pub struct ExecRecord {
+    pub script_id: i64,
+    pub time: NaiveDateTime,
+    pub args: String,
+    pub dir: String,
+    pub done_time: Option<NaiveDateTime>,
+    pub code: Option<i32>,
+    /// 較舊的紀錄沒有耗時，只能用時間差推估
+    pub elapsed_ms: Option<i64>,
+    pub scheduled: bool,
+}
+
+/// 一次執行後的鉤子命令（`post_run` 等）的結果，和主執行的回傳值分開記錄
+#[derive(Debug, Clone)]
+pub struct HookRecord {
+    pub main_event_id: i64,
+    pub script_id: i64,
+    pub hook: String,
+    pub cmd: String,
+    pub code: i32,
+    pub elapsed_ms: i64,
+    pub time: NaiveDateTime,
+}
+
+/// 單一個執行事件的完整內容
+#[derive(Debug)]
+pub struct ExecEvent {
+    pub id: i64,
+    pub script_id: i64,
+    pub time: NaiveDateTime,
+    pub args: String,
+    pub envs: String,
+    pub dir: String,
+    /// 執行當下的腳本內容。內容和上次相同時不會重複記錄，故須往回找，若已被整理掉則為 `None`
+    pub content: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct LastTimeRecord {
     pub script_id: i64,
@@ -227,16 +289,71 @@ impl Historian {
         sqlx::query!("DELETE FROM events WHERE script_id = ?", script_id,)
             .execute(&*pool)
             .await?;
+        sqlx::query!("DELETE FROM hook_events WHERE script_id = ?", script_id,)
+            .execute(&*pool)
+            .await?;
         Ok(())
     }
 
+    pub async fn record_hook(&self, hook: &HookRecord) -> Result<i64, DBError> {
+        log::debug!("記錄鉤子 {:?}", hook);
+        let res = sqlx::query!(
+            "
+            INSERT INTO hook_events
+            (main_event_id, script_id, hook, cmd, code, elapsed_ms, time)
+            VALUES(?, ?, ?, ?, ?, ?, ?)
+            RETURNING id
+            ",
+            hook.main_event_id,
+            hook.script_id,
+            hook.hook,
+            hook.cmd,
+            hook.code,
+            hook.elapsed_ms,
+            hook.time,
+        )
+        .fetch_one(&*self.pool.read().unwrap())
+        .await?;
+        Ok(res.id)
+    }
+
+    /// 某次執行的所有鉤子結果，依執行順序排列
+    pub async fn hook_records(&self, main_event_id: i64) -> Result<Vec<HookRecord>, DBError> {
+        let res = sqlx::query!(
+            "
+            SELECT main_event_id, script_id, hook, cmd, code, elapsed_ms, time
+            FROM hook_events WHERE main_event_id = ? ORDER BY id
+            ",
+            main_event_id
+        )
+        .fetch_all(&*self.pool.read().unwrap())
+        .await?;
+        Ok(res
+            .into_iter()
+            .map(|res| HookRecord {
+                main_event_id: res.main_event_id,
+                script_id: res.script_id,
+                hook: res.hook,
+                cmd: res.cmd,
+                code: res.code as i32,
+                elapsed_ms: res.elapsed_ms,
+                time: res.time,
+            })
+            .collect())
+    }
+
     pub async fn record(&self, event: &Event<'_>) -> Result<i64, DBError> {
         log::debug!("記錄事件 {:?}", event);
         let ty = event.data.get_type().get_code();
         let cmd = std::env::args().collect::<Vec<_>>().join(" ");
         let mut db_event = DBEvent::new(event.script_id, event.time, ty, &cmd, event.humble);
+        db_event.scheduled = event.scheduled;
         let id = match &event.data {
-            EventData::Write | EventData::Read => self.raw_record(db_event).await?,
+            EventData::Read => self.raw_record(db_event).await?,
+            EventData::Write { content } => {
+                db_event.content = *content;
+                self.raw_record(db_event).await?
+            }
             EventData::Exec {
                 content,
                 args,
@@ -269,6 +386,7 @@ impl Historian {
             EventData::ExecDone {
                 code,
                 main_event_id,
+                usage,
             } => {
                 let main_event = sqlx::query!(
                     "SELECT ignored, humble FROM events WHERE type = ? AND id = ?",
@@ -293,7 +411,12 @@ impl Historian {
 
                 let code = code.to_string();
                 let id = self
-                    .raw_record(db_event.content(&code).main_event_id(*main_event_id))
+                    .raw_record(
+                        db_event
+                            .content(&code)
+                            .main_event_id(*main_event_id)
+                            .usage(*usage),
+                    )
                     .await?;
 
                 if db_event.humble {
@@ -332,6 +455,202 @@ impl Historian {
         Ok(res.map(|res| (res.args.unwrap_or_default(), res.envs.unwrap_or_default())))
     }
 
+    /// 每組參數的執行次數，依最近一次執行的時間由新到舊排序
+    pub async fn args_counts(
+        &self,
+        id: i64,
+        limit: u32,
+        dir: Option<&Path>,
+    ) -> Result<Vec<(String, i64)>, DBError> {
+        let no_dir = dir.is_none();
+        let dir = dir.map(|p| p.to_string_lossy());
+        let dir = dir.as_deref().unwrap_or(EMPTY_STR);
+        let limit = limit as i64;
+        let res = sqlx::query!(
+            r#"
+            SELECT args as "args!", COUNT(*) as "count!: i64" FROM events
+            WHERE type = ? AND script_id = ? AND NOT ignored AND args IS NOT NULL
+            AND (? OR dir = ?)
+            GROUP BY args ORDER BY max(time) DESC LIMIT ?
+            "#,
+            EXEC_CODE,
+            id,
+            no_dir,
+            dir,
+            limit
+        )
+        .fetch_all(&*self.pool.read().unwrap())
+        .await?;
+        Ok(res.into_iter().map(|res| (res.args, res.count)).collect())
+    }
+
+    /// 每個腳本最後一次（非謙卑地）執行完畢時的回傳值
+    pub async fn last_exit_codes(&self) -> Result<Vec<(i64, i32)>, DBError> {
+        let res = sqlx::query!(
+            "
+            SELECT script_id, content, MAX(time) as time FROM events
+            WHERE type = ? AND NOT ignored AND NOT humble
+            GROUP BY script_id
+            ",
+            EXEC_DONE_CODE
+        )
+        .fetch_all(&*self.pool.read().unwrap())
+        .await?;
+        Ok(res
+            .into_iter()
+            .filter_map(|res| {
+                let code = res.content?.parse().ok()?;
+                Some((res.script_id?, code))
+            })
+            .collect())
+    }
+
+    /// 腳本最新一次被記錄下來的內容，可能來自執行或寫入事件
+    pub async fn latest_content(&self, script_id: i64) -> Result<Option<String>, DBError> {
+        // NOTE: 和上次執行相同的內容不會重複記錄，要往前找
+        let res = sqlx::query!(
+            r#"
+            SELECT CASE e.type WHEN ? THEN
+                (SELECT c.content FROM events c
+                WHERE c.type = e.type AND c.script_id = e.script_id
+                AND c.id <= e.id AND c.content IS NOT NULL
+                ORDER BY c.id DESC LIMIT 1)
+                ELSE e.content END as "content?: String"
+            FROM events e
+            WHERE e.script_id = ?
+            AND (e.type = ? OR (e.type = ? AND e.content IS NOT NULL))
+            ORDER BY e.id DESC LIMIT 1
+            "#,
+            EXEC_CODE,
+            script_id,
+            EXEC_CODE,
+            WRITE_CODE
+        )
+        .fetch_optional(&*self.pool.read().unwrap())
+        .await?;
+        Ok(res.and_then(|res| res.content))
+    }
+
+    pub async fn exec_event(&self, id: i64) -> Result<Option<ExecEvent>, DBError> {
+        let res = sqlx::query!(
+            r#"
+            SELECT e.id, e.script_id, e.time, e.args, e.envs, e.dir,
+                (SELECT c.content FROM events c
+                WHERE c.type = e.type AND c.script_id = e.script_id
+                AND c.id <= e.id AND c.content IS NOT NULL
+                ORDER BY c.id DESC LIMIT 1) as "content?: String"
+            FROM events e
+            WHERE e.type = ? AND e.id = ?
+            "#,
+            EXEC_CODE,
+            id
+        )
+        .fetch_optional(&*self.pool.read().unwrap())
+        .await?;
+        Ok(res.map(|res| ExecEvent {
+            id: res.id,
+            script_id: res.script_id,
+            time: res.time,
+            args: res.args.unwrap_or_default(),
+            envs: res.envs.unwrap_or_default(),
+            dir: res.dir.unwrap_or_default(),
+            content: res.content,
+        }))
+    }
+
+    /// 執行事件的 id（即 run id），由新到舊排序。未指定腳本時回傳所有腳本的
+    pub async fn exec_ids(&self, script_id: Option<i64>) -> Result<Vec<i64>, DBError> {
+        let res = sqlx::query!(
+            "
+            SELECT id FROM events
+            WHERE type = ? AND (? IS NULL OR script_id = ?)
+            ORDER BY id DESC
+            ",
+            EXEC_CODE,
+            script_id,
+            script_id
+        )
+        .fetch_all(&*self.pool.read().unwrap())
+        .await?;
+        Ok(res.into_iter().map(|res| res.id).collect())
+    }
+
+    /// 指定腳本在 `since` 之後所有未被忽略的執行，依時間排序，並以 `main_event_id` 配對執行完畢事件
+    pub async fn exec_records(
+        &self,
+        ids: &[i64],
+        since: Option<NaiveDateTime>,
+    ) -> Result<Vec<ExecRecord>, DBError> {
+        let ids = join_id_str(ids);
+        let since = since.unwrap_or_default();
+        let res = sqlx::query!(
+            r#"
+            SELECT e.script_id, e.time, e.args, e.dir, e.scheduled,
+                d.time as "done_time?: NaiveDateTime", d.content as done_content,
+                d.elapsed_ms as "elapsed_ms?: i64"
+            FROM events e LEFT JOIN events d ON d.type = ? AND d.main_event_id = e.id
+            WHERE e.type = ? AND NOT e.ignored
+            AND instr(?, '[' || e.script_id || ']') > 0 AND e.time >= ?
+            ORDER BY e.time
+            "#,
+            EXEC_DONE_CODE,
+            EXEC_CODE,
+            ids,
+            since
+        )
+        .fetch_all(&*self.pool.read().unwrap())
+        .await?;
+        Ok(res
+            .into_iter()
+            .map(|res| ExecRecord {
+                script_id: res.script_id,
+                time: res.time,
+                args: res.args.unwrap_or_default(),
+                dir: res.dir.unwrap_or_default(),
+                done_time: res.done_time,
+                code: res.done_content.and_then(|c| c.parse().ok()),
+                elapsed_ms: res.elapsed_ms,
+                scheduled: res.scheduled,
+            })
+            .collect())
+    }
+
+    /// 以某組參數最後一次執行完畢時的回傳值與資源用量，較舊的紀錄沒有資源用量
+    pub async fn last_usage(
+        &self,
+        script_id: i64,
+        args: &str,
+    ) -> Result<Option<(i32, Option<ExecUsage>)>, DBError> {
+        let res = sqlx::query!(
+            r#"
+            SELECT d.content, d.elapsed_ms, d.signal as "signal?: i32",
+                d.user_time_ms, d.sys_time_ms, d.max_rss_kb
+            FROM events d JOIN events e ON d.main_event_id = e.id
+            WHERE d.type = ? AND e.type = ? AND NOT e.ignored
+            AND e.script_id = ? AND e.args = ?
+            ORDER BY d.time DESC LIMIT 1
+            "#,
+            EXEC_DONE_CODE,
+            EXEC_CODE,
+            script_id,
+            args
+        )
+        .fetch_optional(&*self.pool.read().unwrap())
+        .await?;
+        Ok(res.and_then(|res| {
+            let code = res.content?.parse().ok()?;
+            let usage = res.elapsed_ms.map(|elapsed_ms| ExecUsage {
+                elapsed_ms,
+                signal: res.signal,
+                user_time_ms: res.user_time_ms,
+                sys_time_ms: res.sys_time_ms,
+                max_rss_kb: res.max_rss_kb,
+            });
+            Some((code, usage))
+        }))
+    }
+
+    /// 每組參數最新一次執行的事件 id 與時間，依時間由新到舊排序
     pub async fn previous_args_list(
         &self,
         ids: &[i64],
@@ -339,9 +658,9 @@ impl Historian {
         offset: u32,
         no_humble: bool,
         dir: Option<&Path>,
-    ) -> Result<impl ExactSizeIterator<Item = (i64, String)>, DBError> {
+    ) -> Result<impl ExactSizeIterator<Item = (i64, NaiveDateTime, i64, String)>, DBError> {
         let res = do_last_arg!(
-            "script_id, args",
+            "id, script_id, args",
             ", args",
             ids,
             limit,
@@ -352,6 +671,8 @@ impl Historian {
         )?;
         Ok(res.into_iter().map(|res| {
             (
+                res.id.unwrap_or_default(),
+                res.time,
                 res.script_id.unwrap_or_default(),
                 res.args.unwrap_or_default(),
             )
@@ -365,9 +686,10 @@ impl Historian {
         offset: u32,
         no_humble: bool,
         dir: Option<&Path>,
-    ) -> Result<impl ExactSizeIterator<Item = (i64, String, String)>, DBError> {
+    ) -> Result<impl ExactSizeIterator<Item = (i64, NaiveDateTime, i64, String, String)>, DBError>
+    {
         let res = do_last_arg!(
-            "script_id, args, envs",
+            "id, script_id, args, envs",
             ", args, envs",
             ids,
             limit,
@@ -378,6 +700,8 @@ impl Historian {
         )?;
         Ok(res.into_iter().map(|res| {
             (
+                res.id.unwrap_or_default(),
+                res.time,
                 res.script_id.unwrap_or_default(),
                 res.args.unwrap_or_default(),
                 res.envs.unwrap_or_default(),
@@ -392,9 +716,9 @@ impl Historian {
         offset: u32,
         no_humble: bool,
         dir: Option<&Path>,
-    ) -> Result<impl ExactSizeIterator<Item = (i64, String)>, DBError> {
+    ) -> Result<impl ExactSizeIterator<Item = (i64, NaiveDateTime, i64, String)>, DBError> {
         let res = do_last_arg!(
-            "script_id, envs",
+            "id, script_id, envs",
             ", envs",
             ids,
             limit,
@@ -405,6 +729,8 @@ impl Historian {
         )?;
         Ok(res.into_iter().map(|res| {
             (
+                res.id.unwrap_or_default(),
+                res.time,
                 res.script_id.unwrap_or_default(),
                 res.envs.unwrap_or_default(),
             )
@@ -597,6 +923,81 @@ impl Historian {
         Ok(())
     }
 
+    /// 從另一個腳本之家的史學家匯入事件。`id_map` 為（來源腳本 id, 新腳本 id）的對應，
+    /// 其它腳本的事件不會匯入。回傳（來源事件 id, 新事件 id）的對應。
+    /// 來源可能是舊版的資料庫，為了不動到對方，會先複製一份到 `tmp_file` 再遷移
+    pub async fn import_events(
+        &self,
+        src_dir: &Path,
+        tmp_file: &Path,
+        id_map: &[(i64, i64)],
+    ) -> Result<HashMap<i64, i64>, DBError> {
+        let src_file = db::get_file(src_dir);
+        if !src_file.exists() {
+            log::info!("{:?} 沒有歷史可匯入", src_dir);
+            return Ok(HashMap::new());
+        }
+        std::fs::copy(&src_file, tmp_file)?;
+        let src_pool = migration::do_migrate(tmp_file)
+            .await
+            .map_err(|e| DBError::Migrate(Box::new(e)))?;
+        let src_ids: Vec<_> = id_map.iter().map(|(src_id, _)| *src_id).collect();
+        let ids_str = join_id_str(&src_ids);
+        // FIXME: 一旦可以綁定陣列就換掉這個醜死人的 instr
+        let events = sqlx::query!(
+            "SELECT * FROM events WHERE instr(?, '[' || script_id || ']') > 0 ORDER BY id",
+            ids_str
+        )
+        .fetch_all(&src_pool)
+        .await?;
+        src_pool.close().await;
+        log::info!("從 {:?} 匯入 {} 個事件", src_dir, events.len());
+
+        let script_map: HashMap<_, _> = id_map.iter().copied().collect();
+        let mut event_map = HashMap::new();
+        let pool = self.pool.read().unwrap();
+        let mut tx = pool.begin().await?;
+        for e in events.into_iter() {
+            let script_id = script_map[&e.script_id];
+            // NOTE: 主事件必在此事件之前，故必已匯入
+            let main_event_id = match e.main_event_id {
+                Some(id) if id != ZERO => event_map.get(&id).copied().unwrap_or(ZERO),
+                _ => ZERO,
+            };
+            let res = sqlx::query!(
+                "
+                INSERT INTO events
+                (script_id, type, cmd, args, content, time, main_event_id, dir, envs, humble,
+                ignored, elapsed_ms, signal, user_time_ms, sys_time_ms, max_rss_kb, scheduled)
+                VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                RETURNING id
+                ",
+                script_id,
+                e.r#type,
+                e.cmd,
+                e.args,
+                e.content,
+                e.time,
+                main_event_id,
+                e.dir,
+                e.envs,
+                e.humble,
+                e.ignored,
+                e.elapsed_ms,
+                e.signal,
+                e.user_time_ms,
+                e.sys_time_ms,
+                e.max_rss_kb,
+                e.scheduled
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            event_map.insert(e.id, res.id);
+        }
+        tx.commit().await?;
+        Ok(event_map)
+    }
+
     /// 除了輸入進來的 script id 外，其它事件通通砍除
     pub async fn clear_except_script_ids(&self, script_ids: &[i64]) -> Result<(), DBError> {
         let ids = join_id_str(script_ids);
@@ -611,6 +1012,15 @@ impl Historian {
         )
         .execute(&*pool)
         .await?;
+        sqlx::query!(
+            "
+            DELETE FROM hook_events
+            WHERE instr(?, '[' || script_id || ']') <= 0
+            ",
+            ids
+        )
+        .execute(&*pool)
+        .await?;
 
         sqlx::query!("VACUUM").execute(&*pool).await?;
 
@@ -640,6 +1050,15 @@ impl Historian {
         )
         .execute(&*pool)
         .await?;
+        sqlx::query!(
+            "
+            DELETE FROM hook_events
+            WHERE script_id = ? AND main_event_id NOT IN (SELECT id FROM events)
+            ",
+            script_id,
+        )
+        .execute(&*pool)
+        .await?;
 
         Ok(())
     }
@@ -89,6 +89,27 @@ class HSEnv
     exec cmd.to_s
   end
 
+  # Pick lines with the built-in picker (`hs pick`).
+  # `bind` maps a ctrl key letter to a label, e.g. { 'w' => 'wait' }.
+  # Returns nil if cancelled, otherwise [label, lines] where label is nil for enter.
+  def pick(lines, multi: false, query: nil, bind: {})
+    require 'open3'
+    require 'shellwords'
+    arg = 'pick'
+    arg += ' --multi' if multi
+    arg += " --query #{Shellwords.escape(query)}" unless query.nil?
+    bind.each { |k, label| arg += " --bind #{Shellwords.escape("#{k}:#{label}")}" }
+    out, status = Open3.capture2(hs_command_str(arg, false), stdin_data: lines.join("\n"))
+    raise StandardError, 'Hyper scripter exits with error' unless status.success?
+
+    res = out.lines.map(&:chomp)
+    return nil if res.empty?
+
+    label = bind.empty? ? nil : res.shift
+    label = nil if label == ''
+    [label, res]
+  end
+
   def env_var(var_name)
     k = ENV_MAP[var_name]
     v = ENV[k]
@@ -8,7 +8,6 @@
 # [HS_HELP]:     hs top --wait-for 'some pattern' # This will wait until 'some pattern' is found in the running process, and wait for it
 
 require_relative './common'
-require_relative './selector'
 
 IGNORE_LIST = ['util/top'] # Add script name here to ignore it in the list
 
@@ -104,36 +103,25 @@ unless wait_for.nil?
   wait_for_run_id(:wait, wait_obj_arr)
 else
   top_options = get_top_options(args)
-  selector = Selector.new
-  selector.load(top_options)
-
-  action = nil
-
-  selector.register_keys(%w[p P], lambda { |_, obj|
-    system("pstree -plsT #{obj.pid}")
-  }, msg: 'print the ps tree')
-
-  selector.register_keys_virtual([ENTER], lambda { |_, _, options|
-  }, msg: 'do nothing', recur: true)
-
-  selector.register_keys_virtual(%w[a A], lambda { |_, _, options|
-    action = :create
-  }, msg: 'Create new anonymous script')
-
-  selector.register_keys_virtual(%w[w W], lambda { |_, _, options|
-    action = :wait
-  }, msg: 'wait for process to end')
-
-  selector.register_keys_virtual(%w[c C], lambda { |_, _, options|
-    action = :source
-  }, msg: 'wait for process to end, but in the next commandline')
-  begin
-    wait_obj_arr = selector.run.options
-    wait_for_run_id(action, wait_obj_arr)
-  rescue Selector::Empty
+  if top_options.empty?
     warn 'No existing process'
     exit
-  rescue Selector::Quit
-    exit
+  end
+
+  bind = { 'o' => 'pstree', 'a' => 'create', 'w' => 'wait', 'l' => 'source' }
+  loop do
+    label, picked = HS_ENV.pick(top_options.map(&:to_s), multi: true, bind: bind)
+    exit if picked.nil?
+
+    wait_obj_arr = picked.filter_map { |l| top_options.find { |opt| opt.to_s == l } }
+    case label
+    when 'pstree'
+      wait_obj_arr.each { |obj| system("pstree -plsT #{obj.pid}") }
+    when nil
+      # do nothing
+    else
+      wait_for_run_id(label.to_sym, wait_obj_arr)
+      break
+    end
   end
 end
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS schedules (
+    id integer PRIMARY KEY AUTOINCREMENT NOT NULL,
+    script_id integer NOT NULL,
+    cron text,
+    args text NOT NULL,
+    next_time datetime NOT NULL,
+    created_time datetime NOT NULL DEFAULT (STRFTIME ('%Y-%m-%d %H:%M:%f', 'NOW'))
+);
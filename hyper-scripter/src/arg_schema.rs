@@ -0,0 +1,650 @@
+//! 腳本以 `[HS_ARG]`、`[HS_FLAG]` 宣告的參數，例如：
+//! ```text
+//! # [HS_ARG]: stage: dev|staging|prod = dev -> Where to deploy
+//! # [HS_ARG]: count?: int -> How many times
+//! # [HS_ARG]: files...: path -> Files to upload
+//! # [HS_FLAG]: -n, --dry-run -> Print commands only
+//! # [HS_FLAG]: --region!: str -> Region to deploy to
+//! ```
+//! 位置參數預設為必填，`?` 表示選填，`...` 表示吃掉剩下所有參數（可以沒有）；旗標預設為選填，`!` 表示必填。
+//! 沒有型別的旗標是開關，有預設值者即為選填
+
+use crate::error::{Error, FormatCode, Result};
+use crate::extract_msg::extract_arg_from_content;
+use std::fmt::Write;
+
+const ENV_PREFIX: &str = "HS_ARG_";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ArgType {
+    Str,
+    Int,
+    Float,
+    Bool,
+    Path,
+    Choice(Vec<String>),
+}
+impl ArgType {
+    fn parse(s: &str) -> Option<Self> {
+        let ty = match s {
+            "" | "str" => ArgType::Str,
+            "int" => ArgType::Int,
+            "float" => ArgType::Float,
+            "bool" => ArgType::Bool,
+            "path" => ArgType::Path,
+            _ if s.contains('|') => {
+                ArgType::Choice(s.split('|').map(|c| c.trim().to_owned()).collect())
+            }
+            _ => return None,
+        };
+        Some(ty)
+    }
+    /// 檢查值是否符合型別，不符合時回傳錯誤訊息
+    fn check(&self, v: &str) -> std::result::Result<(), String> {
+        let ok = match self {
+            ArgType::Str => true,
+            ArgType::Int => v.parse::<i64>().is_ok(),
+            ArgType::Float => v.parse::<f64>().is_ok(),
+            ArgType::Bool => v == "true" || v == "false",
+            ArgType::Path => std::path::Path::new(v).exists(),
+            ArgType::Choice(choices) => choices.iter().any(|c| c == v),
+        };
+        if ok {
+            return Ok(());
+        }
+        // LOCALE
+        Err(match self {
+            ArgType::Path => format!("path `{}` does not exist", v),
+            ArgType::Choice(choices) => {
+                format!("`{}` is not one of {}", v, choices.join(", "))
+            }
+            _ => format!("`{}` is not a valid {}", v, self.name()),
+        })
+    }
+    fn name(&self) -> &str {
+        match self {
+            ArgType::Str => "str",
+            ArgType::Int => "int",
+            ArgType::Float => "float",
+            ArgType::Bool => "bool",
+            ArgType::Path => "path",
+            ArgType::Choice(_) => "choice",
+        }
+    }
+    /// 補全用的候選值
+    fn candidates(&self) -> Vec<String> {
+        match self {
+            ArgType::Choice(choices) => choices.clone(),
+            ArgType::Bool => vec!["true".to_owned(), "false".to_owned()],
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ArgSpec {
+    pub name: String,
+    pub ty: ArgType,
+    pub default: Option<String>,
+    pub required: bool,
+    pub variadic: bool,
+    pub help: String,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FlagSpec {
+    pub long: Option<String>,
+    pub short: Option<char>,
+    /// `None` 代表開關
+    pub ty: Option<ArgType>,
+    pub default: Option<String>,
+    pub required: bool,
+    pub help: String,
+}
+impl FlagSpec {
+    fn name(&self) -> String {
+        match (&self.long, self.short) {
+            (Some(long), _) => long.clone(),
+            (None, Some(short)) => short.to_string(),
+            (None, None) => unreachable!(),
+        }
+    }
+    fn matches(&self, s: &str) -> bool {
+        if let Some(long) = s.strip_prefix("--") {
+            self.long.as_deref() == Some(long)
+        } else {
+            let mut chars = s[1..].chars();
+            chars.next() == self.short && chars.next().is_none()
+        }
+    }
+    fn display(&self) -> String {
+        let mut s = match (&self.long, self.short) {
+            (Some(long), Some(short)) => format!("-{}, --{}", short, long),
+            (Some(long), None) => format!("    --{}", long),
+            (None, Some(short)) => format!("-{}", short),
+            (None, None) => unreachable!(),
+        };
+        if let Some(ty) = &self.ty {
+            write!(s, " <{}>", ty.name()).unwrap();
+        }
+        s
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ArgSchema {
+    pub args: Vec<ArgSpec>,
+    pub flags: Vec<FlagSpec>,
+}
+
+fn env_name(name: &str) -> String {
+    format!("{}{}", ENV_PREFIX, name.to_uppercase().replace('-', "_"))
+}
+
+fn is_valid_name(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// 把宣告拆成 `(名字, 型別, 預設值, 說明)`
+fn split_decl(line: &str) -> (&str, &str, Option<&str>, &str) {
+    let (spec, help) = match line.split_once("->") {
+        Some((spec, help)) => (spec, help.trim()),
+        None => (line, ""),
+    };
+    let (head, default) = match spec.split_once('=') {
+        Some((head, default)) => (head, Some(default.trim())),
+        None => (spec, None),
+    };
+    let (name, ty) = match head.split_once(':') {
+        Some((name, ty)) => (name, ty),
+        None => (head, ""),
+    };
+    (name.trim(), ty.trim(), default, help)
+}
+
+fn parse_arg(line: &str) -> Option<ArgSpec> {
+    let (name, ty, default, help) = split_decl(line);
+    let (name, variadic) = match name.strip_suffix("...") {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    let (name, optional) = match name.strip_suffix('?') {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    if !is_valid_name(name) {
+        return None;
+    }
+    let ty = ArgType::parse(ty)?;
+    if let Some(default) = default {
+        ty.check(default).ok()?;
+    }
+    Some(ArgSpec {
+        name: name.to_owned(),
+        required: !optional && !variadic && default.is_none(),
+        default: default.map(str::to_owned),
+        ty,
+        variadic,
+        help: help.to_owned(),
+    })
+}
+
+fn parse_flag(line: &str) -> Option<FlagSpec> {
+    let (names, ty, default, help) = split_decl(line);
+    let (names, required) = match names.strip_suffix('!') {
+        Some(names) => (names, true),
+        None => (names, false),
+    };
+    let mut long = None;
+    let mut short = None;
+    for name in names.split(',').map(str::trim) {
+        if let Some(name) = name.strip_prefix("--") {
+            if !is_valid_name(name) || long.is_some() {
+                return None;
+            }
+            long = Some(name.to_owned());
+        } else if let Some(name) = name.strip_prefix('-') {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_alphanumeric() && short.is_none() => short = Some(c),
+                _ => return None,
+            }
+        } else {
+            return None;
+        }
+    }
+    if long.is_none() && short.is_none() {
+        return None;
+    }
+    let ty = if ty.is_empty() && default.is_none() {
+        if required {
+            return None; // 必填的開關沒有意義
+        }
+        None
+    } else {
+        let ty = ArgType::parse(ty)?;
+        if let Some(default) = default {
+            ty.check(default).ok()?;
+        }
+        Some(ty)
+    };
+    Some(FlagSpec {
+        long,
+        short,
+        ty,
+        required: required && default.is_none(),
+        default: default.map(str::to_owned),
+        help: help.to_owned(),
+    })
+}
+
+impl ArgSchema {
+    /// 從腳本內容中解析參數宣告，宣告寫錯時回傳錯誤
+    pub fn from_content(content: &str) -> Result<Self> {
+        let mut schema = ArgSchema::default();
+        for (is_arg, line) in extract_arg_from_content(content) {
+            let err = || FormatCode::ArgSchema.to_err(line.to_owned());
+            if is_arg {
+                if schema.args.last().is_some_and(|a| a.variadic) {
+                    log::warn!("`...` 參數之後不能再有位置參數：{}", line);
+                    return Err(err());
+                }
+                let arg = parse_arg(line).ok_or_else(err)?;
+                if arg.required && schema.args.iter().any(|a| !a.required) {
+                    log::warn!("必填參數不能在選填參數之後：{}", line);
+                    return Err(err());
+                }
+                schema.args.push(arg);
+            } else {
+                schema.flags.push(parse_flag(line).ok_or_else(err)?);
+            }
+        }
+        Ok(schema)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty() && self.flags.is_empty()
+    }
+
+    /// 依已輸入的參數 `words`，給出正在輸入的參數 `cur` 可能的值（未依 `cur` 過濾）
+    pub fn candidates(&self, words: &[String], cur: &str) -> Vec<String> {
+        let mut positional = 0;
+        let mut used = vec![false; self.flags.len()];
+        let mut pending: Option<&FlagSpec> = None;
+        let mut only_positional = false;
+        for word in words.iter() {
+            if pending.take().is_some() {
+                continue;
+            }
+            if only_positional || !word.starts_with('-') || word.parse::<f64>().is_ok() {
+                positional += 1;
+                continue;
+            }
+            if word == "--" {
+                only_positional = true;
+                continue;
+            }
+            let key = word.split_once('=').map_or(word.as_str(), |(key, _)| key);
+            if let Some(idx) = self.flags.iter().position(|f| f.matches(key)) {
+                used[idx] = true;
+                let flag = &self.flags[idx];
+                if flag.ty.is_some() && !word.contains('=') {
+                    pending = Some(flag);
+                }
+            }
+        }
+
+        if let Some(flag) = pending {
+            return flag
+                .ty
+                .as_ref()
+                .map(ArgType::candidates)
+                .unwrap_or_default();
+        }
+        if !only_positional && cur.starts_with('-') {
+            if let Some((key, _)) = cur.split_once('=') {
+                let flag = self.flags.iter().find(|f| f.matches(key));
+                let ty = flag.and_then(|f| f.ty.as_ref());
+                let values = ty.map(ArgType::candidates).unwrap_or_default();
+                return values
+                    .into_iter()
+                    .map(|v| format!("{}={}", key, v))
+                    .collect();
+            }
+            let unused = self.flags.iter().zip(used).filter(|(_, used)| !used);
+            return unused
+                .map(|(f, _)| match (&f.long, f.short) {
+                    (Some(long), _) => format!("--{}", long),
+                    (None, Some(short)) => format!("-{}", short),
+                    (None, None) => unreachable!(),
+                })
+                .collect();
+        }
+        let spec = match self.args.get(positional) {
+            Some(spec) => spec,
+            None => match self.args.last() {
+                Some(spec) if spec.variadic => spec,
+                _ => return vec![],
+            },
+        };
+        spec.ty.candidates()
+    }
+
+    /// 依宣告檢查參數，回傳要給腳本的環境變數。宣告過的名字都會有環境變數，沒給值也沒預設值者為空字串
+    pub fn validate(&self, args: &[String]) -> std::result::Result<Vec<(String, String)>, String> {
+        // LOCALE
+        let mut positional: Vec<&str> = vec![];
+        let mut flag_values: Vec<Option<String>> = vec![None; self.flags.len()];
+        let mut iter = args.iter();
+        let mut only_positional = false;
+        while let Some(arg) = iter.next() {
+            let is_flag = !only_positional
+                && arg.len() > 1
+                && arg.starts_with('-')
+                && arg.parse::<f64>().is_err();
+            if !is_flag {
+                positional.push(arg);
+                continue;
+            }
+            if arg == "--" {
+                only_positional = true;
+                continue;
+            }
+            let (key, inline) = match arg.split_once('=') {
+                Some((key, v)) if arg.starts_with("--") => (key, Some(v)),
+                _ => (arg.as_str(), None),
+            };
+            let idx = self
+                .flags
+                .iter()
+                .position(|f| f.matches(key))
+                .ok_or_else(|| format!("unknown flag `{}`", key))?;
+            let flag = &self.flags[idx];
+            let value = match (&flag.ty, inline) {
+                (None, None) => "1".to_owned(),
+                (None, Some(_)) => return Err(format!("flag `{}` takes no value", key)),
+                (Some(ty), v) => {
+                    let v = match v {
+                        Some(v) => v,
+                        None => iter
+                            .next()
+                            .ok_or_else(|| format!("flag `{}` requires a value", key))?,
+                    };
+                    ty.check(v).map_err(|e| format!("{}: {}", key, e))?;
+                    v.to_owned()
+                }
+            };
+            flag_values[idx] = Some(value);
+        }
+
+        let mut envs = vec![];
+        let mut positional = positional.into_iter();
+        for spec in self.args.iter() {
+            let values: Vec<&str> = if spec.variadic {
+                positional.by_ref().collect()
+            } else {
+                positional.next().into_iter().collect()
+            };
+            for v in values.iter() {
+                spec.ty
+                    .check(v)
+                    .map_err(|e| format!("<{}>: {}", spec.name, e))?;
+            }
+            let value = if values.is_empty() {
+                match &spec.default {
+                    Some(default) => default.clone(),
+                    None if spec.required => {
+                        return Err(format!("missing argument <{}>", spec.name))
+                    }
+                    None => String::new(),
+                }
+            } else {
+                values.join(" ")
+            };
+            envs.push((env_name(&spec.name), value));
+        }
+        if let Some(extra) = positional.next() {
+            return Err(format!("unexpected argument `{}`", extra));
+        }
+        for (flag, value) in self.flags.iter().zip(flag_values) {
+            let value = match value.or_else(|| flag.default.clone()) {
+                Some(v) => v,
+                None if flag.required => {
+                    return Err(format!("missing flag `{}`", flag.display().trim()))
+                }
+                None => String::new(),
+            };
+            envs.push((env_name(&flag.name()), value));
+        }
+        Ok(envs)
+    }
+
+    /// 像 clap 一樣的用法說明
+    pub fn usage(&self, name: &str) -> String {
+        // LOCALE
+        let mut usage = format!("USAGE:\n    {} {}", crate::APP_NAME, name);
+        for flag in self.flags.iter().filter(|f| f.required) {
+            write!(usage, " {}", flag.display().trim()).unwrap();
+        }
+        if self.flags.iter().any(|f| !f.required) {
+            usage += " [OPTIONS]";
+        }
+        for arg in self.args.iter() {
+            match (arg.required, arg.variadic) {
+                (true, _) => write!(usage, " <{}>", arg.name),
+                (false, false) => write!(usage, " [{}]", arg.name),
+                (false, true) => write!(usage, " [{}]...", arg.name),
+            }
+            .unwrap();
+        }
+
+        fn extra(help: &str, ty: Option<&ArgType>, default: &Option<String>) -> String {
+            let mut s = help.to_owned();
+            if let Some(default) = default {
+                write!(s, " [default: {}]", default).unwrap();
+            }
+            if let Some(ArgType::Choice(choices)) = ty {
+                write!(s, " [possible values: {}]", choices.join(", ")).unwrap();
+            }
+            s.trim().to_owned()
+        }
+        let mut sections = vec![];
+        if !self.args.is_empty() {
+            let rows = self.args.iter().map(|a| {
+                let ty = match &a.ty {
+                    ArgType::Str | ArgType::Choice(_) => String::new(),
+                    ty => format!(" <{}>", ty.name()),
+                };
+                let name = format!("<{}>{}", a.name, ty);
+                (name, extra(&a.help, Some(&a.ty), &a.default))
+            });
+            sections.push(("ARGS", rows.collect::<Vec<_>>()));
+        }
+        if !self.flags.is_empty() {
+            let rows = self.flags.iter().map(|f| {
+                let mut help = extra(&f.help, f.ty.as_ref(), &f.default);
+                if f.required {
+                    help = format!("{} [required]", help).trim().to_owned();
+                }
+                (f.display(), help)
+            });
+            sections.push(("OPTIONS", rows.collect::<Vec<_>>()));
+        }
+        for (title, rows) in sections.into_iter() {
+            let width = rows.iter().map(|(n, _)| n.len()).max().unwrap_or_default();
+            write!(usage, "\n\n{}:", title).unwrap();
+            for (name, help) in rows.into_iter() {
+                let row = format!("\n    {:width$}    {}", name, help, width = width);
+                usage += row.trim_end();
+            }
+        }
+        usage
+    }
+}
+
+/// 讀取腳本內容中的參數宣告並檢查參數，回傳要給腳本的環境變數。沒有宣告時不做任何檢查
+pub fn check_args(name: &str, content: &str, args: &[String]) -> Result<Vec<(String, String)>> {
+    let schema = ArgSchema::from_content(content)?;
+    if schema.is_empty() {
+        return Ok(vec![]);
+    }
+    log::debug!("參數宣告：{:?}", schema);
+    schema
+        .validate(args)
+        .map_err(|msg| Error::InvalidScriptArgs(name.to_owned(), msg))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONTENT: &str = "
+    # [HS_HELP]: deploy things
+    # [HS_ARG]: stage: dev|staging|prod = dev -> Where to deploy
+    # [HS_ARG]: count?: int -> How many times
+    # [HS_ARG]: files...
+    # [HS_FLAG]: -n, --dry-run -> Print commands only
+    # [HS_FLAG]: --region!: str -> Region
+    # [HS_FLAG]: -j: int = 4
+    ";
+
+    fn validate(args: &str) -> std::result::Result<Vec<String>, String> {
+        let schema = ArgSchema::from_content(CONTENT).unwrap();
+        let args: Vec<_> = args.split_whitespace().map(str::to_owned).collect();
+        let envs = schema.validate(&args)?;
+        Ok(envs
+            .into_iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect())
+    }
+
+    #[test]
+    fn test_parse_schema() {
+        let schema = ArgSchema::from_content(CONTENT).unwrap();
+        assert_eq!(
+            schema.args[0],
+            ArgSpec {
+                name: "stage".to_owned(),
+                ty: ArgType::Choice(vec![
+                    "dev".to_owned(),
+                    "staging".to_owned(),
+                    "prod".to_owned()
+                ]),
+                default: Some("dev".to_owned()),
+                required: false,
+                variadic: false,
+                help: "Where to deploy".to_owned(),
+            }
+        );
+        assert!(!schema.args[1].required);
+        assert!(schema.args[2].variadic);
+        assert_eq!(schema.flags[0].short, Some('n'));
+        assert_eq!(schema.flags[0].ty, None);
+        assert!(schema.flags[1].required);
+        assert_eq!(schema.flags[2].default.as_deref(), Some("4"));
+
+        for bad in [
+            "[HS_ARG]: stage: unknown-type",
+            "[HS_ARG]: count: int = x",
+            "[HS_ARG]: a b",
+            "[HS_FLAG]: dry-run",
+            "[HS_FLAG]: --dry-run!",
+            "[HS_ARG]: a?\n[HS_ARG]: b",
+            "[HS_ARG]: a...\n[HS_ARG]: b?",
+        ] {
+            ArgSchema::from_content(bad).expect_err(bad);
+        }
+    }
+
+    #[test]
+    fn test_validate_args() {
+        assert_eq!(
+            validate("--region us").unwrap(),
+            vec![
+                "HS_ARG_STAGE=dev",
+                "HS_ARG_COUNT=",
+                "HS_ARG_FILES=",
+                "HS_ARG_DRY_RUN=",
+                "HS_ARG_REGION=us",
+                "HS_ARG_J=4",
+            ]
+        );
+        assert_eq!(
+            validate("prod -n 3 --region=eu a -- -b -j 8").unwrap(),
+            vec![
+                "HS_ARG_STAGE=prod",
+                "HS_ARG_COUNT=3",
+                "HS_ARG_FILES=a -b -j 8",
+                "HS_ARG_DRY_RUN=1",
+                "HS_ARG_REGION=eu",
+                "HS_ARG_J=4",
+            ]
+        );
+        assert_eq!(
+            Err("missing flag `--region <str>`".to_owned()),
+            validate("prod")
+        );
+        assert_eq!(
+            Err("<stage>: `qa` is not one of dev, staging, prod".to_owned()),
+            validate("qa --region us")
+        );
+        assert_eq!(
+            Err("<count>: `x` is not a valid int".to_owned()),
+            validate("dev x --region us")
+        );
+        assert_eq!(
+            Err("unknown flag `--force`".to_owned()),
+            validate("--force --region us")
+        );
+        assert_eq!(
+            Err("flag `--region` requires a value".to_owned()),
+            validate("--region")
+        );
+        assert_eq!(
+            Err("flag `--dry-run` takes no value".to_owned()),
+            validate("--dry-run=1 --region us")
+        );
+    }
+
+    #[test]
+    fn test_candidates() {
+        let schema = ArgSchema::from_content(CONTENT).unwrap();
+        let candidates = |words: &str, cur: &str| {
+            let words: Vec<_> = words.split_whitespace().map(str::to_owned).collect();
+            schema.candidates(&words, cur)
+        };
+        assert_eq!(vec!["dev", "staging", "prod"], candidates("", ""));
+        assert_eq!(
+            vec!["dev", "staging", "prod"],
+            candidates("-n --region us", "p")
+        );
+        assert_eq!(Vec::<String>::new(), candidates("prod", ""));
+        assert_eq!(vec!["--region", "-j"], candidates("-n", "-"));
+        assert_eq!(vec!["--dry-run", "--region", "-j"], candidates("", "-"));
+        assert_eq!(Vec::<String>::new(), candidates("-- x", "-"));
+        assert_eq!(vec!["--region"], candidates("-n -j 3", "--"));
+        assert_eq!(Vec::<String>::new(), candidates("--region", ""));
+    }
+
+    #[test]
+    fn test_usage() {
+        let schema = ArgSchema::from_content(CONTENT).unwrap();
+        assert_eq!(
+            schema.usage("deploy"),
+            "USAGE:
+    hs deploy --region <str> [OPTIONS] [stage] [count] [files]...
+
+ARGS:
+    <stage>          Where to deploy [default: dev] [possible values: dev, staging, prod]
+    <count> <int>    How many times
+    <files>
+
+OPTIONS:
+    -n, --dry-run         Print commands only
+        --region <str>    Region [required]
+    -j <int>              [default: 4]"
+        );
+    }
+}
@@ -26,6 +26,10 @@ pub enum Completion {
         #[clap(required = true, min_values = 1)]
         args: Vec<String>,
     },
+    ScriptArgs {
+        #[clap(required = true, min_values = 1)]
+        args: Vec<String>,
+    },
     NoSubcommand {
         #[clap(required = true, min_values = 1)]
         args: Vec<String>,
@@ -11,3 +11,13 @@ pub const LIST_QUERY_HELP: &str = "Target script.
 `-` or `^{N}` for previous script, and `={NAME}` for exact name matching.
 Otherwise, do fuzzy search.
 Wildcard such as name/* is also allowed.";
+pub const FORMAT_HELP: &str =
+    "Output format. `json` and `ndjson` are machine-readable with stable fields.";
+macro_rules! time_query_suffix {
+    () => {
+        " since the given local date or duration, e.g. `2024-01-31`, `2024-01-31 12:00`, `3d`. Units: s m h d w"
+    };
+}
+pub const CREATED_SINCE_HELP: &str = concat!("Only show scripts created", time_query_suffix!());
+pub const WRITTEN_SINCE_HELP: &str =
+    concat!("Only show scripts last written", time_query_suffix!());
@@ -1,12 +1,15 @@
-use crate::config::{Alias, Config, PromptLevel, Recent};
+use crate::config::{Config, PromptLevel, Recent};
+use crate::config_layer::{ConfigLayer, CONFIG_LAYERS};
+use crate::cron::CronExpr;
 use crate::env_pair::EnvPair;
 use crate::error::{DisplayError, DisplayResult, Result};
-use crate::list::Grouping;
+use crate::list::{Grouping, SortKey, SORT_VALUES};
+use crate::output::{OutputFormat, FORMAT_VALUES};
 use crate::path;
-use crate::query::{EditQuery, ListQuery, RangeQuery, ScriptOrDirQuery, ScriptQuery};
+use crate::picker::PickBinding;
+use crate::query::{EditQuery, ListQuery, RangeQuery, ScriptOrDirQuery, ScriptQuery, TimeQuery};
 use crate::script_type::{ScriptFullType, ScriptType};
 use crate::tag::TagSelector;
-use crate::to_display_args;
 use crate::Either;
 use crate::APP_NAME;
 use clap::{CommandFactory, Error as ClapError, Parser};
@@ -28,6 +31,12 @@ pub use types::*;
 pub struct RootArgs {
     #[clap(short = 'H', long, help = "Path to hyper script home")]
     pub hs_home: Option<String>,
+    #[clap(
+        long,
+        number_of_values = 1,
+        help = "Override a config value, e.g. `--config recent=3` or `--config alias.g=[\"grep\"]`"
+    )]
+    pub config: Vec<String>,
     #[clap(long, hide = true)]
     pub dump_args: bool,
     #[clap(long, global = true, help = "Don't record history")]
@@ -49,7 +58,7 @@ pub struct RootArgs {
         global = true,
         conflicts_with = "all",
         number_of_values = 1,
-        help = "Select by tags, e.g. `all,^remove`"
+        help = "Select by tags, e.g. `all,^remove` or `(all & !remove) | @sh`"
     )]
     pub select: Vec<TagSelector>,
     #[clap(
@@ -114,48 +123,37 @@ pub struct AliasRoot {
     pub subcmd: Option<AliasSubs>,
 }
 impl AliasRoot {
-    fn find_alias<'a>(&'a self, conf: &'a Config) -> Option<(&'a Alias, &'a [String])> {
+    /// `hs config check` 要能檢查壞掉的設定檔，故不能先載入設定檔，也就不展開別名
+    fn is_config_check(&self) -> bool {
         match &self.subcmd {
-            None => None,
-            Some(AliasSubs::Other(v)) => {
-                let first = v.first().unwrap().as_str();
-                if let Some(alias) = conf.alias.get(first) {
-                    log::info!("別名 {} => {:?}", first, alias);
-                    Some((alias, v))
-                } else {
-                    None
-                }
-            }
+            Some(AliasSubs::Other(v)) => v.len() >= 2 && v[0] == "config" && v[1] == "check",
+            None => false,
         }
     }
-    pub fn expand_alias<'a, T: 'a + AsRef<str>>(
-        &'a self,
-        args: &'a [T],
-        conf: &'a Config,
-    ) -> Option<Either<impl Iterator<Item = &'a str>, Vec<String>>> {
-        if let Some((alias, remaining_args)) = self.find_alias(conf) {
-            let (is_shell, after_args) = alias.args();
-            let remaining_args = remaining_args[1..].iter().map(String::as_str);
-
-            if is_shell {
-                // shell 別名，完全無視開頭的參數（例如 `hs -s tag -H path/to/home`）
-                let remaining_args = remaining_args.map(|s| to_display_args(s).to_string());
-                let ret: Vec<_> = after_args
-                    .map(ToOwned::to_owned)
-                    .chain(remaining_args)
-                    .collect();
-                return Some(Either::Two(ret));
-            }
-
-            let base_len = args.len() - remaining_args.len() - 1;
-            let base_args = args.iter().take(base_len).map(AsRef::as_ref);
-            let new_args = base_args.chain(after_args).chain(remaining_args);
-
-            // log::trace!("新的參數為 {:?}", new_args);
-            Some(Either::One(new_args))
-        } else {
-            None
+    /// 展開別名（可能是好幾層），見 `Config::expand_alias`
+    pub fn expand_alias<T: AsRef<str>>(
+        &self,
+        args: &[T],
+        conf: &Config,
+    ) -> Result<Option<Either<Vec<String>, Vec<String>>>> {
+        let words = match &self.subcmd {
+            None => return Ok(None),
+            Some(AliasSubs::Other(v)) => v,
+        };
+        let (is_shell, new_words) = match conf.expand_alias(words)? {
+            Some(res) => res,
+            None => return Ok(None),
+        };
+        if is_shell {
+            // shell 別名，完全無視開頭的參數（例如 `hs -s tag -H path/to/home`）
+            return Ok(Some(Either::Two(new_words)));
         }
+
+        let base_len = args.len() - words.len();
+        let base_args = args.iter().take(base_len).map(|s| s.as_ref().to_owned());
+        let new_args = base_args.chain(new_words).collect();
+        // log::trace!("新的參數為 {:?}", new_args);
+        Ok(Some(Either::One(new_args)))
     }
 }
 
@@ -201,6 +199,13 @@ pub enum Subs {
             help = "Unset an alias."
         )]
         unset: bool,
+        #[clap(
+            long,
+            requires = "before",
+            conflicts_with = "unset",
+            help = "Print the final command line the alias expands to with the given args, e.g. `hs alias --explain deploy prod`"
+        )]
+        explain: bool,
         before: Option<String>,
         #[clap(allow_hyphen_values = true)]
         after: Vec<String>,
@@ -227,6 +232,18 @@ pub enum Subs {
         error_no_previous: bool,
         #[clap(long, short, requires = "previous", help = "")]
         dir: Option<PathBuf>,
+        #[clap(
+            long,
+            short,
+            help = "Set an env var for this run, overriding the one from last run (e.g. VAR=1)"
+        )]
+        env: Vec<EnvPair>,
+        #[clap(
+            long,
+            conflicts_with_all = &["dummy", "repeat", "previous", "env", "dir"],
+            help = "Schedule the run at the given local date or after a duration instead, e.g. `30m`, `2024-01-31 09:00`. Fired by `hs scheduler`"
+        )]
+        at: Option<TimeQuery>,
         #[clap(default_value = "-", help = SCRIPT_QUERY_HELP)]
         script_query: ScriptQuery,
         #[clap(
@@ -237,6 +254,10 @@ pub enum Subs {
     },
     #[clap(about = "Execute the script query and get the exact file")]
     Which {
+        #[clap(long, help = "Explain why the script passes or fails the filter.")]
+        explain: bool,
+        #[clap(long, possible_values(FORMAT_VALUES), default_value = "human", conflicts_with = "explain", help = FORMAT_HELP)]
+        format: OutputFormat,
         #[clap(default_value = "-", help = LIST_QUERY_HELP)]
         queries: Vec<ListQuery>,
     },
@@ -290,6 +311,21 @@ pub enum Subs {
         #[clap(subcommand)]
         subcmd: History,
     },
+    #[clap(about = "Manage the global hs home and project homes (`.hyper_scripter/`)")]
+    Home {
+        #[clap(subcommand)]
+        subcmd: Home,
+    },
+    #[clap(about = "Show or edit config, which is merged from several layers")]
+    Config {
+        #[clap(subcommand)]
+        subcmd: ConfigSubs,
+    },
+    #[clap(about = "Version hs home with git")]
+    Vcs {
+        #[clap(subcommand)]
+        subcmd: Vcs,
+    },
     #[clap(about = "Monitor hs process")]
     Top {
         #[clap(long, short, help = "Wait for all involved processes to halt")]
@@ -299,6 +335,163 @@ pub enum Subs {
         #[clap(help = LIST_QUERY_HELP)]
         queries: Vec<ListQuery>,
     },
+    #[clap(about = "Show the changes of the script between runs, or since a run")]
+    Diff {
+        #[clap(long, help = "Run event ID to diff from, defaults to the latest run")]
+        from_run: Option<u64>,
+        #[clap(
+            long,
+            help = "Run event ID to diff to, defaults to the current content of the script"
+        )]
+        to_run: Option<u64>,
+        #[clap(default_value = "-", help = SCRIPT_QUERY_HELP)]
+        script_query: ScriptQuery,
+    },
+    #[clap(about = "Restore the script to the content of a past run")]
+    Restore {
+        #[clap(long, help = "Run event ID to restore, defaults to the latest run")]
+        run_id: Option<u64>,
+        #[clap(
+            long,
+            conflicts_with = "run-id",
+            help = "Restore the K-th latest run, 1 being the latest"
+        )]
+        previous: Option<NonZeroUsize>,
+        #[clap(long, short, help = "Overwrite changes not recorded in any run")]
+        force: bool,
+        #[clap(
+            long,
+            help = "Restore into a new script instead. `?` for new anonymous."
+        )]
+        to: Option<EditQuery<ScriptOrDirQuery>>,
+        #[clap(default_value = "-", help = SCRIPT_QUERY_HELP)]
+        script_query: ScriptQuery,
+    },
+    #[clap(about = "Show the output log of a script run")]
+    Log {
+        #[clap(long, help = "Show the log of the given run instead of the latest one")]
+        run_id: Option<u64>,
+        #[clap(long, short, help = "Keep printing new output until the run finishes")]
+        follow: bool,
+        #[clap(default_value = "-", help = SCRIPT_QUERY_HELP)]
+        script_query: ScriptQuery,
+    },
+    #[clap(about = "Show run statistics of scripts")]
+    Stats {
+        #[clap(
+            long,
+            help = "Only count runs since the given local date or duration, e.g. `2024-01-31`, `3d`"
+        )]
+        since: Option<TimeQuery>,
+        #[clap(long, possible_values(FORMAT_VALUES), default_value = "human", help = FORMAT_HELP)]
+        format: OutputFormat,
+        #[clap(help = LIST_QUERY_HELP)]
+        queries: Vec<ListQuery>,
+    },
+    #[clap(
+        about = "Reconcile files in hs home with the database: untracked files, scripts without a file, and moved scripts. Only reports by default."
+    )]
+    Collect {
+        #[clap(long, conflicts_with = "interactive", help = "Apply every change")]
+        apply: bool,
+        #[clap(long, short, help = "Ask before applying each change")]
+        interactive: bool,
+    },
+    #[clap(about = "Export scripts into a tarball, which can be loaded back with `hs import`")]
+    Export {
+        #[clap(
+            long,
+            short,
+            help = "Output file, compressed according to its extension, e.g. `bundle.tar.gz`"
+        )]
+        output: PathBuf,
+        #[clap(long, help = "Also export the run history of the scripts")]
+        with_history: bool,
+        #[clap(required = true, min_values = 1, help = LIST_QUERY_HELP)]
+        queries: Vec<ListQuery>,
+    },
+    #[clap(
+        about = "Import scripts and their history from another hs home, a git repo or a tarball"
+    )]
+    Import {
+        #[clap(long, short, help = "Put the imported scripts under this namespace")]
+        namespace: Option<String>,
+        #[clap(
+            long,
+            possible_values(CONFLICT_POLICIES),
+            default_value = "skip",
+            help = "What to do when a script with the same name already exists"
+        )]
+        on_conflict: ConflictPolicy,
+        #[clap(long, help = "Import the scripts only, without their run history")]
+        no_history: bool,
+        #[clap(help = "Path to an hs home or its git checkout, a tarball, or a git URL")]
+        src: String,
+    },
+    #[clap(
+        about = "Generate systemd user units, a service and a timer for each schedule of the scripts"
+    )]
+    ExportUnits {
+        #[clap(long, help = "Where to put the units, e.g. ~/.config/systemd/user")]
+        dir: PathBuf,
+        #[clap(
+            long,
+            help = "Only report units that are missing, changed or whose schedule is removed"
+        )]
+        check: bool,
+        #[clap(required = true, min_values = 1, help = LIST_QUERY_HELP)]
+        queries: Vec<ListQuery>,
+    },
+    #[clap(about = "Manage scheduled runs of scripts")]
+    Schedule {
+        #[clap(subcommand)]
+        subcmd: Schedule,
+    },
+    #[clap(
+        about = "Keep running and fire scheduled runs when they are due. Every run is recorded as a scheduled one."
+    )]
+    Scheduler {
+        #[clap(
+            long,
+            help = "Fire the due runs once and exit, e.g. from cron or a systemd timer"
+        )]
+        once: bool,
+    },
+    #[clap(
+        about = "Pick lines from standard input with the built-in picker, and print the picked ones"
+    )]
+    Pick {
+        #[clap(long, short, help = "Allow picking several lines with tab")]
+        multi: bool,
+        #[clap(long, short, help = "Initial search query")]
+        query: Option<String>,
+        #[clap(
+            long,
+            help = "Extra ctrl key to confirm with, e.g. `w:wait` for ctrl-w. With any of them, the label of the key used (empty for enter) is printed first"
+        )]
+        bind: Vec<PickBinding>,
+    },
+}
+
+pub const CONFLICT_POLICIES: &[&str] = &["skip", "rename", "overwrite"];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub enum ConflictPolicy {
+    Skip,
+    Rename,
+    Overwrite,
+}
+impl FromStr for ConflictPolicy {
+    type Err = DisplayError;
+    fn from_str(s: &str) -> DisplayResult<Self> {
+        let p = match s {
+            "skip" => ConflictPolicy::Skip,
+            "rename" => ConflictPolicy::Rename,
+            "overwrite" => ConflictPolicy::Overwrite,
+            _ => unreachable!(),
+        };
+        Ok(p)
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
@@ -334,6 +527,125 @@ impl FromStr for HistoryDisplay {
     }
 }
 
+pub const BROWSE_ACTIONS: &[&str] = &["run", "amend", "humble", "rm", "copy"];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub enum BrowseAction {
+    Run,
+    Amend,
+    Humble,
+    RM,
+    Copy,
+}
+impl FromStr for BrowseAction {
+    type Err = DisplayError;
+    fn from_str(s: &str) -> DisplayResult<Self> {
+        let a = match s {
+            "run" => BrowseAction::Run,
+            "amend" => BrowseAction::Amend,
+            "humble" => BrowseAction::Humble,
+            "rm" => BrowseAction::RM,
+            "copy" => BrowseAction::Copy,
+            _ => unreachable!(),
+        };
+        Ok(a)
+    }
+}
+
+#[derive(Parser, Debug, Serialize)]
+pub enum ConfigSubs {
+    #[clap(about = "Show every effective config value")]
+    Show {
+        #[clap(long, help = "Also show the layer each value comes from")]
+        origin: bool,
+    },
+    #[clap(about = "Get a config value, e.g. `recent` or `alias.ll`")]
+    Get {
+        #[clap(
+            long,
+            possible_values(CONFIG_LAYERS),
+            help = "Read from this layer only, instead of the merged config"
+        )]
+        layer: Option<ConfigLayer>,
+        key: String,
+    },
+    #[clap(about = "Set a config value in a layer, keeping the rest of the file and its comments")]
+    Set {
+        #[clap(long, possible_values(CONFIG_LAYERS), default_value = "home")]
+        layer: ConfigLayer,
+        key: String,
+        #[clap(help = "A TOML value, or a plain string, e.g. `3`, `[\"nvim\"]` or `nvim`")]
+        value: String,
+    },
+    #[clap(
+        about = "Check every config layer: fields, templates, commands and aliases, with line and column of each problem"
+    )]
+    Check,
+}
+
+#[derive(Parser, Debug, Serialize)]
+pub enum Home {
+    #[clap(about = "List the global home and project homes found upwards from current directory")]
+    LS,
+    #[clap(about = "Show the hs home in use and where it comes from")]
+    Which,
+    #[clap(
+        about = "Redirect the global home to another hs home, or remove the redirection if no path is given"
+    )]
+    Switch { path: Option<String> },
+}
+
+#[derive(Parser, Debug, Serialize)]
+pub enum Schedule {
+    #[clap(about = "Run the script periodically")]
+    Add {
+        #[clap(
+            long,
+            help = "Cron expression in local time: minute hour day month weekday, e.g. `*/5 * * * *`"
+        )]
+        cron: CronExpr,
+        #[clap(help = SCRIPT_QUERY_HELP)]
+        script_query: ScriptQuery,
+        #[clap(last = true, help = "Command line args to pass to the script")]
+        args: Vec<String>,
+    },
+    #[clap(about = "List the schedules")]
+    LS,
+    #[clap(about = "Remove schedules by their ids")]
+    RM {
+        #[clap(required = true, min_values = 1)]
+        ids: Vec<i64>,
+    },
+}
+
+#[derive(Parser, Debug, Serialize)]
+pub enum Vcs {
+    #[clap(
+        about = "Commit all changes of hs home, with the database dumped into `.script_info.sql`"
+    )]
+    Commit {
+        #[clap(
+            long,
+            short,
+            help = "Commit message, generated from the changed scripts by default"
+        )]
+        message: Option<String>,
+    },
+    #[clap(about = "Show the commits of hs home, or of a single script")]
+    Log {
+        #[clap(long, short, default_value = "20")]
+        limit: usize,
+        #[clap(help = SCRIPT_QUERY_HELP)]
+        script_query: Option<ScriptQuery>,
+    },
+    #[clap(about = "Restore hs home, or a single script, to a past commit")]
+    Restore {
+        commit: String,
+        #[clap(help = SCRIPT_QUERY_HELP)]
+        script_query: Option<ScriptQuery>,
+    },
+}
+
 #[derive(Parser, Debug, Serialize)]
 pub enum History {
     RM {
@@ -375,6 +687,13 @@ pub enum History {
         dir: Option<PathBuf>,
         #[clap(long, possible_values(&["all", "env", "args"]), default_value = "args",)]
         display: HistoryDisplay,
+        #[clap(long, possible_values(FORMAT_VALUES), default_value = "human", help = FORMAT_HELP)]
+        format: OutputFormat,
+        #[clap(
+            long,
+            help = "Show exit status, duration and resource usage of the last run with these args."
+        )]
+        usage: bool,
     },
     Neglect {
         #[clap(required = true, min_values = 1, help = LIST_QUERY_HELP)]
@@ -394,31 +713,113 @@ pub enum History {
         args: Vec<String>,
     },
     Tidy,
+    #[clap(about = "Print the script exactly as it was executed")]
+    Content {
+        #[clap(long, help = "Run event ID, defaults to the latest run")]
+        run_id: Option<u64>,
+        #[clap(default_value = "-", help = SCRIPT_QUERY_HELP)]
+        script_query: ScriptQuery,
+    },
+    #[clap(about = "Show the hooks run after a script run, with their exit codes")]
+    Hooks {
+        #[clap(long, help = "Run event ID, defaults to the latest run")]
+        run_id: Option<u64>,
+        #[clap(default_value = "-", help = SCRIPT_QUERY_HELP)]
+        script_query: ScriptQuery,
+    },
+    #[clap(
+        about = "Browse the history interactively, then run, amend, humble, remove or copy the picked entries"
+    )]
+    Browse {
+        #[clap(default_value = "-", help = LIST_QUERY_HELP)]
+        queries: Vec<ListQuery>,
+        #[clap(short, long, default_value = "100")]
+        limit: u32,
+        #[clap(long)]
+        no_humble: bool,
+        #[clap(short, long)]
+        dir: Option<PathBuf>,
+        #[clap(
+            long,
+            possible_values(BROWSE_ACTIONS),
+            help = "Action to take on the picked entries. Defaults to `run`; other actions are also bound to ctrl keys in the picker."
+        )]
+        action: Option<BrowseAction>,
+        #[clap(
+            short,
+            long,
+            help = "Pick the N-th entry, 1 being the latest, instead of prompting"
+        )]
+        number: Vec<NonZeroUsize>,
+        #[clap(last = true, help = "New args for `amend`. Prompted for if not given.")]
+        args: Vec<String>,
+    },
+    #[clap(about = "Show the differences in args, env, dir and content between two runs")]
+    Diff {
+        #[clap(help = "Run event ID")]
+        event_a: u64,
+        #[clap(help = "Run event ID")]
+        event_b: u64,
+    },
 }
 
 #[derive(Parser, Debug, Serialize, Default)]
 #[clap(args_override_self = true)]
 pub struct List {
-    // TODO: 滿滿的其它排序/篩選選項
     #[clap(short, long, help = "Show verbose information.")]
     pub long: bool,
     #[clap(long, possible_values(&["tag", "tree", "none"]), default_value = "tag", help = "Grouping style.")]
     pub grouping: Grouping,
     #[clap(long, help = "Limit the amount of scripts found.")]
     pub limit: Option<NonZeroUsize>,
+    #[clap(
+        long,
+        possible_values(SORT_VALUES),
+        default_value = "recent",
+        help = "Sort scripts by the given key."
+    )]
+    pub sort: SortKey,
+    #[clap(long, help = "Reverse the sorting order.")]
+    pub reverse: bool,
+    #[clap(long, help = "Only show scripts whose last execution failed.")]
+    pub failed: bool,
+    #[clap(
+        long,
+        conflicts_with = "failed",
+        help = "Only show scripts never executed."
+    )]
+    pub never_run: bool,
+    #[clap(long, help = CREATED_SINCE_HELP)]
+    pub created_since: Option<TimeQuery>,
+    #[clap(long, help = WRITTEN_SINCE_HELP)]
+    pub written_since: Option<TimeQuery>,
     #[clap(long, help = "No color and other decoration.")]
     pub plain: bool,
     #[clap(long, help = "Show file path to the script.", conflicts_with = "long")]
     pub file: bool,
     #[clap(long, help = "Show name of the script.", conflicts_with = "long")]
     pub name: bool,
+    #[clap(
+        long,
+        help = "Explain why each script passes or fails the filter.",
+        conflicts_with_all = &["long", "file", "name"]
+    )]
+    pub explain_filter: bool,
+    #[clap(
+        long,
+        possible_values(FORMAT_VALUES),
+        default_value = "human",
+        conflicts_with = "explain-filter",
+        help = FORMAT_HELP
+    )]
+    pub format: OutputFormat,
     #[clap(help = LIST_QUERY_HELP)]
     pub queries: Vec<ListQuery>,
 }
 
-fn set_home(p: &Option<String>, create_on_missing: bool) -> Result {
-    path::set_home(p.as_ref(), create_on_missing)?;
-    Config::init()
+fn set_home(root_args: &RootArgs, create_on_missing: bool) -> Result {
+    path::set_home(root_args.hs_home.as_ref(), create_on_missing)?;
+    Config::init_with(&root_args.config)
 }
 
 fn print_help<S: AsRef<str>>(cmds: impl IntoIterator<Item = S>) {
@@ -455,12 +856,17 @@ fn handle_alias_args(args: Vec<String>) -> Result<ArgsResult> {
         Ok(alias_root) if alias_root.root_args.no_alias => {
             log::debug!("不使用別名！");
             let root = map_clap_res!(Root::try_parse_from(args));
-            return Ok(ArgsResult::Normal(root));
+            return Ok(ArgsResult::Normal(Box::new(root)));
+        }
+        Ok(alias_root) if alias_root.is_config_check() => {
+            log::debug!("檢查設定檔，不載入設定檔也不使用別名");
+            let root = map_clap_res!(Root::try_parse_from(args));
+            Ok(ArgsResult::Normal(Box::new(root)))
         }
         Ok(alias_root) => {
             log::info!("別名命令行物件 {:?}", alias_root);
-            set_home(&alias_root.root_args.hs_home, true)?;
-            let mut root = match alias_root.expand_alias(&args, Config::get()) {
+            set_home(&alias_root.root_args, true)?;
+            let mut root = match alias_root.expand_alias(&args, Config::get())? {
                 Some(Either::One(new_args)) => map_clap_res!(Root::try_parse_from(new_args)),
                 Some(Either::Two(new_args)) => {
                     return Ok(ArgsResult::Shell(new_args));
@@ -468,7 +874,7 @@ fn handle_alias_args(args: Vec<String>) -> Result<ArgsResult> {
                 None => map_clap_res!(Root::try_parse_from(&args)),
             };
             root.is_from_alias = true;
-            Ok(ArgsResult::Normal(root))
+            Ok(ArgsResult::Normal(Box::new(root)))
         }
         Err(e) => {
             log::warn!(
@@ -485,10 +891,19 @@ impl Root {
     /// 若帶了 --no-alias 選項，或是補全模式，我們可以把設定腳本之家（以及載入設定檔）的時間再推遲
     /// 在補全模式中意義重大，因為使用者可能會用 -H 指定別的腳本之家
     pub fn set_home_unless_from_alias(&self, create_on_missing: bool) -> Result {
-        if !self.is_from_alias {
-            set_home(&self.root_args.hs_home, create_on_missing)?;
+        if self.is_from_alias {
+            return Ok(());
         }
-        Ok(())
+        if matches!(
+            self.subcmd,
+            Some(Subs::Config {
+                subcmd: ConfigSubs::Check
+            })
+        ) {
+            // 設定檔可能是壞的，只設定腳本之家，留給 `config_check` 自行載入
+            return path::set_home(self.root_args.hs_home.as_ref(), false);
+        }
+        set_home(&self.root_args, create_on_missing)
     }
     pub fn sanitize_flags(&mut self, bang: bool) {
         if bang {
@@ -536,7 +951,7 @@ impl Root {
 }
 
 pub enum ArgsResult {
-    Normal(Root),
+    Normal(Box<Root>),
     Completion(Completion),
     Shell(Vec<String>),
     Err(ClapError),
@@ -563,7 +978,7 @@ mod test {
             .map(|s| s.to_owned())
             .collect();
         match handle_args(v).unwrap() {
-            ArgsResult::Normal(root) => Ok(root),
+            ArgsResult::Normal(root) => Ok(*root),
             ArgsResult::Err(err) => Err(err),
             _ => panic!(),
         }
@@ -583,15 +998,32 @@ mod test {
         match &args.subcmd {
             Some(Subs::Alias {
                 unset,
+                explain,
                 after,
                 before: Some(before),
             }) => {
-                assert_eq!(*unset, false);
+                assert!(!*unset);
+                assert!(!*explain);
                 assert_eq!(before, "trash");
                 assert_eq!(after, &["-s", "remove"]);
             }
             _ => panic!("{:?} should be alias...", args),
         }
+
+        let args = build_args("alias --explain deploy -s prod");
+        match &args.subcmd {
+            Some(Subs::Alias {
+                explain,
+                after,
+                before: Some(before),
+                ..
+            }) => {
+                assert!(*explain);
+                assert_eq!(before, "deploy");
+                assert_eq!(after, &["-s", "prod"]);
+            }
+            _ => panic!("{:?} should be alias...", args),
+        }
     }
     #[test]
     fn test_displaced_no_alias() {
@@ -700,9 +1132,12 @@ mod test {
                 error_no_previous: false,
                 repeat: Some(42),
                 dir: None,
+                env,
+                at: None,
                 script_query,
                 args,
             }) => {
+                assert!(env.is_empty());
                 assert_eq!(script_query, "=script".parse().unwrap());
                 assert_eq!(args, vec!["-a", "--"]);
             }
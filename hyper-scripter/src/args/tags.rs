@@ -1,3 +1,5 @@
+use super::help_str::*;
+use crate::output::{OutputFormat, FORMAT_VALUES};
 use crate::tag::TagSelector;
 use clap::{Error as ClapError, Parser};
 use serde::Serialize;
@@ -26,6 +28,8 @@ pub enum TagsSubs {
         known: bool,
         #[clap(long, short, conflicts_with = "known")]
         named: bool,
+        #[clap(long, possible_values(FORMAT_VALUES), default_value = "human", help = FORMAT_HELP, conflicts_with_all = &["known", "named"])]
+        format: OutputFormat,
     },
     Toggle {
         names: Vec<String>,
@@ -39,6 +43,7 @@ impl Tags {
                 self.subcmd = Some(TagsSubs::LS {
                     named: false,
                     known: false,
+                    format: OutputFormat::Human,
                 })
             }
             Some(TagsSubs::Other(args)) => {
@@ -1,4 +1,5 @@
 use super::help_str::*;
+use crate::output::{OutputFormat, FORMAT_VALUES};
 use crate::script_type::ScriptFullType;
 use clap::{Error as ClapError, Parser};
 use serde::Serialize;
@@ -17,6 +18,8 @@ pub enum TypesSubs {
     LS {
         #[clap(long)]
         no_sub: bool,
+        #[clap(long, possible_values(FORMAT_VALUES), default_value = "human", help = FORMAT_HELP)]
+        format: OutputFormat,
     },
     Template {
         #[clap(long, short)]
@@ -29,7 +32,12 @@ pub enum TypesSubs {
 impl Types {
     pub fn sanitize(&mut self) -> Result<(), ClapError> {
         match self.subcmd.as_ref() {
-            None => self.subcmd = Some(TypesSubs::LS { no_sub: false }),
+            None => {
+                self.subcmd = Some(TypesSubs::LS {
+                    no_sub: false,
+                    format: OutputFormat::Human,
+                })
+            }
             Some(TypesSubs::Other(args)) => {
                 let args = ["types", "template"]
                     .into_iter()
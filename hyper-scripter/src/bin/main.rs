@@ -1,20 +1,40 @@
+use chrono::{NaiveDateTime, Utc};
 use futures::future::try_join_all;
 use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use hyper_scripter::arg_schema::ArgSchema;
 use hyper_scripter::args::{
-    self, ArgsResult, History, HistoryDisplay, List, Root, Subs, Tags, TagsSubs, Types, TypesSubs,
+    self, ArgsResult, BrowseAction, ConfigSubs, History, HistoryDisplay, Home, List, Root, Subs,
+    Tags, TagsSubs, Types, TypesSubs, Vcs,
 };
+use hyper_scripter::collect;
 use hyper_scripter::config::{Config, NamedTagSelector};
+use hyper_scripter::config_check;
+use hyper_scripter::config_layer::{self, Layers};
 use hyper_scripter::db;
+use hyper_scripter::diff;
 use hyper_scripter::env_pair::EnvPair;
 use hyper_scripter::error::{Contextable, DisplayError, Error, ExitCode, RedundantOpt, Result};
+use hyper_scripter::export;
 use hyper_scripter::extract_msg::{extract_env_from_content, extract_help_from_content};
-use hyper_scripter::list::{fmt_list, DisplayIdentStyle, DisplayStyle, ListOptions};
+use hyper_scripter::home;
+use hyper_scripter::import;
+use hyper_scripter::list::{
+    fmt_explain_list, fmt_filter_explain, fmt_list, sort_scripts, DisplayIdentStyle, DisplayStyle,
+    ListFilter, ListOptions,
+};
 use hyper_scripter::my_env_logger;
+use hyper_scripter::output::{
+    write_record, write_records, HistoryRecord, LastRunRecord, NamedSelectorRecord, OutputFormat,
+    ScriptRecord, TagsRecord, TypeRecord,
+};
 use hyper_scripter::path;
+use hyper_scripter::picker::{self, Picker};
 use hyper_scripter::query::{self, EditQuery, ListQuery, ScriptOrDirQuery, ScriptQuery};
+use hyper_scripter::schedule;
 use hyper_scripter::script::{IntoScriptName, ScriptInfo, ScriptName};
 use hyper_scripter::script_repo::{RepoEntry, ScriptRepo, Visibility};
-use hyper_scripter::script_time::ScriptTime;
+use hyper_scripter::stats::{fmt_last_run, fmt_ms, fmt_stats, ScriptStats};
+use hyper_scripter::systemd;
 use hyper_scripter::tag::{Tag, TagSelector};
 use hyper_scripter::to_display_args;
 use hyper_scripter::util::{
@@ -23,7 +43,8 @@ use hyper_scripter::util::{
     main_util::{self, EditTagArgs},
     print_iter,
 };
-use hyper_scripter_historian::{Historian, LastTimeRecord};
+use hyper_scripter::vcs;
+use hyper_scripter_historian::{ExecRecord, ExecUsage, Historian};
 
 #[tokio::main]
 async fn main() {
@@ -41,9 +62,9 @@ async fn main() {
 }
 async fn main_err_handle(errs: &mut Vec<Error>) -> Result {
     let args: Vec<_> = std::env::args().collect();
-    let root = args::handle_args(args)?;
+    let root = args::handle_args(args.clone())?;
     let root = match root {
-        ArgsResult::Normal(root) => root,
+        ArgsResult::Normal(root) => *root,
         ArgsResult::Shell(shell) => std::process::exit(util::run_shell(&shell)?),
         ArgsResult::Completion(comp) => {
             let mut repo = None;
@@ -68,17 +89,59 @@ async fn main_err_handle(errs: &mut Vec<Error>) -> Result {
 
     root.set_home_unless_from_alias(true)?;
 
+    if matches!(
+        root.subcmd,
+        Some(Subs::Config {
+            subcmd: ConfigSubs::Check
+        })
+    ) {
+        return config_check::check(path::get_home(), &root.root_args.config);
+    }
+
     if matches!(root.subcmd, Some(Subs::Migrate)) {
         db::do_migrate(db::get_file()).await?;
         Historian::do_migrate(path::get_home()).await?;
         return Ok(());
     }
 
+    let auto_commit =
+        Config::get().auto_commit && root.subcmd.as_ref().is_some_and(main_util::need_write);
+    let layered = match &root.subcmd {
+        Some(Subs::Run { .. }) => Layered::Run,
+        Some(Subs::LS(List {
+            format,
+            explain_filter: false,
+            ..
+        })) if format.is_human() => Layered::LS,
+        _ => Layered::None,
+    };
+    let global = match layered {
+        Layered::None => None,
+        _ => home::layered_global(root.root_args.hs_home.as_ref())?,
+    };
+    if let (Some(_), Layered::LS) = (&global, &layered) {
+        println!("[project] {}", path::get_home().display()); // LOCALE
+    }
+
     let mut resource = Resource::None;
     let mut ret = MainReturn { conf: None, errs };
     let res = main_inner(root, &mut resource, &mut ret).await;
     resource.close().await; // 不論如何都要關閉資料庫！
-    res?;
+    match (global, layered, res) {
+        (Some(global), Layered::Run, Err(Error::ScriptNotFound(name))) => {
+            log::info!("專案腳本之家中找不到 {}，改找全域腳本之家", name);
+            std::process::exit(home::rerun_in(&global, &args[1..])?);
+        }
+        (Some(global), Layered::LS, res) => {
+            res?;
+            println!("[global] {}", global.display()); // LOCALE
+            let code = home::rerun_in(&global, &args[1..])?;
+            if code != 0 {
+                return Err(Error::ScriptError(code));
+            }
+        }
+        (_, _, res) => res?,
+    }
 
     if let Some(conf) = ret.conf {
         log::info!("存入改變後的設定檔");
@@ -87,9 +150,20 @@ async fn main_err_handle(errs: &mut Vec<Error>) -> Result {
         log::info!("存入憑空產生的設定檔");
         Config::get().store()?;
     }
+    if auto_commit {
+        log::info!("自動提交腳本之家");
+        vcs::commit(None).await?;
+    }
     Ok(())
 }
 
+/// 疊在全域腳本之家之上時，需要特殊處理的命令
+enum Layered {
+    None,
+    Run,
+    LS,
+}
+
 struct MainReturn<'a> {
     conf: Option<Config>,
     /// 用來裝那種不會馬上造成中止的錯誤，例如 ScriptError
@@ -102,6 +176,8 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
 
     let conf = Config::get();
     let need_journal = main_util::need_write(root.subcmd.as_ref().unwrap());
+    let hs_home = root.root_args.hs_home.clone();
+    let config_overrides = root.root_args.config.clone();
 
     let repo = RepoHolder {
         resource,
@@ -121,6 +197,22 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
             let repo = repo.init().await?;
             main_util::load_utils(repo, None).await?;
         }
+        Subs::Alias {
+            explain: true,
+            before: Some(before),
+            after,
+            ..
+        } => {
+            let words: Vec<_> = std::iter::once(before.clone()).chain(after).collect();
+            match conf.expand_alias(&words)? {
+                Some((true, args)) => println!("sh -c {}", to_display_args(&args.join(" "))),
+                Some((false, args)) => {
+                    print_iter(args.iter().map(|s| to_display_args(s)), " ");
+                    println!();
+                }
+                None => return Err(Error::NoAlias(before)),
+            }
+        }
         Subs::Alias {
             unset: false,
             before: Some(before),
@@ -265,7 +357,16 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
             let content = util::read_file(&script_path)?;
 
             let helps = extract_help_from_content(&content);
-            let has_help = print_iter(helps, "\n");
+            let mut has_help = print_iter(helps, "\n");
+
+            let schema = ArgSchema::from_content(&content)?;
+            if !schema.is_empty() {
+                if has_help {
+                    println!("\n");
+                }
+                print!("{}", schema.usage(&entry.name.key()));
+                has_help = true;
+            }
 
             let mut envs = extract_env_from_content(&content).peekable();
             if envs.peek().is_some() {
@@ -275,6 +376,25 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
                 print_iter(envs, "\n");
             }
         }
+        Subs::Run {
+            script_query,
+            args,
+            at: Some(at),
+            ..
+        } => {
+            let repo = repo.init().await?;
+            let pool = repo.info_pool().clone();
+            let entry = query::do_script_query_strict(&script_query, repo).await?;
+            main_util::confirm_caution(&entry, &args)?;
+            let time = at.to_utc_later();
+            let id = schedule::add(&pool, entry.id, None, &args, time).await?;
+            println!(
+                "{} {} at {}",
+                id,
+                entry.name,
+                schedule::fmt_local_time(time)
+            ); // LOCALE
+        }
         Subs::Run {
             script_query,
             dummy,
@@ -283,32 +403,49 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
             error_no_previous,
             repeat,
             dir,
+            env,
+            at: None,
         } => {
             let repo = repo.init().await?;
             let dir = util::option_map_res(dir, |d| path::normalize_path(d))?;
             let mut entry = query::do_script_query_strict(&script_query, repo).await?;
-            main_util::run_n_times(
-                repeat.unwrap_or(1),
+            let opts = main_util::RunOptions {
+                repeat: repeat.unwrap_or(1),
                 dummy,
-                &mut entry,
-                args,
-                &mut ret.errs,
-                previous,
+                use_previous: previous,
                 error_no_previous,
                 dir,
-            )
-            .await?;
+                env_overrides: env,
+            };
+            main_util::run_n_times(&mut entry, args, &mut ret.errs, opts).await?;
         }
-        Subs::Which { queries } => {
+        Subs::Which {
+            queries,
+            explain,
+            format,
+        } => {
             let repo = repo.init().await?;
             let home = path::get_home();
-            let mut scripts = query::do_list_query(repo, queries).await?;
+            let mut scripts = query::do_list_query(&mut *repo, queries).await?;
             scripts.sort_by_key(|s| std::cmp::Reverse(s.last_time()));
-            for entry in scripts.into_iter() {
-                log::info!("定位 {:?}", entry.name);
-                // NOTE: 不檢查存在與否
-                let p = home.join(entry.file_path_fallback());
-                println!("{}", p.to_string_lossy());
+            let scripts: Vec<ScriptInfo> = scripts
+                .into_iter()
+                .map(|e| e.into_inner().clone())
+                .collect();
+            if !format.is_human() {
+                write_script_records(repo, &scripts, format).await?;
+            } else {
+                let mut stdout = std::io::stdout();
+                let plain = !console::Term::stdout().features().is_attended();
+                for script in scripts.iter() {
+                    log::info!("定位 {:?}", script.name);
+                    // NOTE: 不檢查存在與否
+                    let p = home.join(script.file_path_fallback());
+                    println!("{}", p.to_string_lossy());
+                    if explain {
+                        fmt_filter_explain(&mut stdout, repo, script, plain)?;
+                    }
+                }
             }
         }
         Subs::Cat { queries, with } => {
@@ -335,7 +472,24 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
             }
         }
         Subs::Types(Types {
-            subcmd: Some(TypesSubs::LS { no_sub }),
+            subcmd: Some(TypesSubs::LS { no_sub, format }),
+        }) if !format.is_human() => {
+            let mut records = vec![];
+            for ty in conf.types.keys() {
+                let sub_types = if no_sub {
+                    vec![]
+                } else {
+                    path::get_sub_types(ty)?
+                };
+                records.push(TypeRecord {
+                    name: ty,
+                    sub_types,
+                });
+            }
+            write_records(&mut std::io::stdout().lock(), format, records)?;
+        }
+        Subs::Types(Types {
+            subcmd: Some(TypesSubs::LS { no_sub, .. }),
         }) => {
             let mut first = true;
             for ty in conf.types.keys() {
@@ -371,6 +525,48 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
                 println!("{}", conf.recent);
             }
         }
+        Subs::LS(List {
+            queries,
+            plain,
+            explain_filter: true,
+            ..
+        }) => {
+            let repo = repo.init().await?;
+            fmt_explain_list(&mut std::io::stdout().lock(), repo, plain, queries).await?;
+        }
+        Subs::LS(List {
+            limit,
+            queries,
+            format,
+            sort,
+            reverse,
+            failed,
+            never_run,
+            created_since,
+            written_since,
+            ..
+        }) if !format.is_human() => {
+            let repo = repo.init().await?;
+            let filter = ListFilter {
+                failed,
+                never_run,
+                created_since,
+                written_since,
+            };
+            let filter = filter.prepare(repo).await?;
+            let scripts = query::do_list_query(&mut *repo, queries).await?;
+            let mut scripts: Vec<_> = scripts
+                .into_iter()
+                .map(|e| e.into_inner())
+                .filter(|s| filter.check(s))
+                .collect();
+            sort_scripts(&mut scripts, sort, reverse);
+            if let Some(limit) = limit {
+                scripts.truncate(limit.get());
+            }
+            let scripts: Vec<ScriptInfo> = scripts.into_iter().cloned().collect();
+            write_script_records(repo, &scripts, format).await?;
+        }
         Subs::LS(List {
             long,
             grouping,
@@ -379,6 +575,13 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
             plain,
             name,
             file,
+            sort,
+            reverse,
+            failed,
+            never_run,
+            created_since,
+            written_since,
+            ..
         }) => {
             let display_style = match (long, file, name) {
                 (false, true, false) => DisplayStyle::Short(DisplayIdentStyle::File, ()),
@@ -392,6 +595,14 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
                 grouping: grouping.into(),
                 plain,
                 limit,
+                sort,
+                reverse,
+                filter: ListFilter {
+                    failed,
+                    never_run,
+                    created_since,
+                    written_since,
+                },
                 display_style,
             };
             let stdout = std::io::stdout();
@@ -512,6 +723,7 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
                 Some(TagsSubs::LS {
                     named: true,
                     known: false,
+                    ..
                 }),
         }) => {
             print_iter(conf.tag_selectors.iter().map(|f| &f.name), " ");
@@ -521,10 +733,27 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
                 Some(TagsSubs::LS {
                     named: false,
                     known,
+                    format,
                 }),
         }) => {
             let repo = repo.init().await?;
-            if known {
+            if !format.is_human() {
+                let record = TagsRecord {
+                    known_tags: known_tags_iter(repo).map(|t| t.as_ref()).collect(),
+                    caution_tags: conf.caution_tags.to_string(),
+                    selectors: conf
+                        .tag_selectors
+                        .iter()
+                        .map(|s| NamedSelectorRecord {
+                            name: &s.name,
+                            content: s.content.to_string(),
+                            inactivated: s.inactivated,
+                        })
+                        .collect(),
+                    main_selector: conf.main_tag_selector.to_string(),
+                };
+                write_record(&mut std::io::stdout().lock(), record)?;
+            } else if known {
                 print_iter(known_tags_iter(repo), " ");
             } else {
                 print!("known tags:\n  ");
@@ -598,26 +827,7 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
                     range.get_max(),
                 )
                 .await?;
-            // TODO: 測試多個腳本的狀況
-            for (entry, res) in scripts.iter_mut().zip(res_vec) {
-                // TODO: 平行？
-                if check_time_changed(&entry, &res) {
-                    log::debug!(
-                        "刪除後時間不同 {:?} {:?} {:?} v.s. {:?}",
-                        entry.exec_time,
-                        entry.exec_done_time,
-                        entry.humble_time,
-                        res
-                    );
-                    entry
-                        .update(|info| {
-                            info.exec_time = res.exec_time.map(|t| ScriptTime::new(t));
-                            info.exec_done_time = res.exec_done_time.map(|t| ScriptTime::new(t));
-                            info.humble_time = res.humble_time;
-                        })
-                        .await?;
-                }
-            }
+            main_util::update_last_times(&mut scripts, res_vec).await?;
         }
         Subs::History {
             subcmd: History::RMID { event_id },
@@ -662,6 +872,96 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
             let tidy_fut = id_vec.iter().map(|id| historian.tidy(*id));
             try_join_all(tidy_fut).await?;
             historian.clear_except_script_ids(&id_vec).await?;
+
+            let run_ids: HashSet<_> = historian.exec_ids(None).await?.into_iter().collect();
+            main_util::tidy_run_logs(&run_ids)?;
+        }
+        Subs::History {
+            subcmd:
+                History::Content {
+                    run_id,
+                    script_query,
+                },
+        } => {
+            let repo = repo.init().await?;
+            let entry = query::do_script_query_strict(&script_query, repo).await?;
+            let event = main_util::get_run_event(&entry, run_id).await?;
+            print!("{}", main_util::get_run_content(&event)?);
+        }
+        Subs::History {
+            subcmd:
+                History::Hooks {
+                    run_id,
+                    script_query,
+                },
+        } => {
+            let repo = repo.init().await?;
+            let historian = repo.historian().clone();
+            let entry = query::do_script_query_strict(&script_query, repo).await?;
+            let event = main_util::get_run_event(&entry, run_id).await?;
+            for hook in historian.hook_records(event.id).await?.into_iter() {
+                let outcome = format!("exit {}, {}", hook.code, fmt_ms(hook.elapsed_ms));
+                println!("{}\t{}\t{}", hook.hook, outcome, hook.cmd); // LOCALE
+            }
+        }
+        Subs::History {
+            subcmd: History::Diff { event_a, event_b },
+        } => {
+            let historian = repo.historian().await?;
+            let mut events = vec![];
+            for id in [event_a as i64, event_b as i64] {
+                match historian.exec_event(id).await? {
+                    Some(event) => events.push(event),
+                    None => return Err(Error::NoRunEvent(id)),
+                }
+            }
+            let mut stdout = std::io::stdout().lock();
+            diff::write_event_diff(&mut stdout, &events[0], &events[1])?;
+        }
+        Subs::History {
+            subcmd:
+                History::Browse {
+                    queries,
+                    limit,
+                    no_humble,
+                    dir,
+                    action,
+                    number,
+                    args,
+                },
+        } => {
+            let repo = repo.init().await?;
+            let historian = repo.historian().clone();
+            let dir = util::option_map_res(dir, |d| path::normalize_path(d))?;
+            let mut scripts = query::do_list_query(repo, queries).await?;
+            let ids: Vec<_> = scripts.iter().map(|s| s.id).collect();
+            let list =
+                main_util::load_browse_records(&historian, &ids, limit, no_humble, dir).await?;
+            if list.records.is_empty() {
+                return Err(Error::EmptyHistory);
+            }
+
+            let default_action = action.unwrap_or(BrowseAction::Run);
+            let (action, picked) = if number.is_empty() {
+                match main_util::pick_browse_records(&scripts, &list.records, default_action)? {
+                    Some(picked) => picked,
+                    None => {
+                        log::info!("取消瀏覽歷史");
+                        return Ok(());
+                    }
+                }
+            } else {
+                let mut picked = vec![];
+                for n in number.into_iter() {
+                    if n.get() > list.records.len() {
+                        return Err(Error::NoHistoryEntry(n.get()));
+                    }
+                    picked.push(n.get() - 1);
+                }
+                (default_action, picked)
+            };
+            main_util::browse_history(&mut scripts, &list, picked, action, args, &mut ret.errs)
+                .await?;
         }
         Subs::History {
             subcmd: History::Neglect { queries },
@@ -682,6 +982,8 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
                     offset,
                     display,
                     dir,
+                    format,
+                    usage,
                 },
         } => {
             let repo = repo.init().await?;
@@ -718,7 +1020,28 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
             }
 
             let mut script_getter = ScriptGetter::new(&ids, repo)?;
-            let mut print_basic = |script_id: i64, args: Vec<String>| -> Result {
+            let mut records = vec![];
+            let mut print_one = |event_id: i64,
+                                 time: NaiveDateTime,
+                                 script_id: i64,
+                                 args: Option<Vec<String>>,
+                                 envs: Option<Vec<EnvPair>>,
+                                 last_run: Option<(i32, Option<ExecUsage>)>|
+             -> Result {
+                if !format.is_human() {
+                    let name = script_getter.get(script_id)?.name.key().to_string();
+                    records.push(HistoryRecord {
+                        event_id,
+                        time,
+                        script_id,
+                        name,
+                        args,
+                        envs,
+                        last_run: last_run.map(|(code, usage)| LastRunRecord::new(code, usage)),
+                    });
+                    return Ok(());
+                }
+                let args = args.unwrap_or_default();
                 if with_name {
                     let info = script_getter.get(script_id)?;
                     print!("{}", info.name.key());
@@ -728,6 +1051,12 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
                 }
                 print_iter(args.iter().map(|s| to_display_args(s)), " ");
                 println!("");
+                if let Some((code, usage)) = last_run {
+                    println!("  ({})", fmt_last_run(code, usage.as_ref()));
+                }
+                for p in envs.into_iter().flatten() {
+                    println!("  {}", p);
+                }
                 Ok(())
             };
 
@@ -742,27 +1071,37 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
                             dir.as_deref(),
                         )
                         .await?;
-                    for (script_id, args, envs) in args_list {
+                    for (event_id, time, script_id, args, envs) in args_list {
                         log::debug!("嘗試打印參數 {} {} {}", script_id, args, envs);
+                        let last_run = if usage {
+                            historian.last_usage(script_id, &args).await?
+                        } else {
+                            None
+                        };
                         let args: Vec<String> = serde_json::from_str(&args)?;
                         let envs: Vec<EnvPair> = serde_json::from_str(&envs)?;
-                        print_basic(script_id, args)?;
-                        for p in envs.into_iter() {
-                            println!("  {}", p);
-                        }
+                        print_one(event_id, time, script_id, Some(args), Some(envs), last_run)?;
                     }
                 }
                 HistoryDisplay::Args => {
                     let args_list = historian
                         .previous_args_list(&ids, limit, offset, no_humble, dir.as_deref())
                         .await?;
-                    for (script_id, args) in args_list {
+                    for (event_id, time, script_id, args) in args_list {
                         log::debug!("嘗試打印參數 {} {}", script_id, args);
+                        let last_run = if usage {
+                            historian.last_usage(script_id, &args).await?
+                        } else {
+                            None
+                        };
                         let args: Vec<String> = serde_json::from_str(&args)?;
-                        print_basic(script_id, args)?;
+                        print_one(event_id, time, script_id, Some(args), None, last_run)?;
                     }
                 }
                 HistoryDisplay::Env => {
+                    if usage {
+                        log::warn!("只顯示環境變數時無法對應執行紀錄，忽略 --usage");
+                    }
                     let args_list = historian
                         .previous_args_list_only_envs(
                             &ids,
@@ -772,17 +1111,375 @@ async fn main_inner(root: Root, resource: &mut Resource, ret: &mut MainReturn<'_
                             dir.as_deref(),
                         )
                         .await?;
-                    for (script_id, envs) in args_list {
+                    for (event_id, time, script_id, envs) in args_list {
                         log::debug!("嘗試打印參數 {} {}", script_id, envs);
                         let envs: Vec<EnvPair> = serde_json::from_str(&envs)?;
-                        print_basic(script_id, vec![])?;
-                        for p in envs.into_iter() {
-                            println!("  {}", p);
+                        print_one(event_id, time, script_id, None, Some(envs), None)?;
+                    }
+                }
+            }
+            if !format.is_human() {
+                write_records(&mut std::io::stdout().lock(), format, records)?;
+            }
+        }
+        Subs::Diff {
+            from_run,
+            to_run,
+            script_query,
+        } => {
+            let repo = repo.init().await?;
+            let entry = query::do_script_query_strict(&script_query, repo).await?;
+            let name = entry.name.key();
+            let from = main_util::get_run_event(&entry, from_run).await?;
+            let from_header = format!("{} (run {})", name, from.id);
+            let (to_content, to_header) = match to_run {
+                Some(to_run) => {
+                    let to = main_util::get_run_event(&entry, Some(to_run)).await?;
+                    let content = main_util::get_run_content(&to)?.to_owned();
+                    (content, format!("{} (run {})", name, to.id))
+                }
+                None => {
+                    let p = path::open_script(&entry.name, &entry.ty, Some(true))?;
+                    (util::read_file(&p)?, format!("{} (current)", name))
+                }
+            };
+            let mut stdout = std::io::stdout().lock();
+            diff::write_unified(
+                &mut stdout,
+                main_util::get_run_content(&from)?,
+                &to_content,
+                &from_header,
+                &to_header,
+            )?;
+        }
+        Subs::Restore {
+            run_id,
+            previous,
+            force,
+            to,
+            script_query,
+        } => {
+            let repo = repo.init().await?;
+            let new_name = match to {
+                Some(to) => {
+                    let og = ListQuery::Query(script_query.clone());
+                    let mut pairs = create_dir_pair(repo, og, to).await?;
+                    pairs.pop().map(|(_, new_name)| new_name)
+                }
+                None => None,
+            };
+            let previous = previous.map(|n| n.get());
+            main_util::restore(repo, &script_query, run_id, previous, force, new_name).await?;
+        }
+        Subs::Config {
+            subcmd: ConfigSubs::Show { origin },
+        } => {
+            let layers = Layers::load(path::get_home(), &config_overrides)?;
+            for (key, value) in layers.flatten().into_iter() {
+                match layers.origins.get(&key).filter(|_| origin) {
+                    Some(origin) => println!("{} = {}  # {}", key, value, origin),
+                    None => println!("{} = {}", key, value),
+                }
+            }
+        }
+        Subs::Config {
+            subcmd: ConfigSubs::Get { layer, key },
+        } => {
+            let home = path::get_home();
+            let value = match layer {
+                Some(layer) => config_layer::get_in_layer(layer, home, &key)?,
+                None => Layers::load(home, &config_overrides)?.get(&key).cloned(),
+            };
+            let value = value.ok_or_else(|| Error::NoConfigKey(key))?;
+            println!("{}", config_layer::fmt_value(&value));
+        }
+        Subs::Config {
+            subcmd: ConfigSubs::Set { layer, key, value },
+        } => config_layer::set(layer, path::get_home(), &key, &value)?,
+        Subs::Home { subcmd: Home::LS } => home::ls()?,
+        Subs::Home {
+            subcmd: Home::Which,
+        } => home::which(hs_home.as_ref())?,
+        Subs::Home {
+            subcmd: Home::Switch { path },
+        } => home::switch(path.as_deref())?,
+        Subs::Vcs {
+            subcmd: Vcs::Commit { message },
+        } => {
+            vcs::commit(message.as_deref()).await?;
+        }
+        Subs::Vcs {
+            subcmd: Vcs::Log {
+                limit,
+                script_query,
+            },
+        } => {
+            let file = match script_query {
+                Some(script_query) => {
+                    let repo = repo.init().await?;
+                    let entry = query::do_script_query_strict(&script_query, repo).await?;
+                    Some(entry.name.to_file_path_fallback(&entry.ty).0)
+                }
+                None => None,
+            };
+            vcs::log(file.as_deref(), limit)?;
+        }
+        Subs::Vcs {
+            subcmd:
+                Vcs::Restore {
+                    commit,
+                    script_query,
+                },
+        } => {
+            let file = match script_query {
+                Some(script_query) => {
+                    let repo = repo.init().await?;
+                    let entry = query::do_script_query_strict(&script_query, repo).await?;
+                    Some(entry.name.to_file_path_fallback(&entry.ty).0)
+                }
+                None => None,
+            };
+            vcs::restore(&commit, file.as_deref()).await?;
+        }
+        Subs::Collect { apply, interactive } => {
+            let repo = repo.init().await?;
+            for finding in collect::detect(repo)?.into_iter() {
+                if interactive {
+                    if !util::prompt(format!("{}, apply?", finding), false)? {
+                        continue;
+                    }
+                } else {
+                    println!("{}", finding);
+                    if !apply {
+                        continue;
+                    }
+                }
+                collect::apply(repo, finding).await?;
+            }
+        }
+        Subs::Schedule {
+            subcmd:
+                args::Schedule::Add {
+                    cron,
+                    script_query,
+                    args,
+                },
+        } => {
+            let repo = repo.init().await?;
+            let pool = repo.info_pool().clone();
+            let entry = query::do_script_query_strict(&script_query, repo).await?;
+            main_util::confirm_caution(&entry, &args)?;
+            let time = schedule::next_time(&cron, Utc::now().naive_utc())
+                .ok_or_else(|| Error::msg(format!("`{}` never happens", cron)))?;
+            let id = schedule::add(&pool, entry.id, Some(&cron), &args, time).await?;
+            println!(
+                "{} {} next at {}",
+                id,
+                entry.name,
+                schedule::fmt_local_time(time)
+            ); // LOCALE
+        }
+        Subs::Schedule {
+            subcmd: args::Schedule::LS,
+        } => {
+            let repo = repo.init().await?;
+            let schedules = schedule::load_all(repo.info_pool()).await?;
+            for s in schedules.into_iter() {
+                let name = match repo.get_mut_by_id(s.script_id) {
+                    Some(entry) => entry.name.to_string(),
+                    None => format!("<removed {}>", s.script_id), // LOCALE
+                };
+                let cron = s.cron.map_or("once".to_owned(), |c| c.to_string());
+                let time = schedule::fmt_local_time(s.next_time);
+                print!("{}\t{}\t{}\t{}", s.id, cron, time, name);
+                for arg in s.args.iter() {
+                    print!(" {}", to_display_args(arg));
+                }
+                println!();
+            }
+        }
+        Subs::Schedule {
+            subcmd: args::Schedule::RM { ids },
+        } => {
+            let repo = repo.init().await?;
+            for id in ids.into_iter() {
+                if !schedule::remove(repo.info_pool(), id).await? {
+                    return Err(Error::NoSchedule(id));
+                }
+            }
+        }
+        Subs::Scheduler { once: true } => {
+            let repo = repo.init().await?;
+            repo.scheduled();
+            schedule::run_due(repo, ret.errs).await?;
+        }
+        Subs::Scheduler { once: false } => {
+            log::info!("啟動排程器");
+            schedule::daemon().await?;
+        }
+        Subs::Pick { multi, query, bind } => {
+            use std::io::BufRead;
+            let items = std::io::stdin()
+                .lock()
+                .lines()
+                .collect::<std::io::Result<Vec<_>>>()?;
+            if items.is_empty() {
+                return Err(Error::NothingToPick);
+            }
+            if !picker::is_available() {
+                return Err(Error::NoTerminal);
+            }
+            let mut p = Picker::new(items.clone(), hyper_scripter::SEP).multi(multi);
+            if let Some(query) = query {
+                p = p.query(query);
+            }
+            for b in bind.iter() {
+                p = p.bind(b.key, b.label.clone());
+            }
+            if let Some(picked) = p.pick_with_binding()? {
+                if !bind.is_empty() {
+                    let label = picked.binding.map_or("", |i| bind[i].label.as_str());
+                    println!("{}", label);
+                }
+                for i in picked.indices.into_iter() {
+                    println!("{}", items[i]);
+                }
+            }
+        }
+        Subs::ExportUnits {
+            dir,
+            check,
+            queries,
+        } => {
+            let repo = repo.init().await?;
+            let dir = path::normalize_path(dir)?;
+            let schedules = schedule::load_all(repo.info_pool()).await?;
+            let schedule_ids: HashSet<_> = schedules.iter().map(|s| s.id).collect();
+            let scripts: Vec<ScriptInfo> = query::do_list_query(repo, queries)
+                .await?
+                .into_iter()
+                .map(|e| e.into_inner().clone())
+                .collect();
+            let mut units = vec![];
+            for s in schedules.iter() {
+                if let Some(info) = scripts.iter().find(|info| info.id == s.script_id) {
+                    units.extend(systemd::gen_units(info, s)?);
+                }
+            }
+            let drifts = systemd::check(&dir, &units, &schedule_ids)?;
+            if check {
+                print_iter(drifts.iter(), "\n");
+                if !drifts.is_empty() {
+                    println!();
+                    return Err(Error::UnitDrift(drifts.len()));
+                }
+            } else {
+                systemd::write(&dir, &units, &drifts)?;
+                for drift in drifts.iter() {
+                    match drift {
+                        systemd::Drift::Missing(p) | systemd::Drift::Changed(p) => {
+                            println!("write     {}", p.display()); // LOCALE
                         }
+                        _ => println!("{}", drift),
                     }
                 }
             }
         }
+        Subs::Export {
+            output,
+            with_history,
+            queries,
+        } => {
+            let repo = repo.init().await?;
+            let scripts: Vec<ScriptInfo> = query::do_list_query(repo, queries)
+                .await?
+                .into_iter()
+                .map(|e| e.into_inner().clone())
+                .collect();
+            export::export(scripts, &output, with_history).await?;
+        }
+        Subs::Import {
+            namespace,
+            on_conflict,
+            no_history,
+            src,
+        } => {
+            let repo = repo.init().await?;
+            import::import(repo, &src, namespace.as_deref(), on_conflict, no_history).await?;
+        }
+        Subs::Log {
+            run_id,
+            follow,
+            script_query,
+        } => {
+            let repo = repo.init().await?;
+            let historian = repo.historian().clone();
+            let entry = query::do_script_query_strict(&script_query, repo).await?;
+            let run_ids = historian.exec_ids(Some(entry.id)).await?;
+            let run_id = match run_id {
+                Some(run_id) => {
+                    let run_id = run_id as i64;
+                    if !run_ids.contains(&run_id) || !path::get_run_log(run_id)?.exists() {
+                        let name = entry.name.key().to_string();
+                        return Err(Error::NoRunLog(name, Some(run_id)));
+                    }
+                    run_id
+                }
+                None => {
+                    let mut found = None;
+                    for run_id in run_ids.into_iter() {
+                        if path::get_run_log(run_id)?.exists() {
+                            found = Some(run_id);
+                            break;
+                        }
+                    }
+                    match found {
+                        Some(run_id) => run_id,
+                        None => return Err(Error::NoRunLog(entry.name.key().to_string(), None)),
+                    }
+                }
+            };
+            main_util::print_run_log(run_id, follow)?;
+        }
+        Subs::Stats {
+            since,
+            format,
+            queries,
+        } => {
+            let repo = repo.init().await?;
+            let historian = repo.historian().clone();
+            let scripts: Vec<ScriptInfo> = query::do_list_query(repo, queries)
+                .await?
+                .into_iter()
+                .map(|e| e.into_inner().clone())
+                .collect();
+            let ids: Vec<_> = scripts.iter().map(|s| s.id).collect();
+            let since = since.map(|t| t.to_utc());
+            let records = historian.exec_records(&ids, since).await?;
+            let mut record_map: HashMap<i64, Vec<&ExecRecord>> = Default::default();
+            for record in records.iter() {
+                record_map.entry(record.script_id).or_default().push(record);
+            }
+
+            let now = Utc::now().naive_utc();
+            let mut stats: Vec<_> = scripts
+                .iter()
+                .filter_map(|s| {
+                    let records = record_map.get(&s.id)?;
+                    Some(ScriptStats::new(s, records, since, now))
+                })
+                .collect();
+            stats.sort_by_key(|s| std::cmp::Reverse(s.total_runs));
+
+            let mut stdout = std::io::stdout().lock();
+            if format.is_human() {
+                for s in stats.iter() {
+                    fmt_stats(&mut stdout, s)?;
+                }
+            } else {
+                write_records(&mut stdout, format, stats)?;
+            }
+        }
         Subs::Top { id, queries, wait } => {
             let script_id_set: Option<HashSet<_>> = if queries.is_empty() {
                 None
@@ -823,6 +1520,23 @@ async fn create_read_event(entry: &mut RepoEntry<'_>) -> Result<i64> {
     entry.update(|info| info.read()).await
 }
 
+async fn write_script_records(
+    repo: &mut ScriptRepo,
+    scripts: &[ScriptInfo],
+    format: OutputFormat,
+) -> Result {
+    let exit_codes: HashMap<_, _> = repo
+        .historian()
+        .last_exit_codes()
+        .await?
+        .into_iter()
+        .collect();
+    let records = scripts
+        .iter()
+        .map(|s| ScriptRecord::new(s, exit_codes.get(&s.id).copied()));
+    write_records(&mut std::io::stdout().lock(), format, records)
+}
+
 fn known_tags_iter<'a>(repo: &'a mut ScriptRepo) -> impl Iterator<Item = &'a Tag> {
     use std::collections::hash_map::Entry::*;
 
@@ -865,17 +1579,6 @@ async fn process_event_by_id<'a>(is_humble: bool, repo: RepoHolder<'a>, event_id
     Ok(())
 }
 
-fn check_time_changed(entry: &RepoEntry<'_>, ignrore_res: &LastTimeRecord) -> bool {
-    let s_exec_time = entry.exec_time.as_ref().map(|t| **t);
-    let s_exec_done_time = entry.exec_done_time.as_ref().map(|t| **t);
-    (s_exec_time, s_exec_done_time, entry.humble_time)
-        != (
-            ignrore_res.exec_time,
-            ignrore_res.exec_done_time,
-            ignrore_res.humble_time,
-        )
-}
-
 async fn create_dir_pair(
     repo: &mut ScriptRepo,
     og: ListQuery,
@@ -0,0 +1,193 @@
+//! 比對腳本之家中的檔案與資料庫，找出沒有檔案的腳本、沒被記錄的檔案，以及檔案被搬走的腳本
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::path;
+use crate::script::{IntoScriptName, ScriptInfo, ScriptName, ANONYMOUS};
+use crate::script_repo::{ScriptRepo, Visibility};
+use crate::script_type::ScriptType;
+use crate::util::{self, handle_fs_res};
+use fxhash::FxHashSet as HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum Finding {
+    /// 資料庫中有記錄，卻找不到檔案
+    Orphaned {
+        id: i64,
+        name: ScriptName,
+        path: PathBuf,
+    },
+    /// 有檔案，資料庫中卻沒有記錄
+    Untracked {
+        name: ScriptName,
+        ty: ScriptType,
+        path: PathBuf,
+    },
+    /// 檔案被搬到別處，且只有一個同名同類型的檔案可能是它
+    Moved {
+        id: i64,
+        name: ScriptName,
+        new_name: ScriptName,
+    },
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // LOCALE
+        match self {
+            Finding::Orphaned { name, path, .. } => {
+                write!(f, "orphaned   {} ({} not found)", name, path.display())
+            }
+            Finding::Untracked { name, ty, path } => {
+                write!(f, "untracked  {} -> {}({})", path.display(), name, ty)
+            }
+            Finding::Moved { name, new_name, .. } => {
+                write!(f, "moved      {} -> {}", name, new_name)
+            }
+        }
+    }
+}
+
+/// 列出 `dir` 下所有的檔案，回傳相對於腳本之家的路徑。
+/// 忽略所有 `.` 開頭的檔案和資料夾，除了最上層的匿名腳本資料夾
+fn walk(home: &Path, rel: &Path, files: &mut HashSet<PathBuf>) -> Result {
+    let dir = home.join(rel);
+    for entry in handle_fs_res(&[&dir], read_dir(&dir))? {
+        let entry = entry.map_err(|e| util::handle_fs_err(&[&dir], e))?;
+        let file_name = entry.file_name();
+        let file_name = match file_name.to_str() {
+            Some(s) => s,
+            None => {
+                log::warn!("非 utf8 的檔名 {:?}，略過", file_name);
+                continue;
+            }
+        };
+        let is_anonymous_dir = rel.as_os_str().is_empty() && file_name == ANONYMOUS;
+        if file_name.starts_with('.') && !is_anonymous_dir {
+            continue;
+        }
+        let rel = rel.join(file_name);
+        if entry.path().is_dir() {
+            walk(home, &rel, files)?;
+        } else {
+            files.insert(rel);
+        }
+    }
+    Ok(())
+}
+
+/// 由擴展名推斷檔案的類型和腳本名。有多個類型用同一擴展名時，優先選與擴展名同名者
+pub(crate) fn identify(rel: &Path) -> Option<(ScriptName, ScriptType)> {
+    let ext = rel.extension().and_then(|s| s.to_str());
+    let ty = Config::get()
+        .types
+        .iter()
+        .filter(|(_, c)| c.ext.as_deref() == ext)
+        .map(|(ty, _)| ty)
+        .min_by_key(|ty| (Some(ty.as_ref()) != ext, ty.as_ref().to_owned()))?
+        .clone();
+
+    let stem = rel.with_extension("");
+    let name = match stem.strip_prefix(ANONYMOUS) {
+        Ok(id) => id.to_str()?.parse::<u32>().ok()?.into_script_name(),
+        Err(_) => stem.to_str()?.to_owned().into_script_name(),
+    };
+    match name {
+        Ok(name) => Some((name, ty)),
+        Err(e) => {
+            log::warn!("{:?} 不是合法的腳本名：{}", stem, e);
+            None
+        }
+    }
+}
+
+pub fn detect(repo: &mut ScriptRepo) -> Result<Vec<Finding>> {
+    let home = path::get_home();
+    let mut files = HashSet::default();
+    walk(home, Path::new(""), &mut files)?;
+
+    let mut names = HashSet::default();
+    let mut missing = vec![];
+    for entry in repo.iter_mut(Visibility::All) {
+        names.insert(entry.name.key().into_owned());
+        let rel = entry.name.to_file_path_fallback(&entry.ty).0;
+        if !files.remove(&rel) {
+            log::debug!("找不到 {:?} 的檔案 {:?}", entry.name, rel);
+            missing.push((entry.id, entry.name.clone(), entry.ty.clone(), rel));
+        }
+    }
+
+    missing.sort_by(|a, b| a.1.key().cmp(&b.1.key()));
+    let mut files: Vec<_> = files.into_iter().collect();
+    files.sort();
+    let mut untracked = vec![];
+    for rel in files.into_iter() {
+        let (name, ty) = match identify(&rel) {
+            Some(t) => t,
+            None => {
+                log::debug!("{:?} 不像是腳本，略過", rel);
+                continue;
+            }
+        };
+        if names.contains(name.key().as_ref()) {
+            log::warn!("{:?} 和既存的腳本 {} 撞名，略過", rel, name);
+            continue;
+        }
+        untracked.push((name, ty, rel));
+    }
+
+    let mut findings = vec![];
+    for (id, name, ty, rel) in missing.into_iter() {
+        let mut candidates = untracked
+            .iter()
+            .enumerate()
+            .filter(|(_, (new_name, new_ty, p))| {
+                !name.is_anonymous()
+                    && !new_name.is_anonymous()
+                    && new_ty == &ty
+                    && p.file_name() == rel.file_name()
+            });
+        let moved_to = match (candidates.next(), candidates.next()) {
+            (Some((i, _)), None) => Some(i),
+            _ => None,
+        };
+        match moved_to {
+            Some(i) => {
+                let (new_name, ..) = untracked.remove(i);
+                findings.push(Finding::Moved { id, name, new_name });
+            }
+            None => findings.push(Finding::Orphaned {
+                id,
+                name,
+                path: rel,
+            }),
+        }
+    }
+    findings.extend(
+        untracked
+            .into_iter()
+            .map(|(name, ty, path)| Finding::Untracked { name, ty, path }),
+    );
+    Ok(findings)
+}
+
+pub async fn apply(repo: &mut ScriptRepo, finding: Finding) -> Result {
+    log::info!("套用 {:?}", finding);
+    match finding {
+        Finding::Orphaned { id, .. } => repo.remove(id).await?,
+        Finding::Untracked { name, ty, .. } => {
+            let tags = Config::get().main_tag_selector.clone().into_allowed_iter();
+            let info = ScriptInfo::builder(0, name.clone(), ty, tags).build();
+            repo.entry(&name).or_insert(info).await?;
+        }
+        Finding::Moved { id, new_name, .. } => {
+            // NOTE: 檔案已經在新的位置了，只改資料庫
+            let mut entry = repo.get_mut_by_id(id).unwrap();
+            entry.update(|info| info.name = new_name).await?;
+        }
+    }
+    Ok(())
+}
@@ -26,7 +26,14 @@ pub enum Color {
 }
 impl Color {
     pub fn from(s: &str) -> Self {
-        match s {
+        Self::parse(s).unwrap_or_else(|| {
+            let ret = Color::White;
+            log::warn!("錯誤的顏色 {}，改用 {:?}", s, ret);
+            ret
+        })
+    }
+    pub fn parse(s: &str) -> Option<Self> {
+        let c = match s {
             "black" => Color::Black,
             "red" => Color::Red,
             "green" => Color::Green,
@@ -43,13 +50,9 @@ impl Color {
             "bright magenta" => Color::BrightMagenta,
             "bright cyan" => Color::BrightCyan,
             "bright white" => Color::BrightWhite,
-
-            _ => {
-                let ret = Color::White;
-                log::warn!("錯誤的顏色 {}，改用 {:?}", s, ret);
-                ret
-            }
-        }
+            _ => return None,
+        };
+        Some(c)
     }
 }
 
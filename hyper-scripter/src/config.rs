@@ -1,5 +1,7 @@
 use crate::color::Color;
+use crate::config_layer::{self, Layers};
 use crate::error::{DisplayError, DisplayResult, Error, FormatCode, Result};
+use crate::hook::Hooks;
 use crate::path;
 use crate::script_type::{ScriptType, ScriptTypeConfig};
 use crate::tag::{TagGroup, TagSelector, TagSelectorGroup};
@@ -13,6 +15,9 @@ use std::str::FromStr;
 use std::time::SystemTime;
 
 const CONFIG_FILE: &str = ".config.toml";
+/// 主選擇器及命令行選擇器在解釋篩選結果時的標籤，加括號以免和具名選擇器混淆
+pub const MAIN_SELECTOR_LABEL: &str = "(main)";
+pub const ARG_SELECTOR_LABEL: &str = "(--select)";
 
 crate::local_global_state!(config_state, Config, || { Default::default() });
 crate::local_global_state!(runtime_conf_state, RuntimeConf, || { unreachable!() });
@@ -36,7 +41,7 @@ where
     Ok(v)
 }
 
-fn config_file(home: &Path) -> PathBuf {
+pub(crate) fn config_file(home: &Path) -> PathBuf {
     home.join(CONFIG_FILE)
 }
 
@@ -105,6 +110,128 @@ impl Alias {
 
         return (is_shell, std::iter::once(first_args).chain(iter));
     }
+
+    /// 展開別名，代入 `$1`、`$@`、`${1:-預設值}` 等佔位符，回傳是否為 shell 別名及展開後的參數。
+    /// 沒用到 `$@` 時，最後一個被引用的參數之後的參數都會接在後面，故沒有佔位符的別名行為同以往。
+    /// 在 shell 別名中，代入的參數都會加上引號
+    /// ```rust
+    /// use hyper_scripter::config::Alias;
+    ///
+    /// fn expand(after: &[&str], args: &[&str]) -> (bool, String) {
+    ///     let alias = Alias::from(after.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+    ///     let (is_shell, args) = alias.expand(args);
+    ///     (is_shell, args.join("|"))
+    /// }
+    ///
+    /// assert_eq!((false, "ls|-l|a|b".to_owned()), expand(&["ls", "-l"], &["a", "b"]));
+    /// assert_eq!((false, "run|b|--env=a".to_owned()), expand(&["run", "$2", "--env=$1"], &["a", "b"]));
+    /// assert_eq!((false, "run|x|a|b".to_owned()), expand(&["run", "x", "$@"], &["a", "b"]));
+    /// assert_eq!((false, "run|a|b|x".to_owned()), expand(&["run", "$@", "x"], &["a", "b"]));
+    /// assert_eq!((false, "run|a|c".to_owned()), expand(&["run", "$1"], &["a", "c"]));
+    /// assert_eq!((false, "run|dev".to_owned()), expand(&["run", "${1:-dev}"], &[]));
+    /// assert_eq!((false, "run|prod".to_owned()), expand(&["run", "${1:-dev}"], &["prod"]));
+    /// assert_eq!((false, "run".to_owned()), expand(&["run", "$1"], &[]));
+    /// assert_eq!((false, "run|$HOME|${HOME}".to_owned()), expand(&["run", "$HOME", "${HOME}"], &[]));
+    ///
+    /// assert_eq!((true, "echo|'a b'|c".to_owned()), expand(&["!echo"], &["a b", "c"]));
+    /// assert_eq!((true, "echo 'a b' >|c".to_owned()), expand(&["!echo $1 >"], &["a b", "c"]));
+    /// assert_eq!((true, "echo a 'b c'|done".to_owned()), expand(&["!echo $@", "done"], &["a", "b c"]));
+    /// ```
+    pub fn expand<T: AsRef<str>>(&self, args: &[T]) -> (bool, Vec<String>) {
+        let (is_shell, after) = self.args();
+        let args: Vec<String> = args
+            .iter()
+            .map(|s| {
+                let s = s.as_ref();
+                if is_shell {
+                    crate::to_display_args(s).into_owned()
+                } else {
+                    s.to_owned()
+                }
+            })
+            .collect();
+        let mut max_pos = 0;
+        let mut use_all = false;
+        let mut ret = vec![];
+        for token in after {
+            let whole = token
+                .strip_prefix('$')
+                .and_then(parse_placeholder)
+                .filter(|(_, len)| len + 1 == token.len());
+            match whole {
+                Some((Placeholder::All, _)) if !is_shell => {
+                    use_all = true;
+                    ret.extend(args.iter().cloned());
+                    continue;
+                }
+                Some((Placeholder::Pos(n, default), _)) if !is_shell => {
+                    max_pos = max_pos.max(n);
+                    // 沒有給值的 `$1` 就整個省略，而不是變成空字串參數
+                    if let Some(v) = args.get(n - 1).map(String::as_str).or(default) {
+                        ret.push(v.to_owned());
+                    }
+                    continue;
+                }
+                _ => (),
+            }
+
+            let mut s = String::new();
+            let mut rest = token;
+            while let Some(i) = rest.find('$') {
+                s.push_str(&rest[..i]);
+                rest = &rest[i + 1..];
+                match parse_placeholder(rest) {
+                    Some((Placeholder::All, len)) => {
+                        use_all = true;
+                        s.push_str(&args.join(" "));
+                        rest = &rest[len..];
+                    }
+                    Some((Placeholder::Pos(n, default), len)) => {
+                        max_pos = max_pos.max(n);
+                        s.push_str(
+                            args.get(n - 1)
+                                .map(String::as_str)
+                                .or(default)
+                                .unwrap_or_default(),
+                        );
+                        rest = &rest[len..];
+                    }
+                    None => s.push('$'),
+                }
+            }
+            s.push_str(rest);
+            ret.push(s);
+        }
+        if !use_all {
+            ret.extend(args.into_iter().skip(max_pos));
+        }
+        (is_shell, ret)
+    }
+}
+
+enum Placeholder<'a> {
+    All,
+    Pos(usize, Option<&'a str>),
+}
+
+/// 解析 `$` 之後的佔位符，回傳佔位符及其長度。`$HOME`、`${HOME}` 之類的不算
+fn parse_placeholder(s: &str) -> Option<(Placeholder<'_>, usize)> {
+    if s.starts_with('@') {
+        return Some((Placeholder::All, 1));
+    }
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if digits > 0 {
+        let n = s[..digits].parse().ok().filter(|n| *n > 0)?;
+        return Some((Placeholder::Pos(n, None), digits));
+    }
+    let inner = s.strip_prefix('{')?;
+    let end = inner.find('}')?;
+    let (n, default) = match inner[..end].split_once(":-") {
+        Some((n, default)) => (n, Some(default)),
+        None => (&inner[..end], None),
+    };
+    let n = n.parse().ok().filter(|n| *n > 0)?;
+    Some((Placeholder::Pos(n, default), end + 2))
 }
 
 #[derive(Display, PartialEq, Eq, Debug, Clone, Copy)]
@@ -197,6 +324,15 @@ pub struct Config {
     pub main_tag_selector: TagSelector,
     #[serde(default)]
     pub caution_tags: TagGroup,
+    /// 符合者執行時，其標準輸出與標準錯誤會另外存一份到家目錄中，見 `hs log`
+    #[serde(default)]
+    pub log_tags: TagGroup,
+    /// 執行會改動腳本的命令後，自動以 git 提交腳本之家，見 `hs vcs`
+    #[serde(default)]
+    pub auto_commit: bool,
+    /// 專案腳本之家專用：找不到腳本時改找全域的腳本之家，`ls` 也會一併列出
+    #[serde(default)]
+    pub layer_global: bool,
     prompt_level: PromptLevel,
     #[serde(deserialize_with = "de_nonempty_vec")]
     pub editor: Vec<String>,
@@ -204,9 +340,27 @@ pub struct Config {
     pub alias: HashMap<String, Alias>,
     pub types: HashMap<ScriptType, ScriptTypeConfig>,
     pub env: HashMap<String, String>,
+    /// 每次執行完後要跑的命令，見 `hook`
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+    /// 依標籤設定的鉤子，腳本有該標籤才會執行，在全域及類別的鉤子之後
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tag_hooks: HashMap<String, Hooks>,
     #[serde(skip)]
     last_modified: Option<SystemTime>,
+    #[serde(skip)]
+    snapshot: Snapshot,
 }
+
+/// 載入時的設定值，存檔時用來找出改動了哪些鍵。不參與比較
+#[derive(Debug, Clone, Default)]
+struct Snapshot(Option<toml::value::Table>);
+impl PartialEq for Snapshot {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+impl Eq for Snapshot {}
 impl Default for Config {
     fn default() -> Self {
         fn gen_alias(from: &str, after: &[&str]) -> (String, Alias) {
@@ -219,6 +373,7 @@ impl Default for Config {
         }
         Config {
             last_modified: None,
+            snapshot: Default::default(),
             recent: Default::default(),
             editor: vec!["vim".to_string()],
             prompt_level: PromptLevel::Smart,
@@ -241,6 +396,11 @@ impl Default for Config {
             ],
             main_tag_selector: "+all".parse().unwrap(),
             caution_tags: "caution".parse().unwrap(),
+            log_tags: "log".parse().unwrap(),
+            auto_commit: false,
+            layer_global: false,
+            hooks: Default::default(),
+            tag_hooks: Default::default(),
             types: ScriptTypeConfig::default_script_types(),
             alias: [
                 gen_alias("la", &["ls", "-a"]),
@@ -250,20 +410,11 @@ impl Default for Config {
                 gen_alias("gc", &["rm", "--timeless", "--purge", "-s", "remove", "*"]),
                 gen_alias("t", &["tags"]),
                 gen_alias("p", &["run", "--previous"]),
-                gen_alias(
-                    "pc",
-                    &["=util/historian!", "--sequence", "c", "--display=all"],
-                ),
-                gen_alias(
-                    "pr",
-                    &["=util/historian!", "--sequence", "r", "--display=all"],
-                ),
-                gen_alias("h", &["=util/historian!", "--display=all"]),
+                gen_alias("pc", &["history", "browse", "--action", "copy"]),
+                gen_alias("pr", &["history", "browse", "--action", "amend"]),
+                gen_alias("h", &["history", "browse"]),
                 // Showing humble events of all scripts will be a mess
-                gen_alias(
-                    "hh",
-                    &["=util/historian!", "*", "--display=all", "--no-humble"],
-                ),
+                gen_alias("hh", &["history", "browse", "*", "--no-humble"]),
             ]
             .into_iter()
             .collect(),
@@ -291,46 +442,46 @@ impl Default for Config {
     }
 }
 impl Config {
+    /// 載入所有設定層，見 `config_layer`
     pub fn load(p: &Path) -> Result<Self> {
+        Self::load_with(p, &[])
+    }
+    /// 同 `load`，另外套用命令行的 `key=value` 覆寫
+    pub fn load_with(p: &Path, overrides: &[String]) -> Result<Self> {
         let path = config_file(p);
         log::info!("載入設定檔：{:?}", path);
-        match util::read_file(&path) {
-            Ok(s) => {
-                let meta = util::handle_fs_res(&[&path], std::fs::metadata(&path))?;
-                let modified = util::handle_fs_res(&[&path], meta.modified())?;
-
-                let mut conf: Config = toml::from_str(&s).map_err(|err| {
-                    FormatCode::Config.to_err(format!("{}: {}", path.to_string_lossy(), err))
-                })?;
-                conf.last_modified = Some(modified);
-                Ok(conf)
-            }
-            Err(Error::PathNotFound(_)) => {
+        let layers = Layers::load(p, overrides)?;
+        let files = if layers.files.is_empty() {
+            path.to_string_lossy().into_owned()
+        } else {
+            let files: Vec<_> = layers.files.iter().map(|f| f.to_string_lossy()).collect();
+            files.join(", ")
+        };
+        let mut conf: Config = toml::Value::Table(layers.value)
+            .try_into()
+            .map_err(|err| FormatCode::Config.to_err(format!("{}: {}", files, err)))?;
+        conf.last_modified = match std::fs::metadata(&path) {
+            Ok(meta) => Some(util::handle_fs_res(&[&path], meta.modified())?),
+            Err(_) => {
                 log::debug!("找不到設定檔");
-                Ok(Default::default())
+                None
             }
-            Err(e) => Err(e),
+        };
+        conf.snapshot = Snapshot(Some(conf.to_table()?));
+        Ok(conf)
+    }
+
+    fn to_table(&self) -> Result<toml::value::Table> {
+        match toml::Value::try_from(self)? {
+            toml::Value::Table(t) => Ok(t),
+            _ => unreachable!(),
         }
     }
 
+    /// 只把載入後改動過的鍵寫回腳本之家的設定檔，不會蓋掉檔案中的其它內容
     pub fn store(&self) -> Result {
-        let path = config_file(path::get_home());
-        log::info!("寫入設定檔至 {:?}…", path);
-        match util::handle_fs_res(&[&path], std::fs::metadata(&path)) {
-            Ok(meta) => {
-                let modified = util::handle_fs_res(&[&path], meta.modified())?;
-                // NOTE: 若設定檔是憑空造出來的，但要存入時卻發現已有檔案，同樣不做存入
-                if self.last_modified.map_or(true, |time| time < modified) {
-                    log::info!("設定檔已被修改，不寫入");
-                    return Ok(());
-                }
-            }
-            Err(Error::PathNotFound(_)) => {
-                log::debug!("設定檔不存在，寫就對了");
-            }
-            Err(err) => return Err(err),
-        }
-        util::write_file(&path, &toml::to_string_pretty(self)?)
+        let base = self.snapshot.0.clone().unwrap_or_default();
+        config_layer::store_diff(path::get_home(), &base, &self.to_table()?)
     }
 
     pub fn is_from_dafault(&self) -> bool {
@@ -338,7 +489,10 @@ impl Config {
     }
 
     pub fn init() -> Result {
-        config_state::set(Config::load(path::get_home())?);
+        Self::init_with(&[])
+    }
+    pub fn init_with(overrides: &[String]) -> Result {
+        config_state::set(Config::load_with(path::get_home(), overrides)?);
         Ok(())
     }
 
@@ -381,6 +535,39 @@ impl Config {
         }
         Ok(env)
     }
+    /// 展開以別名開頭的參數，回傳是否為 shell 別名及展開後的參數，不是別名則回傳 `None`。
+    /// 展開後若以另一個別名開頭就繼續展開，直到遇上 shell 別名、非別名，
+    /// 或是指向自己的別名（如 `ls = ['ls', '-l']`）為止。繞回先前的別名則為 `AliasLoop`
+    pub fn expand_alias<T: AsRef<str>>(&self, words: &[T]) -> Result<Option<(bool, Vec<String>)>> {
+        let mut chain: Vec<String> = vec![];
+        let mut words: Vec<String> = words.iter().map(|s| s.as_ref().to_owned()).collect();
+        while let Some(first) = words.first() {
+            let alias = match self.alias.get(first) {
+                Some(alias) => alias,
+                None => break,
+            };
+            if chain.last() == Some(first) {
+                break;
+            }
+            let first = first.clone();
+            if chain.contains(&first) {
+                chain.push(first);
+                return Err(Error::AliasLoop(chain));
+            }
+            log::info!("別名 {} => {:?}", first, alias);
+            let (is_shell, new_words) = alias.expand(&words[1..]);
+            chain.push(first);
+            if is_shell {
+                return Ok(Some((true, new_words)));
+            }
+            words = new_words;
+        }
+        if chain.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some((false, words)))
+        }
+    }
     pub fn get_color(&self, ty: &ScriptType) -> Result<Color> {
         let c = self.get_script_conf(ty)?.color.as_str();
         Ok(Color::from(c))
@@ -398,9 +585,13 @@ impl Config {
                 log::debug!("{:?} 未啟用", f);
                 continue;
             }
-            group.push(f.content.clone()); // TODO: TagSelectorGroup 可以多帶點 lifetime 減少複製
+            // TODO: TagSelectorGroup 可以多帶點 lifetime 減少複製
+            group.push_labeled(f.name.clone(), f.content.clone());
         }
-        group.push(self.main_tag_selector.clone());
+        group.push_labeled(
+            MAIN_SELECTOR_LABEL.to_owned(),
+            self.main_tag_selector.clone(),
+        );
         group
     }
 }
@@ -418,5 +609,16 @@ mod test {
         let s = to_string_pretty(&c1).unwrap();
         let c2: Config = from_str(&s).unwrap();
         assert_eq!(c1, c2);
+
+        let mut c1 = Config {
+            main_tag_selector: "+(all & !hide) | (!build & !setup)".parse().unwrap(),
+            caution_tags: "caution | @rb & danger".parse().unwrap(),
+            log_tags: "log | @sh".parse().unwrap(),
+            ..Default::default()
+        };
+        c1.tag_selectors[0].content = "+!(pin & @sh)".parse().unwrap();
+        let s = to_string_pretty(&c1).unwrap();
+        let c2: Config = from_str(&s).unwrap();
+        assert_eq!(c1, c2);
     }
 }
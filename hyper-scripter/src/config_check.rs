@@ -0,0 +1,658 @@
+//! `hs config check`：先逐一檢查每個設定層檔案中的欄位與模板，再檢查合併後的設定，
+//! 如命令是否存在、別名是否蓋掉子命令或繞成圈。每個問題都盡量指出其所在的行列
+
+use crate::args::Root;
+use crate::color::Color;
+use crate::config::{config_file, Alias, Config, NamedTagSelector, PromptLevel, Recent};
+use crate::config_layer::{
+    find_project_config, override_layers, user_config_path, Layers, Origin, Origins,
+};
+use crate::error::{Error, Result};
+use crate::path;
+use crate::script_type::{ScriptType, ScriptTypeConfig};
+use crate::tag::{Tag, TagGroup, TagSelector};
+use crate::util::{self, TmplVal};
+use clap::CommandFactory;
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use handlebars::{Handlebars, TemplateError, TemplateRenderError};
+use serde::de::DeserializeOwned;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use toml::value::{Table, Value};
+use toml_edit::ImDocument;
+
+const TAG_SELECTORS: &str = "tag_selectors";
+
+#[derive(Debug, Clone, Copy)]
+enum Seg<'a> {
+    Key(&'a str),
+    Idx(usize),
+}
+
+fn fmt_path(path: &[Seg<'_>]) -> String {
+    let mut ret = String::new();
+    for seg in path.iter() {
+        match seg {
+            Seg::Key(k) if ret.is_empty() => ret.push_str(k),
+            Seg::Key(k) => {
+                ret.push('.');
+                ret.push_str(k);
+            }
+            Seg::Idx(i) => ret.push_str(&format!("[{}]", i)),
+        }
+    }
+    ret
+}
+
+fn push<'a>(path: &[Seg<'a>], seg: Seg<'a>) -> Vec<Seg<'a>> {
+    let mut path = path.to_vec();
+    path.push(seg);
+    path
+}
+
+/// 一個問題。`location` 為 `檔案:行:列`，或是環境變數、`--config` 等沒有行列的來源
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub location: String,
+    pub key: String,
+    pub msg: String,
+    line_col: Option<(usize, usize)>,
+}
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // LOCALE
+        if self.key.is_empty() {
+            write!(f, "{}: {}", self.location, self.msg)
+        } else {
+            write!(f, "{}: {}: {}", self.location, self.key, self.msg)
+        }
+    }
+}
+
+fn line_col(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, col)
+}
+
+/// 一個設定層。環境變數及 `--config` 沒有文件，也就沒有行列
+struct LayerFile {
+    origin: Origin,
+    table: Table,
+    doc: Option<ImDocument<String>>,
+}
+impl LayerFile {
+    /// 找出 `path` 在檔案中的位置。找不到值本身時，退而求其次找它的鍵，再不然就找上一層
+    fn span(&self, path: &[Seg<'_>], at_key: bool) -> Option<usize> {
+        let doc = self.doc.as_ref()?;
+        let mut item = doc.as_item();
+        let mut best = None;
+        for (i, seg) in path.iter().enumerate() {
+            let key_span = match seg {
+                Seg::Key(k) => item
+                    .as_table_like()
+                    .and_then(|t| t.key(k))
+                    .and_then(|k| k.span()),
+                Seg::Idx(_) => None,
+            };
+            item = match seg {
+                Seg::Key(k) => item.get(*k),
+                Seg::Idx(i) => item.get(*i),
+            }?;
+            let is_last = i + 1 == path.len();
+            let span = if is_last && at_key {
+                key_span.or_else(|| item.span())
+            } else {
+                item.span().or(key_span)
+            };
+            if let Some(span) = span {
+                best = Some(span.start);
+            }
+        }
+        best
+    }
+    fn locate(&self, path: &[Seg<'_>], at_key: bool) -> (String, Option<(usize, usize)>) {
+        match (self.span(path, at_key), &self.doc) {
+            (Some(offset), Some(doc)) => {
+                let (line, col) = line_col(doc.raw(), offset);
+                let location = format!("{}:{}:{}", self.origin, line, col);
+                (location, Some((line, col)))
+            }
+            _ => (self.origin.to_string(), None),
+        }
+    }
+    fn is_file(&self, p: &Path) -> bool {
+        matches!(&self.origin, Origin::File(path) if path == p)
+    }
+    /// `tag_selectors` 在合併後以名字對應，找出它在這個檔案中的索引
+    fn selector_index(&self, name: &str) -> Option<usize> {
+        self.table
+            .get(TAG_SELECTORS)?
+            .as_array()?
+            .iter()
+            .position(|v| v.get("name").and_then(Value::as_str) == Some(name))
+    }
+}
+
+struct Checker {
+    files: Vec<LayerFile>,
+    origins: Origins,
+    diags: Vec<Diagnostic>,
+    reg: Handlebars<'static>,
+    tmpl_val: TmplVal<'static>,
+}
+
+impl Checker {
+    fn report_at(&mut self, location: String, key: String, msg: impl Display) {
+        self.diags.push(Diagnostic {
+            location,
+            key,
+            msg: msg.to_string().trim_end().to_owned(),
+            line_col: None,
+        });
+    }
+    fn report(&mut self, f: usize, path: &[Seg<'_>], at_key: bool, msg: impl Display) {
+        let (location, line_col) = self.files[f].locate(path, at_key);
+        self.report_at(location, fmt_path(path), msg);
+        self.diags.last_mut().unwrap().line_col = line_col;
+    }
+    /// 依合併後的鍵找出其來源，檔案的話再找出行列
+    fn report_merged(&mut self, key: &str, path: &[Seg<'_>], msg: impl Display) {
+        let prefix = format!("{}.", key);
+        let origin = self.origins.get(key).or_else(|| {
+            self.origins
+                .range(prefix.clone()..)
+                .next()
+                .filter(|(k, _)| k.starts_with(&prefix))
+                .map(|(_, o)| o)
+        });
+        let location = match origin {
+            Some(Origin::File(p)) => match self.files.iter().find(|f| f.is_file(p)) {
+                Some(file) => {
+                    let mut path = path.to_vec();
+                    if let [Seg::Key(TAG_SELECTORS), Seg::Key(name), ..] = path[..] {
+                        match file.selector_index(name) {
+                            Some(i) => path[1] = Seg::Idx(i),
+                            None => path.truncate(1),
+                        }
+                    }
+                    file.locate(&path, false).0
+                }
+                None => p.display().to_string(),
+            },
+            Some(origin) => origin.to_string(),
+            None => Origin::Default.to_string(),
+        };
+        self.report_at(location, key.to_owned(), msg);
+    }
+
+    fn check_de<T: DeserializeOwned>(
+        &mut self,
+        f: usize,
+        path: &[Seg<'_>],
+        v: &Value,
+    ) -> Option<T> {
+        match v.clone().try_into::<T>() {
+            Ok(t) => Some(t),
+            Err(err) => {
+                self.report(f, path, false, err);
+                None
+            }
+        }
+    }
+    fn check_table<'v>(&mut self, f: usize, path: &[Seg<'_>], v: &'v Value) -> Option<&'v Table> {
+        let t = v.as_table();
+        if t.is_none() {
+            self.report(f, path, false, "expected a table");
+        }
+        t
+    }
+    fn check_nonempty(&mut self, f: usize, path: &[Seg<'_>], v: &Value) -> Option<Vec<String>> {
+        let arr: Vec<String> = self.check_de(f, path, v)?;
+        if arr.is_empty() {
+            self.report(f, path, false, "expected a non-empty array");
+            return None;
+        }
+        Some(arr)
+    }
+    fn check_tmpl(&mut self, f: usize, path: &[Seg<'_>], v: &Value) {
+        if let Some(s) = self.check_de::<String>(f, path, v) {
+            if let Err(err) = self.reg.render_template(&s, &self.tmpl_val) {
+                let msg = match err {
+                    TemplateRenderError::TemplateError(err) => fmt_template_error(&err),
+                    err => err.to_string(),
+                };
+                self.report(f, path, false, msg);
+            }
+        }
+    }
+    fn unknown_key(&mut self, f: usize, path: &[Seg<'_>]) {
+        self.report(f, path, true, "unknown key");
+    }
+
+    fn check_layer(&mut self, f: usize) {
+        let start = self.diags.len();
+        let table = std::mem::take(&mut self.files[f].table);
+        for (key, v) in table.iter() {
+            let path = [Seg::Key(key)];
+            match key.as_str() {
+                "recent" => {
+                    self.check_de::<Recent>(f, &path, v);
+                }
+                "main_tag_selector" => {
+                    self.check_de::<TagSelector>(f, &path, v);
+                }
+                "caution_tags" | "log_tags" => {
+                    self.check_de::<TagGroup>(f, &path, v);
+                }
+                "prompt_level" => {
+                    self.check_de::<PromptLevel>(f, &path, v);
+                }
+                "auto_commit" | "layer_global" => {
+                    self.check_de::<bool>(f, &path, v);
+                }
+                "editor" => {
+                    self.check_nonempty(f, &path, v);
+                }
+                TAG_SELECTORS => self.check_selectors(f, v),
+                "alias" => self.check_aliases(f, v),
+                "types" => self.check_types(f, v),
+                "env" => self.check_env(f, &path, v),
+                "hooks" => self.check_hooks(f, &path, v),
+                "tag_hooks" => self.check_tag_hooks(f, v),
+                _ => self.unknown_key(f, &path),
+            }
+        }
+        self.files[f].table = table;
+        // 表格中的鍵是排序過的，改成依照行列排序
+        self.diags[start..].sort_by_key(|d| d.line_col);
+    }
+    fn check_selectors(&mut self, f: usize, v: &Value) {
+        let path = [Seg::Key(TAG_SELECTORS)];
+        let arr = match v.as_array() {
+            Some(arr) => arr,
+            None => {
+                self.report(f, &path, false, "expected an array of tables");
+                return;
+            }
+        };
+        for (i, v) in arr.iter().enumerate() {
+            let path = push(&path, Seg::Idx(i));
+            let t = match self.check_table(f, &path, v) {
+                Some(t) => t,
+                None => continue,
+            };
+            for (key, v) in t.iter() {
+                let path = push(&path, Seg::Key(key));
+                match key.as_str() {
+                    "content" => {
+                        self.check_de::<TagSelector>(f, &path, v);
+                    }
+                    "name" => {
+                        self.check_de::<String>(f, &path, v);
+                    }
+                    "inactivated" => {
+                        self.check_de::<bool>(f, &path, v);
+                    }
+                    _ => self.unknown_key(f, &path),
+                }
+            }
+        }
+    }
+    fn check_aliases(&mut self, f: usize, v: &Value) {
+        let path = [Seg::Key("alias")];
+        let aliases = match self.check_table(f, &path, v) {
+            Some(t) => t,
+            None => return,
+        };
+        for (name, v) in aliases.iter() {
+            let path = push(&path, Seg::Key(name));
+            let t = match self.check_table(f, &path, v) {
+                Some(t) => t,
+                None => continue,
+            };
+            for (key, v) in t.iter() {
+                let path = push(&path, Seg::Key(key));
+                match key.as_str() {
+                    "after" => {
+                        self.check_nonempty(f, &path, v);
+                    }
+                    _ => self.unknown_key(f, &path),
+                }
+            }
+        }
+    }
+    fn check_types(&mut self, f: usize, v: &Value) {
+        let path = [Seg::Key("types")];
+        let types = match self.check_table(f, &path, v) {
+            Some(t) => t,
+            None => return,
+        };
+        for (ty, v) in types.iter() {
+            let path = push(&path, Seg::Key(ty));
+            if let Err(err) = ty.parse::<ScriptType>() {
+                self.report(f, &path, true, err);
+            }
+            let t = match self.check_table(f, &path, v) {
+                Some(t) => t,
+                None => continue,
+            };
+            for (key, v) in t.iter() {
+                let path = push(&path, Seg::Key(key));
+                match key.as_str() {
+                    "ext" | "cmd" => {
+                        self.check_de::<String>(f, &path, v);
+                    }
+                    "color" => {
+                        if let Some(color) = self.check_de::<String>(f, &path, v) {
+                            if Color::parse(&color).is_none() {
+                                self.report(f, &path, false, format!("unknown color `{}`", color));
+                            }
+                        }
+                    }
+                    "args" => {
+                        if let Some(arr) = self.check_de::<Vec<Value>>(f, &path, v) {
+                            for (i, v) in arr.iter().enumerate() {
+                                self.check_tmpl(f, &push(&path, Seg::Idx(i)), v);
+                            }
+                        }
+                    }
+                    "env" => self.check_env(f, &path, v),
+                    "hooks" => self.check_hooks(f, &path, v),
+                    _ => self.unknown_key(f, &path),
+                }
+            }
+        }
+    }
+    fn check_hooks(&mut self, f: usize, path: &[Seg<'_>], v: &Value) {
+        let t = match self.check_table(f, path, v) {
+            Some(t) => t,
+            None => return,
+        };
+        for (key, v) in t.iter() {
+            let path = push(path, Seg::Key(key));
+            match key.as_str() {
+                "post_run" | "on_success" | "on_failure" => {
+                    self.check_de::<String>(f, &path, v);
+                }
+                _ => self.unknown_key(f, &path),
+            }
+        }
+    }
+    fn check_tag_hooks(&mut self, f: usize, v: &Value) {
+        let path = [Seg::Key("tag_hooks")];
+        let tags = match self.check_table(f, &path, v) {
+            Some(t) => t,
+            None => return,
+        };
+        for (tag, v) in tags.iter() {
+            let path = push(&path, Seg::Key(tag));
+            if let Err(err) = tag.parse::<Tag>() {
+                self.report(f, &path, true, err);
+            }
+            self.check_hooks(f, &path, v);
+        }
+    }
+    fn check_env(&mut self, f: usize, path: &[Seg<'_>], v: &Value) {
+        if let Some(t) = self.check_table(f, path, v) {
+            for (name, v) in t.iter() {
+                self.check_tmpl(f, &push(path, Seg::Key(name)), v);
+            }
+        }
+    }
+
+    /// 已經在某一層中回報過的鍵，合併後就不再重複回報
+    fn has_reported(&self, key: &str) -> bool {
+        self.diags.iter().any(|d| {
+            d.key == key
+                || d.key.starts_with(&format!("{}.", key))
+                || d.key.starts_with(&format!("{}[", key))
+        })
+    }
+
+    /// 各層都沒問題，不代表合併起來就沒問題，例如某個類別在任何一層都沒有設定 `color`
+    fn check_merged(&mut self, value: Table) {
+        if let Some(types) = value.get("types").and_then(Value::as_table) {
+            for (ty, v) in types.iter() {
+                let key = format!("types.{}", ty);
+                let path = [Seg::Key("types"), Seg::Key(ty)];
+                if !self.has_reported(&key) {
+                    if let Err(err) = v.clone().try_into::<ScriptTypeConfig>() {
+                        self.report_merged(&key, &path, err);
+                    }
+                }
+                if let Some(cmd) = v.get("cmd").and_then(Value::as_str) {
+                    if !in_path(cmd) {
+                        let path = push(&path, Seg::Key("cmd"));
+                        let msg = format!("`{}` is not found in PATH", cmd);
+                        self.report_merged(&format!("{}.cmd", key), &path, msg);
+                    }
+                }
+            }
+        }
+        if let Some(arr) = value.get(TAG_SELECTORS).and_then(Value::as_array) {
+            for v in arr.iter() {
+                let name = v.get("name").and_then(Value::as_str).unwrap_or_default();
+                let key = format!("{}.{}", TAG_SELECTORS, name);
+                if self.has_reported(TAG_SELECTORS) {
+                    break;
+                }
+                if let Err(err) = v.clone().try_into::<NamedTagSelector>() {
+                    let path = [Seg::Key(TAG_SELECTORS), Seg::Key(name)];
+                    self.report_merged(&key, &path, err);
+                }
+            }
+        }
+        if let Some(aliases) = value.get("alias").and_then(Value::as_table) {
+            let aliases: HashMap<&str, Alias> = aliases
+                .iter()
+                .filter_map(|(name, v)| Some((name.as_str(), v.clone().try_into().ok()?)))
+                .collect();
+            self.check_alias_graph(&aliases);
+        }
+
+        if self.diags.is_empty() {
+            if let Err(err) = Value::Table(value).try_into::<Config>() {
+                self.report_at("(merged)".to_owned(), String::new(), err);
+            }
+        }
+    }
+    fn check_alias_graph(&mut self, aliases: &HashMap<&str, Alias>) {
+        let root = Root::command();
+        let mut subcmds: HashSet<&str> = HashSet::default();
+        for sub in root.get_subcommands() {
+            subcmds.insert(sub.get_name());
+            subcmds.extend(sub.get_all_aliases());
+        }
+
+        let mut names: Vec<_> = aliases.keys().copied().collect();
+        names.sort_unstable();
+        // 指向自己的子命令的別名（如 `ls = ['ls', '-l']`）是常見的用法，不算蓋掉子命令
+        let mut next: HashMap<&str, &str> = HashMap::default();
+        for name in names.iter() {
+            let (is_shell, mut args) = aliases[name].args();
+            let first = args.next().unwrap_or_default();
+            let path = [Seg::Key("alias"), Seg::Key(name), Seg::Key("after")];
+            let key = format!("alias.{}.after", name);
+            if subcmds.contains(name) && (is_shell || first != *name) {
+                let msg = format!("shadows the subcommand `{}`", name);
+                self.report_merged(&key, &path, msg);
+            }
+            if !is_shell
+                && aliases.contains_key(first)
+                && (first != *name || !subcmds.contains(name))
+            {
+                next.insert(name, first);
+            }
+        }
+
+        let mut reported: HashSet<&str> = HashSet::default();
+        for start in names.iter() {
+            let mut chain = vec![*start];
+            let mut cur = *start;
+            while let Some(n) = next.get(cur) {
+                if let Some(pos) = chain.iter().position(|c| c == n) {
+                    let cycle = &chain[pos..];
+                    if cycle.iter().all(|c| reported.insert(c)) {
+                        let path = [Seg::Key("alias"), Seg::Key(cycle[0]), Seg::Key("after")];
+                        let key = format!("alias.{}.after", cycle[0]);
+                        let mut desc = cycle.to_vec();
+                        desc.push(n);
+                        let msg = format!("alias loop: {}", desc.join(" -> "));
+                        self.report_merged(&key, &path, msg);
+                    }
+                    break;
+                }
+                chain.push(n);
+                cur = n;
+            }
+        }
+    }
+
+    fn check_templates(&mut self, dir: &Path) -> Result {
+        if !dir.is_dir() {
+            return Ok(());
+        }
+        let mut entries: Vec<_> = util::handle_fs_res(&[dir], std::fs::read_dir(dir))?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<std::io::Result<_>>()?;
+        entries.sort();
+        for p in entries.into_iter() {
+            if p.is_dir() {
+                self.check_templates(&p)?;
+                continue;
+            }
+            if p.extension().is_none_or(|ext| ext != "hbs") {
+                continue;
+            }
+            let content = util::read_file(&p)?;
+            let mut reg = Handlebars::new();
+            if let Err(err) = reg.register_template_string("t", content) {
+                let location = match (err.line_no, err.column_no) {
+                    (Some(line), Some(col)) => format!("{}:{}:{}", p.display(), line, col),
+                    _ => p.display().to_string(),
+                };
+                self.report_at(location, String::new(), err.reason);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 模板內的行列，附在錯誤原因後面
+fn fmt_template_error(err: &TemplateError) -> String {
+    match (err.line_no, err.column_no) {
+        (Some(line), Some(col)) => format!("{} (template {}:{})", err.reason, line, col),
+        _ => err.reason.to_string(),
+    }
+}
+
+fn in_path(cmd: &str) -> bool {
+    let p = Path::new(cmd);
+    if p.components().count() > 1 {
+        return p.is_file();
+    }
+    match std::env::var_os("PATH") {
+        Some(paths) => std::env::split_paths(&paths).any(|dir| dir.join(cmd).is_file()),
+        None => false,
+    }
+}
+
+fn read_layer_file(path: PathBuf, diags: &mut Vec<Diagnostic>) -> Result<Option<LayerFile>> {
+    let content = match util::read_file(&path) {
+        Ok(s) => s,
+        Err(Error::PathNotFound(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let table: Table = match toml::from_str(&content) {
+        Ok(t) => t,
+        Err(err) => {
+            let location = match err.line_col() {
+                Some((line, col)) => format!("{}:{}:{}", path.display(), line + 1, col + 1),
+                None => path.display().to_string(),
+            };
+            let msg = err.to_string();
+            let msg = match msg.rfind(" at line ") {
+                Some(i) => msg[..i].to_owned(),
+                None => msg,
+            };
+            diags.push(Diagnostic {
+                location,
+                key: String::new(),
+                msg,
+                line_col: None,
+            });
+            return Ok(None);
+        }
+    };
+    let doc = ImDocument::parse(content).ok();
+    Ok(Some(LayerFile {
+        origin: Origin::File(path),
+        table,
+        doc,
+    }))
+}
+
+/// 檢查所有設定層並印出每個問題，有問題時回傳 `InvalidConfig`
+pub fn check(home: &Path, overrides: &[String]) -> Result {
+    let mut paths = vec![];
+    if let Ok(p) = user_config_path() {
+        paths.push(p);
+    }
+    paths.push(config_file(home));
+    if let Some(p) = find_project_config()? {
+        paths.push(p);
+    }
+
+    let mut diags = vec![];
+    let mut files = vec![];
+    for p in paths.into_iter() {
+        log::info!("檢查設定層 {:?}", p);
+        if let Some(file) = read_layer_file(p, &mut diags)? {
+            files.push(file);
+        }
+    }
+
+    match override_layers(overrides) {
+        Ok(sources) => {
+            for (origin, table) in sources.into_iter() {
+                let doc = None;
+                files.push(LayerFile { origin, table, doc });
+            }
+        }
+        Err(err) => diags.push(Diagnostic {
+            location: Origin::Cli.to_string(),
+            key: String::new(),
+            msg: err.to_string().trim_end().to_owned(),
+            line_col: None,
+        }),
+    }
+
+    let mut checker = Checker {
+        files,
+        origins: Default::default(),
+        diags,
+        reg: Handlebars::new(),
+        tmpl_val: TmplVal::dummy(),
+    };
+    for f in 0..checker.files.len() {
+        checker.check_layer(f);
+    }
+    match Layers::load(home, overrides) {
+        Ok(layers) => {
+            checker.origins = layers.origins;
+            checker.check_merged(layers.value);
+        }
+        Err(err) => log::info!("設定層本身有問題，無法合併：{}", err),
+    }
+    checker.check_templates(&path::get_template_dir())?;
+
+    for diag in checker.diags.iter() {
+        println!("{}", diag);
+    }
+    match checker.diags.len() {
+        0 => Ok(()),
+        count => Err(Error::InvalidConfig(count)),
+    }
+}
@@ -0,0 +1,632 @@
+//! 設定檔的分層，由低到高：內建預設值、使用者設定檔、腳本之家設定檔、專案設定檔、
+//! `HS_CONFIG_*` 環境變數、命令行的 `--config`。
+//! 表格（如 `alias`、`types`、`env`）逐鍵合併，`tag_selectors` 依名字合併。
+//! 內建預設值只用來補上沒有任何一層設定過的頂層鍵，故從設定檔中刪掉的別名不會再冒出來
+
+use crate::config::{config_file, Config};
+use crate::error::{DisplayError, DisplayResult, Error, FormatCode, Result, SysPath};
+use crate::util;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::value::{Table, Value};
+use toml_edit::DocumentMut;
+
+pub const USER_CONFIG: &str = "hyper_scripter.toml";
+pub const PROJECT_CONFIG: &str = ".hyper_scripter.toml";
+const ENV_PREFIX: &str = "HS_CONFIG_";
+const TAG_SELECTORS: &str = "tag_selectors";
+
+pub const CONFIG_LAYERS: &[&str] = &["user", "home", "project"];
+
+/// 可以用 `hs config set` 寫入的設定層
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub enum ConfigLayer {
+    User,
+    Home,
+    Project,
+}
+impl FromStr for ConfigLayer {
+    type Err = DisplayError;
+    fn from_str(s: &str) -> DisplayResult<Self> {
+        let l = match s {
+            "user" => ConfigLayer::User,
+            "home" => ConfigLayer::Home,
+            "project" => ConfigLayer::Project,
+            _ => unreachable!(),
+        };
+        Ok(l)
+    }
+}
+
+/// 一個設定值的來源
+#[derive(Debug, Clone)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    Env(String),
+    Cli,
+}
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // LOCALE
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(p) => write!(f, "{}", p.display()),
+            Origin::Env(var) => write!(f, "env {}", var),
+            Origin::Cli => write!(f, "--config"),
+        }
+    }
+}
+
+pub type Origins = BTreeMap<String, Origin>;
+
+/// 合併後的設定，及每個值的來源。`tag_selectors` 中的值以 `tag_selectors.<name>` 為鍵
+pub struct Layers {
+    pub value: Table,
+    pub origins: Origins,
+    /// 實際讀到的設定檔，由低到高
+    pub files: Vec<PathBuf>,
+}
+
+pub fn user_config_path() -> Result<PathBuf> {
+    let dir = dirs::config_dir().ok_or(Error::SysPathNotFound(SysPath::Config))?;
+    Ok(dir.join(USER_CONFIG))
+}
+
+/// 從當前目錄往上找專案設定檔
+pub fn find_project_config() -> Result<Option<PathBuf>> {
+    let here = std::env::current_dir()?;
+    Ok(here
+        .ancestors()
+        .map(|d| d.join(PROJECT_CONFIG))
+        .find(|p| p.is_file()))
+}
+
+/// 設定層對應的檔案。專案設定檔不存在時，回傳當前目錄下的路徑
+pub fn layer_path(layer: ConfigLayer, home: &Path) -> Result<PathBuf> {
+    match layer {
+        ConfigLayer::User => user_config_path(),
+        ConfigLayer::Home => Ok(config_file(home)),
+        ConfigLayer::Project => match find_project_config()? {
+            Some(p) => Ok(p),
+            None => Ok(std::env::current_dir()?.join(PROJECT_CONFIG)),
+        },
+    }
+}
+
+fn read_layer(path: &Path) -> Result<Option<Table>> {
+    let s = match util::read_file(path) {
+        Ok(s) => s,
+        Err(Error::PathNotFound(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let table: Table = toml::from_str(&s)
+        .map_err(|err| FormatCode::Config.to_err(format!("{}: {}", path.display(), err)))?;
+    Ok(Some(table))
+}
+
+/// 將字串解析成 TOML 值，解析不了就視為字串，例如 `3`、`true`、`["a", "b"]` 或 `vim`
+pub fn parse_value(s: &str) -> Value {
+    match toml::from_str::<Table>(&format!("v = {}", s)) {
+        Ok(mut t) => t.remove("v").unwrap(),
+        Err(_) => Value::String(s.to_owned()),
+    }
+}
+
+fn nest(path: &[&str], v: Value) -> Table {
+    let mut v = v;
+    for key in path[1..].iter().rev() {
+        let mut t = Table::new();
+        t.insert(key.to_string(), v);
+        v = Value::Table(t);
+    }
+    let mut t = Table::new();
+    t.insert(path[0].to_owned(), v);
+    t
+}
+
+/// `key=value` 形式的覆寫，`key` 以 `.` 分隔層級
+fn parse_override(s: &str) -> Result<Table> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| FormatCode::Config.to_err(s.to_owned()))?;
+    let path: Vec<_> = key.trim().split('.').collect();
+    Ok(nest(&path, parse_value(value.trim())))
+}
+
+/// `HS_CONFIG_PROMPT_LEVEL=never` 設定 `prompt_level`，以 `__` 分隔層級，
+/// 如 `HS_CONFIG_ENV__EDITOR=nvim` 設定 `env.EDITOR`。只有第一層會轉成小寫
+fn env_layers() -> Vec<(Origin, Table)> {
+    let mut vars: Vec<_> = std::env::vars()
+        .filter(|(k, _)| k.starts_with(ENV_PREFIX) && k.len() > ENV_PREFIX.len())
+        .collect();
+    vars.sort();
+    vars.into_iter()
+        .map(|(var, value)| {
+            let key = &var[ENV_PREFIX.len()..];
+            let mut path: Vec<String> = key.split("__").map(str::to_owned).collect();
+            path[0] = path[0].to_lowercase();
+            let path: Vec<_> = path.iter().map(String::as_str).collect();
+            log::debug!("環境變數 {} 設定 {:?}", var, path);
+            (Origin::Env(var.clone()), nest(&path, parse_value(&value)))
+        })
+        .collect()
+}
+
+/// 環境變數及命令行的覆寫，由低到高排列
+pub fn override_layers(overrides: &[String]) -> Result<Vec<(Origin, Table)>> {
+    let mut sources = env_layers();
+    for o in overrides.iter() {
+        sources.push((Origin::Cli, parse_override(o)?));
+    }
+    Ok(sources)
+}
+
+fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+fn selector_name(v: &Value) -> Option<&str> {
+    v.get("name").and_then(Value::as_str)
+}
+
+fn mark(v: &Value, path: &str, origin: &Origin, origins: &mut Origins) {
+    origins.retain(|k, _| k != path && !k.starts_with(&format!("{}.", path)));
+    match v {
+        Value::Table(t) => {
+            for (k, v) in t.iter() {
+                mark(v, &join_key(path, k), origin, origins);
+            }
+        }
+        Value::Array(arr) if path == TAG_SELECTORS => {
+            for v in arr.iter() {
+                if let Some(name) = selector_name(v) {
+                    origins.insert(join_key(path, name), origin.clone());
+                }
+            }
+        }
+        _ => {
+            origins.insert(path.to_owned(), origin.clone());
+        }
+    }
+}
+
+fn merge_selectors(dst: &mut Vec<Value>, src: Vec<Value>, origin: &Origin, origins: &mut Origins) {
+    for v in src.into_iter() {
+        let name = selector_name(&v).map(str::to_owned);
+        if let Some(name) = &name {
+            let path = join_key(TAG_SELECTORS, name);
+            origins.insert(path, origin.clone());
+        }
+        let pos = name
+            .as_deref()
+            .and_then(|name| dst.iter().position(|d| selector_name(d) == Some(name)));
+        match (pos.map(|i| &mut dst[i]), v) {
+            (Some(Value::Table(d)), Value::Table(s)) => {
+                for (k, v) in s.into_iter() {
+                    d.insert(k, v);
+                }
+            }
+            (Some(d), v) => *d = v,
+            (None, v) => dst.push(v),
+        }
+    }
+}
+
+fn merge(dst: &mut Table, src: Table, prefix: &str, origin: &Origin, origins: &mut Origins) {
+    for (k, v) in src.into_iter() {
+        let path = join_key(prefix, &k);
+        match (dst.get_mut(&k), v) {
+            (Some(Value::Table(d)), Value::Table(s)) => merge(d, s, &path, origin, origins),
+            (Some(Value::Array(d)), Value::Array(s)) if path == TAG_SELECTORS => {
+                merge_selectors(d, s, origin, origins)
+            }
+            (_, v) => {
+                mark(&v, &path, origin, origins);
+                dst.insert(k, v);
+            }
+        }
+    }
+}
+
+impl Layers {
+    /// 讀取並合併所有設定層
+    pub fn load(home: &Path, overrides: &[String]) -> Result<Layers> {
+        let mut files = vec![];
+        if let Ok(p) = user_config_path() {
+            files.push(p);
+        }
+        files.push(config_file(home));
+        if let Some(p) = find_project_config()? {
+            files.push(p);
+        }
+
+        let mut sources = vec![];
+        let mut read_files = vec![];
+        for file in files.into_iter() {
+            if let Some(table) = read_layer(&file)? {
+                log::debug!("載入設定層 {:?}", file);
+                read_files.push(file.clone());
+                sources.push((Origin::File(file), table));
+            }
+        }
+        sources.extend(override_layers(overrides)?);
+
+        let mut value = Table::new();
+        let mut origins = Origins::new();
+        for (origin, table) in sources.into_iter() {
+            merge(&mut value, table, "", &origin, &mut origins);
+        }
+        let default = match Value::try_from(Config::default())? {
+            Value::Table(t) => t,
+            _ => unreachable!(),
+        };
+        for (k, v) in default.into_iter() {
+            if !value.contains_key(&k) {
+                mark(&v, &k, &Origin::Default, &mut origins);
+                value.insert(k, v);
+            }
+        }
+        Ok(Layers {
+            value,
+            origins,
+            files: read_files,
+        })
+    }
+
+    /// 依 `alias.ll`、`tag_selectors.pin` 這樣的鍵取值
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        get_in(&self.value, key)
+    }
+
+    /// 攤平所有的值，每行形如 `alias.ll = ["ls", "-l"]`
+    pub fn flatten(&self) -> Vec<(String, String)> {
+        let mut ret = vec![];
+        flatten_into(&self.value, "", &mut ret);
+        ret
+    }
+}
+
+fn get_in<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let mut segments = key.split('.');
+    let mut cur = table.get(segments.next()?)?;
+    let mut is_selectors = key.starts_with(TAG_SELECTORS);
+    for seg in segments {
+        cur = match cur {
+            Value::Array(arr) if is_selectors => {
+                is_selectors = false;
+                arr.iter().find(|v| selector_name(v) == Some(seg))?
+            }
+            Value::Table(t) => t.get(seg)?,
+            _ => return None,
+        };
+    }
+    Some(cur)
+}
+
+fn flatten_into(v: &Table, prefix: &str, ret: &mut Vec<(String, String)>) {
+    for (k, v) in v.iter() {
+        let path = join_key(prefix, k);
+        match v {
+            Value::Table(t) => flatten_into(t, &path, ret),
+            Value::Array(arr) if path == TAG_SELECTORS => {
+                for v in arr.iter() {
+                    let name = selector_name(v).unwrap_or_default();
+                    ret.push((join_key(&path, name), fmt_inline(v)));
+                }
+            }
+            _ => ret.push((path, fmt_inline(v))),
+        }
+    }
+}
+
+/// 給 `hs config get` 用：字串不加引號，表格攤平成多行，其它則為單行的 TOML
+pub fn fmt_value(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Table(t) => {
+            let mut lines = vec![];
+            flatten_into(t, "", &mut lines);
+            let lines: Vec<_> = lines
+                .into_iter()
+                .map(|(k, v)| format!("{} = {}", k, v))
+                .collect();
+            lines.join("\n")
+        }
+        _ => fmt_inline(v),
+    }
+}
+
+/// 將值格式化成單行的 TOML
+pub fn fmt_inline(v: &Value) -> String {
+    match v {
+        Value::Table(t) => {
+            let fields: Vec<_> = t
+                .iter()
+                .map(|(k, v)| format!("{} = {}", k, fmt_inline(v)))
+                .collect();
+            format!("{{ {} }}", fields.join(", "))
+        }
+        Value::Array(arr) => {
+            let items: Vec<_> = arr.iter().map(fmt_inline).collect();
+            format!("[{}]", items.join(", "))
+        }
+        _ => {
+            let mut t = Table::new();
+            t.insert("v".to_owned(), v.clone());
+            let s = toml::to_string(&Value::Table(t)).unwrap_or_default();
+            s.trim()["v = ".len()..].to_owned()
+        }
+    }
+}
+
+fn to_item(key: &str, v: &Value) -> Result<toml_edit::Item> {
+    let mut t = Table::new();
+    t.insert(key.to_owned(), v.clone());
+    let mut doc: DocumentMut = toml::to_string(&Value::Table(t))?.parse()?;
+    Ok(doc.remove(key).unwrap())
+}
+
+/// 設定 `key` 的值。若已有此鍵，原地替換之，以保留其前後的註解
+fn put(table: &mut dyn toml_edit::TableLike, key: &str, mut item: toml_edit::Item) {
+    match table.get_mut(key) {
+        Some(old) => {
+            if let (Some(old_value), toml_edit::Item::Value(value)) = (old.as_value(), &mut item) {
+                *value.decor_mut() = old_value.decor().clone();
+            }
+            *old = item;
+        }
+        None => {
+            table.insert(key, item);
+        }
+    }
+}
+
+fn read_doc(path: &Path) -> Result<DocumentMut> {
+    let s = match util::read_file(path) {
+        Ok(s) => s,
+        Err(Error::PathNotFound(_)) => String::new(),
+        Err(e) => return Err(e),
+    };
+    s.parse()
+        .map_err(|err| FormatCode::Config.to_err(format!("{}: {}", path.display(), err)))
+}
+
+/// 找出（或新增）文件中該名字的選擇器。`key` 只用於錯誤訊息
+fn selector_in_doc<'a>(
+    doc: &'a mut DocumentMut,
+    name: &str,
+    key: &str,
+) -> Result<&'a mut toml_edit::Table> {
+    let arr = doc
+        .entry(TAG_SELECTORS)
+        .or_insert_with(|| toml_edit::Item::ArrayOfTables(Default::default()))
+        .as_array_of_tables_mut()
+        .ok_or_else(|| FormatCode::Config.to_err(key.to_owned()))?;
+    let pos = arr
+        .iter()
+        .position(|t| t.get("name").and_then(|n| n.as_str()) == Some(name));
+    let table = match pos {
+        Some(i) => arr.get_mut(i).unwrap(),
+        None => {
+            let mut t = toml_edit::Table::new();
+            t.insert("name", toml_edit::value(name));
+            arr.push(t);
+            arr.get_mut(arr.len() - 1).unwrap()
+        }
+    };
+    Ok(table)
+}
+
+/// 在文件中設定 `key` 的值，保留其它內容及註解。`tag_selectors.<name>.<field>` 會找出（或新增）該名字的選擇器
+fn set_in_doc(doc: &mut DocumentMut, key: &str, v: &Value) -> Result {
+    let path: Vec<_> = key.split('.').collect();
+    if path[0] == TAG_SELECTORS && path.len() > 1 {
+        let table = selector_in_doc(doc, path[1], key)?;
+        return match &path[2..] {
+            [] => Err(FormatCode::Config.to_err(key.to_owned())),
+            [field] => {
+                put(table, field, to_item(field, v)?);
+                Ok(())
+            }
+            _ => Err(FormatCode::Config.to_err(key.to_owned())),
+        };
+    }
+
+    let (last, parents) = path.split_last().unwrap();
+    let mut cur = doc.as_table_mut();
+    for seg in parents.iter() {
+        cur = cur
+            .entry(seg)
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .ok_or_else(|| FormatCode::Config.to_err(key.to_owned()))?;
+    }
+    put(cur, last, to_item(last, v)?);
+    Ok(())
+}
+
+/// 編輯某一設定層中的單一值，不動到其它內容。寫入後若合併的設定解析失敗，就還原之
+pub fn set(layer: ConfigLayer, home: &Path, key: &str, value: &str) -> Result {
+    let path = layer_path(layer, home)?;
+    let mut doc = match layer {
+        ConfigLayer::Home => home_doc(home)?.0,
+        _ => read_doc(&path)?,
+    };
+    set_in_doc(&mut doc, key, &parse_value(value))?;
+
+    let old = match util::read_file(&path) {
+        Ok(s) => Some(s),
+        Err(Error::PathNotFound(_)) => None,
+        Err(e) => return Err(e),
+    };
+    log::info!("寫入 {} 至 {:?}", key, path);
+    util::write_file(&path, &doc.to_string())?;
+    if let Err(err) = Config::load(home) {
+        log::warn!("寫入後的設定無法解析，還原之：{}", err);
+        match old {
+            Some(old) => util::write_file(&path, &old)?,
+            None => util::remove(&path)?,
+        }
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// 取得某一設定層中的值
+pub fn get_in_layer(layer: ConfigLayer, home: &Path, key: &str) -> Result<Option<Value>> {
+    let path = layer_path(layer, home)?;
+    let table = read_layer(&path)?.unwrap_or_default();
+    Ok(get_in(&table, key).cloned())
+}
+
+fn as_table(v: Option<&Value>) -> Option<&Table> {
+    v.and_then(Value::as_table)
+}
+
+/// 依名字索引選擇器，沒有名字的略過
+fn selectors_by_name(v: Option<&Value>) -> BTreeMap<&str, &Table> {
+    let arr = v
+        .and_then(Value::as_array)
+        .map_or(&[][..], |a| a.as_slice());
+    arr.iter()
+        .filter_map(|v| Some((selector_name(v)?, v.as_table()?)))
+        .collect()
+}
+
+/// 逐一比對名字相同的選擇器，只把改動的欄位寫進文件。
+/// 選擇器是各層依名字合併的，故不能整個陣列寫回，否則其它層的選擇器都會被抄進腳本之家
+fn store_selectors_diff(
+    doc: &mut DocumentMut,
+    base: Option<&Value>,
+    new: Option<&Value>,
+) -> Result {
+    let (base, new) = (selectors_by_name(base), selectors_by_name(new));
+    let names: std::collections::BTreeSet<_> = base.keys().chain(new.keys()).copied().collect();
+    for name in names.into_iter() {
+        let key = join_key(TAG_SELECTORS, name);
+        match (base.get(name), new.get(name)) {
+            (b, Some(n)) => {
+                let empty = Table::new();
+                let b = b.copied().unwrap_or(&empty);
+                let fields: std::collections::BTreeSet<_> = b.keys().chain(n.keys()).collect();
+                for field in fields.into_iter() {
+                    if b.get(field) == n.get(field) {
+                        continue;
+                    }
+                    let table = selector_in_doc(doc, name, &key)?;
+                    match n.get(field) {
+                        Some(v) => put(table, field, to_item(field, v)?),
+                        None => {
+                            table.remove(field);
+                        }
+                    }
+                }
+            }
+            (Some(_), None) => {
+                let removed = doc
+                    .get_mut(TAG_SELECTORS)
+                    .and_then(|item| item.as_array_of_tables_mut())
+                    .and_then(|arr| {
+                        let pos = arr
+                            .iter()
+                            .position(|t| t.get("name").and_then(|n| n.as_str()) == Some(name))?;
+                        arr.remove(pos);
+                        Some(())
+                    });
+                if removed.is_none() {
+                    log::warn!("{} 不在腳本之家的設定檔中，無法刪除", key);
+                }
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// 讀取腳本之家的設定檔，並回傳檔案是否存在。
+/// 設定檔不存在時，先放入一份內建預設值，但略過使用者設定檔中已有的頂層鍵，以免蓋掉它們
+fn home_doc(home: &Path) -> Result<(DocumentMut, bool)> {
+    let path = config_file(home);
+    if path.exists() {
+        return Ok((read_doc(&path)?, true));
+    }
+    let mut default = match Value::try_from(Config::default())? {
+        Value::Table(t) => t,
+        _ => unreachable!(),
+    };
+    if let Some(user) = read_layer(&user_config_path()?)? {
+        for k in user.keys() {
+            default.remove(k);
+        }
+    }
+    let doc = toml::to_string_pretty(&Value::Table(default))?.parse()?;
+    Ok((doc, false))
+}
+
+/// 把 `new` 相對於 `base` 的改動寫進腳本之家的設定檔，其它內容（包括註解）保持原樣。
+pub fn store_diff(home: &Path, base: &Table, new: &Table) -> Result {
+    let path = config_file(home);
+    let (mut doc, exists) = home_doc(home)?;
+    let before = doc.to_string();
+
+    let keys: std::collections::BTreeSet<_> = base.keys().chain(new.keys()).collect();
+    for key in keys.into_iter() {
+        let (b, n) = (base.get(key), new.get(key));
+        if b == n {
+            continue;
+        }
+        if key == TAG_SELECTORS {
+            store_selectors_diff(&mut doc, b, n)?;
+            continue;
+        }
+        match (as_table(b), as_table(n)) {
+            (Some(b), Some(n)) => {
+                let sub_keys: std::collections::BTreeSet<_> = b.keys().chain(n.keys()).collect();
+                for sub in sub_keys.into_iter() {
+                    if b.get(sub) == n.get(sub) {
+                        continue;
+                    }
+                    let table = doc
+                        .entry(key)
+                        .or_insert_with(toml_edit::table)
+                        .as_table_like_mut()
+                        .ok_or_else(|| FormatCode::Config.to_err(key.to_owned()))?;
+                    match n.get(sub) {
+                        Some(v) => {
+                            put(table, sub, to_item(sub, v)?);
+                        }
+                        None => {
+                            if table.remove(sub).is_none() {
+                                log::warn!("{}.{} 不在腳本之家的設定檔中，無法刪除", key, sub);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => match n {
+                Some(v) => {
+                    put(doc.as_table_mut(), key, to_item(key, v)?);
+                }
+                None => {
+                    doc.remove(key);
+                }
+            },
+        }
+    }
+
+    let after = doc.to_string();
+    if exists && before == after {
+        log::debug!("設定沒有改動，不寫入");
+        return Ok(());
+    }
+    log::info!("寫入設定檔至 {:?}…", path);
+    util::write_file(&path, &after)
+}
@@ -0,0 +1,300 @@
+//! 五個欄位的 cron 表達式（分 時 日 月 週），例如 `*/5 * * * *`、`0 9 * * mon-fri`。
+//! 支援 `*`、`a-b`、`*/n`、`a-b/n`、以逗號分隔的清單、月份與星期的英文縮寫，以及 `@daily` 等簡寫。
+//! 和一般的 cron 相同，若「日」和「週」皆有限定，符合其中一個即可
+
+use crate::error::{DisplayError, DisplayResult, FormatCode};
+use crate::util::impl_ser_by_to_string;
+use chrono::{Datelike, Duration, NaiveDateTime, Timelike};
+use std::str::FromStr;
+
+const MONTH_NAMES: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const DOW_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const SYSTEMD_DOW_NAMES: &[&str] = &["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+/// 找下一個時間點時最多往後看幾天，足以涵蓋閏年的二月二十九日
+const MAX_DAYS: i64 = 366 * 8;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CronExpr {
+    src: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn parse_num(s: &str, names: &[&str], offset: u32) -> Option<u32> {
+    if let Ok(n) = s.parse() {
+        return Some(n);
+    }
+    let s = s.to_ascii_lowercase();
+    let pos = names.iter().position(|name| *name == s)?;
+    Some(pos as u32 + offset)
+}
+
+/// 解析單一欄位，回傳位元集合，以及是否為 `*`
+fn parse_field(s: &str, min: u32, max: u32, names: &[&str]) -> Option<(u64, bool)> {
+    let mut bits = 0;
+    for part in s.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_num(start, names, min)?, parse_num(end, names, min)?)
+        } else {
+            let start = parse_num(range, names, min)?;
+            // NOTE: `5/10` 表示從 5 開始每 10 個
+            let end = if part.contains('/') { max } else { start };
+            (start, end)
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for n in (start..=end).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Some((bits, s == "*"))
+}
+
+fn has(bits: u64, n: u32) -> bool {
+    bits & (1 << n) != 0
+}
+
+fn full(min: u32, max: u32) -> u64 {
+    (min..=max).fold(0, |bits, n| bits | 1 << n)
+}
+
+/// 把位元集合寫成 systemd 的格式，連續三個以上的值寫成 `a..b`
+fn fmt_systemd_field(bits: u64, min: u32, max: u32, fmt: impl Fn(u32) -> String) -> String {
+    let full = full(min, max);
+    if bits & full == full {
+        return "*".to_owned();
+    }
+    let mut parts = vec![];
+    let mut n = min;
+    while n <= max {
+        if !has(bits, n) {
+            n += 1;
+            continue;
+        }
+        let start = n;
+        while n < max && has(bits, n + 1) {
+            n += 1;
+        }
+        if n - start >= 2 {
+            parts.push(format!("{}..{}", fmt(start), fmt(n)));
+        } else {
+            parts.extend((start..=n).map(&fmt));
+        }
+        n += 1;
+    }
+    parts.join(",")
+}
+
+impl CronExpr {
+    /// 轉成 systemd timer 的 `OnCalendar=` 值。日和週皆有限定時，cron 只需符合其一，
+    /// systemd 卻要兩者皆符合，所以拆成兩個值
+    pub fn to_on_calendar(&self) -> Vec<String> {
+        let num = |n: u32| format!("{:02}", n);
+        let time = format!(
+            "{}:{}:00",
+            fmt_systemd_field(self.hours, 0, 23, num),
+            fmt_systemd_field(self.minutes, 0, 59, num)
+        );
+        let months = fmt_systemd_field(self.months, 1, 12, num);
+        let days = fmt_systemd_field(self.days, 1, 31, num);
+        let weekdays = fmt_systemd_field(self.weekdays, 0, 6, |n| {
+            SYSTEMD_DOW_NAMES[n as usize].to_owned()
+        });
+        let by_day = format!("*-{}-{} {}", months, days, time);
+        let by_weekday = if weekdays == "*" {
+            format!("*-{}-* {}", months, time)
+        } else {
+            format!("{} *-{}-* {}", weekdays, months, time)
+        };
+        match (self.any_day, self.any_weekday) {
+            (_, true) => vec![by_day],
+            (true, false) => vec![by_weekday],
+            (false, false) => vec![by_day, by_weekday],
+        }
+    }
+    fn match_day(&self, t: &NaiveDateTime) -> bool {
+        if !has(self.months, t.month()) {
+            return false;
+        }
+        let day = has(self.days, t.day());
+        let weekday = has(self.weekdays, t.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+    /// 嚴格晚於 `t` 的下一個符合的時間點，精確到分鐘
+    pub fn next_after(&self, t: NaiveDateTime) -> Option<NaiveDateTime> {
+        let start = t.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut day = start.date().and_hms_opt(0, 0, 0)?;
+        for _ in 0..MAX_DAYS {
+            if self.match_day(&day) {
+                let first_day = day.date() == start.date();
+                for hour in 0..24 {
+                    if !has(self.hours, hour) || (first_day && hour < start.hour()) {
+                        continue;
+                    }
+                    for minute in 0..60 {
+                        if !has(self.minutes, minute) {
+                            continue;
+                        }
+                        let candidate = day.with_hour(hour)?.with_minute(minute)?;
+                        if candidate >= start {
+                            return Some(candidate);
+                        }
+                    }
+                }
+            }
+            day += Duration::days(1);
+        }
+        None
+    }
+}
+
+impl FromStr for CronExpr {
+    type Err = DisplayError;
+    fn from_str(s: &str) -> DisplayResult<Self> {
+        let expanded = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            s => s,
+        };
+        let fields: Vec<_> = expanded.split_whitespace().collect();
+        let parse = || -> Option<CronExpr> {
+            let [minutes, hours, days, months, weekdays] = fields[..] else {
+                return None;
+            };
+            let (minutes, _) = parse_field(minutes, 0, 59, &[])?;
+            let (hours, _) = parse_field(hours, 0, 23, &[])?;
+            let (days, any_day) = parse_field(days, 1, 31, &[])?;
+            let (months, _) = parse_field(months, 1, 12, MONTH_NAMES)?;
+            let (mut weekdays, any_weekday) = parse_field(weekdays, 0, 7, DOW_NAMES)?;
+            if has(weekdays, 7) {
+                weekdays |= 1; // 7 也是星期日
+            }
+            Some(CronExpr {
+                src: s.trim().to_owned(),
+                minutes,
+                hours,
+                days,
+                months,
+                weekdays,
+                any_day,
+                any_weekday,
+            })
+        };
+        match parse() {
+            Some(expr) => Ok(expr),
+            None => {
+                log::error!("cron 格式不符：{}", s);
+                FormatCode::Cron.to_display_res(s.to_owned())
+            }
+        }
+    }
+}
+impl std::fmt::Display for CronExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.src)
+    }
+}
+impl_ser_by_to_string!(CronExpr);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn t(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+    fn next(expr: &str, from: &str) -> String {
+        let expr: CronExpr = expr.parse().unwrap();
+        let next = expr.next_after(t(from)).unwrap();
+        next.format("%Y-%m-%d %H:%M").to_string()
+    }
+
+    #[test]
+    fn test_cron_next() {
+        assert_eq!(next("* * * * *", "2026-10-17 12:00"), "2026-10-17 12:01");
+        assert_eq!(next("*/5 * * * *", "2026-10-17 12:03"), "2026-10-17 12:05");
+        assert_eq!(next("*/5 * * * *", "2026-10-17 12:55"), "2026-10-17 13:00");
+        assert_eq!(next("30 9 * * *", "2026-10-17 09:30"), "2026-10-18 09:30");
+        assert_eq!(
+            next("0 9-17/4 * * *", "2026-10-17 10:00"),
+            "2026-10-17 13:00"
+        );
+        // 2026-10-17 是星期六
+        assert_eq!(
+            next("0 9 * * mon-fri", "2026-10-17 08:00"),
+            "2026-10-19 09:00"
+        );
+        assert_eq!(next("0 0 * * 7", "2026-10-17 08:00"), "2026-10-18 00:00");
+        assert_eq!(next("0 0 31 * *", "2026-10-31 00:00"), "2026-12-31 00:00");
+        assert_eq!(next("0 0 29 feb *", "2026-10-17 00:00"), "2028-02-29 00:00");
+        // 日和週皆有限定時，符合其一即可
+        assert_eq!(next("0 0 1 * sat", "2026-10-17 08:00"), "2026-10-24 00:00");
+        assert_eq!(next("@daily", "2026-10-17 08:00"), "2026-10-18 00:00");
+        assert_eq!(next("0,30 * * * *", "2026-10-17 08:10"), "2026-10-17 08:30");
+    }
+
+    #[test]
+    fn test_on_calendar() {
+        fn on_calendar(expr: &str) -> Vec<String> {
+            expr.parse::<CronExpr>().unwrap().to_on_calendar()
+        }
+        assert_eq!(on_calendar("* * * * *"), ["*-*-* *:*:00"]);
+        assert_eq!(on_calendar("30 9 * * *"), ["*-*-* 09:30:00"]);
+        assert_eq!(
+            on_calendar("*/15 0-12/6 * * *"),
+            ["*-*-* 00,06,12:00,15,30,45:00"]
+        );
+        assert_eq!(on_calendar("0 9 * * mon-fri"), ["Mon..Fri *-*-* 09:00:00"]);
+        assert_eq!(on_calendar("0 0 * * 0,6"), ["Sun,Sat *-*-* 00:00:00"]);
+        assert_eq!(on_calendar("0 0 1-3 feb *"), ["*-02-01..03 00:00:00"]);
+        assert_eq!(
+            on_calendar("0 0 1 * sat"),
+            ["*-*-01 00:00:00", "Sat *-*-* 00:00:00"]
+        );
+    }
+
+    #[test]
+    fn test_cron_parse() {
+        let expr: CronExpr = " */5 * * * * ".parse().unwrap();
+        assert_eq!(expr.to_string(), "*/5 * * * *");
+        for s in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "* * * * someday",
+            "@never",
+        ] {
+            s.parse::<CronExpr>().expect_err(s);
+        }
+        let expr: CronExpr = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(expr.next_after(t("2026-01-01 00:00")), None);
+    }
+}
@@ -1,17 +1,20 @@
-use crate::error::Result;
-use crate::util::read_file;
+use crate::error::{Error, Result};
+use crate::util::{self, read_file};
 use sqlx::{
     sqlite::{SqliteConnectOptions, SqliteJournalMode},
     SqlitePool,
 };
 use std::path::{Path, PathBuf};
 
+const DB_FILE: &str = ".script_info.db";
+const SQL_FILE: &str = ".script_info.sql";
+
 pub fn get_file() -> PathBuf {
-    crate::path::get_home().join(".script_info.db")
+    crate::path::get_home().join(DB_FILE)
 }
 
 fn get_sql_file() -> PathBuf {
-    crate::path::get_home().join(".script_info.sql")
+    crate::path::get_home().join(SQL_FILE)
 }
 
 /// 有可能改變 need_journal 的值。
@@ -63,3 +66,111 @@ async fn do_migrate_may_force_pre_sql(
 pub async fn do_migrate(file: impl AsRef<Path>) -> Result<SqlitePool> {
     do_migrate_may_force_pre_sql(file, false).await
 }
+
+/// 在另一個資料夾建立全新的資料庫，例如用來打包匯出的腳本
+pub async fn create_other(home: &Path) -> Result<SqlitePool> {
+    let pool = crate::migration::do_migrate_with_pre_sql(home.join(DB_FILE), None).await?;
+    Ok(pool)
+}
+
+/// 打開另一個腳本之家的資料庫。為了不動到對方，會先複製一份到 `tmp_file` 再遷移；
+/// 若對方只有 `.script_info.sql`（例如來自 git 倉庫），則以之重建資料庫
+pub async fn open_other(home: &Path, tmp_file: &Path) -> Result<SqlitePool> {
+    let file = home.join(DB_FILE);
+    let sql_file = home.join(SQL_FILE);
+    let pre_sql = if file.exists() {
+        util::cp(&file, tmp_file)?;
+        None
+    } else if sql_file.exists() {
+        Some(read_file(&sql_file)?)
+    } else {
+        return Err(Error::PathNotFound(vec![file, sql_file]).context("來源不是腳本之家"));
+    };
+    let pool = crate::migration::do_migrate_with_pre_sql(tmp_file, pre_sql.as_deref()).await?;
+    Ok(pool)
+}
+
+/// 將資料庫匯出成 `.script_info.sql`，格式同 `sqlite3 .dump`，可供 `do_migrate` 重建資料庫。
+/// 不匯出 `last_events` 的內容，因為它每次執行都會變動
+pub async fn dump_sql() -> Result {
+    let opt = SqliteConnectOptions::new()
+        .filename(get_file())
+        .read_only(true);
+    let pool = SqlitePool::connect_with(opt).await?;
+    let objects: Vec<(String, String, String)> = sqlx::query_as(
+        "
+        SELECT type, name, sql FROM sqlite_master
+        WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+        ORDER BY type != 'table', name
+        ",
+    )
+    .fetch_all(&pool)
+    .await?;
+
+    let mut dump = "PRAGMA foreign_keys=OFF;\nBEGIN TRANSACTION;\n".to_owned();
+    for (ty, name, sql) in objects.into_iter() {
+        dump += &sql;
+        dump += ";\n";
+        if ty != "table" || name == "last_events" {
+            continue;
+        }
+        let columns: Vec<(String,)> = sqlx::query_as("SELECT name FROM pragma_table_info(?)")
+            .bind(&name)
+            .fetch_all(&pool)
+            .await?;
+        let values = columns
+            .iter()
+            .map(|(c,)| format!("quote(\"{}\")", c))
+            .collect::<Vec<_>>()
+            .join(" || ',' || ");
+        let query = format!(
+            "SELECT 'INSERT INTO \"{0}\" VALUES(' || {1} || ');' FROM \"{0}\" ORDER BY rowid",
+            name, values
+        );
+        let rows: Vec<(String,)> = sqlx::query_as(&query).fetch_all(&pool).await?;
+        for (row,) in rows.into_iter() {
+            dump += &row;
+            dump += "\n";
+        }
+    }
+    dump += "COMMIT;\n";
+    pool.close().await;
+    util::write_file(&get_sql_file(), &dump)
+}
+
+/// 以 `.script_info.sql` 重建資料庫，並盡量保留原本各腳本的最新事件
+pub async fn rebuild_from_sql() -> Result {
+    let sql_file = get_sql_file();
+    if !sql_file.exists() {
+        return Err(Error::PathNotFound(vec![sql_file]).context("無法重建資料庫"));
+    }
+    let file = get_file();
+    let old_file = file.with_extension("db.bak");
+    if file.exists() {
+        util::mv(&file, &old_file)?;
+    }
+    let pool = do_migrate_may_force_pre_sql(&file, true).await?;
+    if old_file.exists() {
+        // NOTE: ATTACH 只對單一連線有效
+        let mut conn = pool.acquire().await?;
+        sqlx::query("ATTACH DATABASE ? AS old")
+            .bind(old_file.to_string_lossy())
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query(
+            "
+            INSERT OR REPLACE INTO last_events SELECT * FROM old.last_events
+            WHERE script_id IN (SELECT id FROM script_infos)
+            ",
+        )
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query("DETACH DATABASE old")
+            .execute(&mut *conn)
+            .await?;
+        drop(conn);
+        util::remove(&old_file)?;
+    }
+    pool.close().await;
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! 比較兩次執行的參數、環境變數、目錄與腳本內容
+
+use crate::env_pair::EnvPair;
+use crate::error::Result;
+use crate::to_display_args;
+use hyper_scripter_historian::ExecEvent;
+use similar::TextDiff;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+const CONTEXT_RADIUS: usize = 3;
+
+/// 以 unified 格式寫出兩段文字的差異，兩者相同時什麼都不寫
+pub fn write_unified<W: Write>(
+    w: &mut W,
+    old: &str,
+    new: &str,
+    old_header: &str,
+    new_header: &str,
+) -> Result {
+    if old == new {
+        return Ok(());
+    }
+    let diff = TextDiff::from_lines(old, new);
+    let mut unified = diff.unified_diff();
+    unified
+        .context_radius(CONTEXT_RADIUS)
+        .header(old_header, new_header);
+    write!(w, "{}", unified)?;
+    Ok(())
+}
+
+fn fmt_args(args: &str) -> String {
+    let args: Vec<String> = serde_json::from_str(args).unwrap_or_else(|e| {
+        log::warn!("無法解析參數 {}：{}", args, e);
+        vec![]
+    });
+    if args.is_empty() {
+        return "(none)".to_owned();
+    }
+    let args: Vec<_> = args.iter().map(|s| to_display_args(s)).collect();
+    args.join(" ")
+}
+
+fn parse_envs(envs: &str) -> BTreeMap<String, String> {
+    if envs.is_empty() {
+        return Default::default();
+    }
+    let envs: Vec<EnvPair> = serde_json::from_str(envs).unwrap_or_else(|e| {
+        log::warn!("無法解析環境變數 {}：{}", envs, e);
+        vec![]
+    });
+    envs.into_iter().map(|p| (p.key, p.val)).collect()
+}
+
+fn fmt_dir(dir: &str) -> &str {
+    if dir.is_empty() {
+        "(none)"
+    } else {
+        dir
+    }
+}
+
+/// 寫出兩個執行事件的差異，只列出有變動的部分
+pub fn write_event_diff<W: Write>(w: &mut W, a: &ExecEvent, b: &ExecEvent) -> Result {
+    let (args_a, args_b) = (fmt_args(&a.args), fmt_args(&b.args));
+    if args_a != args_b {
+        writeln!(w, "args:\n- {}\n+ {}", args_a, args_b)?;
+    }
+
+    let (envs_a, envs_b) = (parse_envs(&a.envs), parse_envs(&b.envs));
+    if envs_a != envs_b {
+        writeln!(w, "env:")?;
+        let mut keys: Vec<_> = envs_a.keys().chain(envs_b.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            match (envs_a.get(key), envs_b.get(key)) {
+                (Some(val_a), Some(val_b)) if val_a == val_b => (),
+                (val_a, val_b) => {
+                    if let Some(val) = val_a {
+                        writeln!(w, "- {}={}", key, val)?;
+                    }
+                    if let Some(val) = val_b {
+                        writeln!(w, "+ {}={}", key, val)?;
+                    }
+                }
+            }
+        }
+    }
+
+    if a.dir != b.dir {
+        writeln!(w, "dir:\n- {}\n+ {}", fmt_dir(&a.dir), fmt_dir(&b.dir))?;
+    }
+
+    match (&a.content, &b.content) {
+        (Some(content_a), Some(content_b)) => {
+            if content_a != content_b {
+                writeln!(w, "content:")?;
+                let header_a = format!("run {}", a.id);
+                let header_b = format!("run {}", b.id);
+                write_unified(w, content_a, content_b, &header_a, &header_b)?;
+            }
+        }
+        (content_a, _) => {
+            let id = if content_a.is_none() { a.id } else { b.id };
+            log::warn!("找不到執行 {} 的腳本內容", id);
+            writeln!(w, "content: unknown for run {}", id)?;
+        }
+    }
+    Ok(())
+}
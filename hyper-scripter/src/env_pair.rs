@@ -24,6 +24,15 @@ impl EnvPair {
             });
         }
     }
+    /// 以 `overrides` 覆蓋同名的環境變數，沒有的則附加在後
+    pub fn merge(v: &mut Vec<Self>, overrides: Vec<Self>) {
+        for pair in overrides.into_iter() {
+            match v.iter_mut().find(|p| p.key == pair.key) {
+                Some(p) => p.val = pair.val,
+                None => v.push(pair),
+            }
+        }
+    }
     pub fn sort(v: &mut Vec<Self>) {
         v.sort_by(|a, b| a.key.cmp(&b.key));
     }
@@ -38,10 +38,14 @@ pub enum FormatCode {
     ScriptType,
     Regex,
     RangeQuery,
+    TimeQuery,
     ScriptQuery,
     Tag,
     NonEmptyArray,
     EnvPair,
+    ArgSchema,
+    Cron,
+    PickBinding,
     Template, // TODO: 一旦特化穩定了，就讓 handlebars 錯誤自動轉成這個
 }
 
@@ -76,17 +80,35 @@ pub enum Error {
     ScriptIsFiltered(String),
     ScriptNotFound(String),
     NoAlias(String),
+    AliasLoop(Vec<String>),
+    NoConfigKey(String),
+    InvalidConfig(usize),
     UnknownType(String),
     Format(FormatCode, String),
 
     ScriptError(i32),
     PreRunError(i32),
+    InvalidScriptArgs(String, String),
     EditorError(i32, Vec<String>),
+    CommandError(i32, Vec<String>),
 
     RedundantOpt(RedundantOpt),
     TagSelectorNotFound(String),
     DontFuzz,
     NoPreviousArgs,
+    NoRunLog(String, Option<i64>),
+    NoRunEvent(i64),
+    NoSchedule(i64),
+    UnitDrift(usize),
+    NeverRun(String),
+    NotEnoughRuns(String, usize),
+    NoRunContent(i64),
+    UncommittedChanges(String),
+    EmptyHistory,
+    NothingToPick,
+    NoTerminal,
+    ImportFromSelf(PathBuf),
+    NoHistoryEntry(usize),
     Empty,
     Caution,
 
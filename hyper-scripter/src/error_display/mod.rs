@@ -25,6 +25,28 @@ impl Display for Error {
             DontFuzz | Caution => return Ok(()),
             Empty => write!(f, "No existing script!")?,
             NoPreviousArgs => write!(f, "No previous argument!")?,
+            NoRunLog(name, None) => write!(f, "No output log for {}", name)?,
+            NoRunEvent(run_id) => write!(f, "No such run: {}", run_id)?,
+            NoSchedule(id) => write!(f, "No such schedule: {}", id)?,
+            UnitDrift(n) => write!(f, "{} systemd unit file(s) drifted from the schedules", n)?,
+            NeverRun(name) => write!(f, "Script has never been run: {}", name)?,
+            NotEnoughRuns(name, count) => write!(f, "Script has only {} runs: {}", count, name)?,
+            NoRunContent(run_id) => write!(f, "Content of run {} is missing", run_id)?,
+            EmptyHistory => write!(f, "History is empty")?,
+            NothingToPick => write!(f, "Nothing to pick from")?,
+            NoTerminal => write!(f, "The picker needs a terminal")?,
+            NoHistoryEntry(number) => write!(f, "No history entry #{}", number)?,
+            ImportFromSelf(path) => {
+                write!(f, "Can not import from the current hs home: {:?}", path)?
+            }
+            UncommittedChanges(name) => write!(
+                f,
+                "Script has changes not recorded by any run or edit: {}. Use --force to overwrite them.",
+                name
+            )?,
+            NoRunLog(name, Some(run_id)) => {
+                write!(f, "No output log for run {} of {}", run_id, name)?
+            }
             SysPathNotFound(SysPath::Config) => write!(
                 f,
                 "Can not find you're config path. Usually it should be `$HOME/.config`",
@@ -43,6 +65,7 @@ impl Display for Error {
                 use FormatCode::*;
                 match code {
                     RangeQuery => write!(f, "range query")?,
+                    TimeQuery => write!(f, "time (e.g. 2024-01-31 or 3d)")?,
                     Config => write!(f, "config file")?,
                     ScriptName => write!(f, "script name")?,
                     Regex => write!(f, "regular expression")?,
@@ -51,6 +74,9 @@ impl Display for Error {
                     Tag => write!(f, "tag")?,
                     PromptLevel => write!(f, "prompt level")?,
                     EnvPair => write!(f, "env pair (e.g. VAR=1)")?,
+                    ArgSchema => write!(f, "argument declaration (e.g. name?: int = 1 -> help)")?,
+                    Cron => write!(f, "cron expression (e.g. */5 * * * *)")?,
+                    PickBinding => write!(f, "pick binding (e.g. w:wait for ctrl-w)")?,
                     Template => write!(f, "template")?,
                     NonEmptyArray => {
                         write!(f, "non-empty array")?;
@@ -61,11 +87,23 @@ impl Display for Error {
             }
             ScriptError(code) => write!(f, "Script exited unexpectedly with {}", code)?,
             PreRunError(code) => write!(f, "Pre-run script exited unexpectedly with {}", code)?,
+            InvalidScriptArgs(name, msg) => write!(
+                f,
+                "Invalid args for {}: {}. See `hs help {}`",
+                name, msg, name
+            )?,
             EditorError(code, cmd) => {
                 let cmd = cmd.join(" ");
                 write!(f, "Editor `{}` exited unexpectedly with {}", cmd, code)?
             }
+            CommandError(code, cmd) => {
+                let cmd = cmd.join(" ");
+                write!(f, "Command `{}` exited unexpectedly with {}", cmd, code)?
+            }
             NoAlias(alias) => write!(f, "No such alias: {}", alias)?,
+            AliasLoop(chain) => write!(f, "Alias loop: {}", chain.join(" -> "))?,
+            NoConfigKey(key) => write!(f, "No such config key: {}", key)?,
+            InvalidConfig(count) => write!(f, "Found {} problem(s) in config", count)?,
             RedundantOpt(opt) => write!(f, "Redundant option: {:?}", opt)?,
             _ => {
                 log::warn!("未被正確打印的錯誤：{:?}", self);
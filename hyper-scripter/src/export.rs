@@ -0,0 +1,62 @@
+//! 將腳本打包成壓縮檔。壓縮檔的內容即是一個小型的腳本之家，故可直接以 `hs import` 匯入
+
+use crate::db;
+use crate::error::Result;
+use crate::import::{run_tool, TmpDir};
+use crate::path;
+use crate::script::ScriptInfo;
+use crate::script_repo::DBEnv;
+use crate::util::{self, handle_fs_res};
+use hyper_scripter_historian::Historian;
+use std::fs::create_dir_all;
+use std::path::Path;
+
+/// 將 `scripts` 連同其類型、標籤、創建時間、執行次數（及歷史）打包至 `output`
+/// 壓縮格式由 `output` 的擴展名決定，如 `.tar`、`.tar.gz`
+pub async fn export(scripts: Vec<ScriptInfo>, output: &Path, with_history: bool) -> Result {
+    let output = path::normalize_path(output)?;
+    let tmp = TmpDir::new("export")?;
+    let bundle = tmp.0.join("bundle");
+    handle_fs_res(&[&bundle], create_dir_all(&bundle))?;
+
+    let pool = db::create_other(&bundle).await?;
+    let historian = Historian::new(bundle.clone()).await?;
+    let env = DBEnv::new(pool, historian, true);
+
+    let mut id_map = vec![];
+    for info in scripts.iter() {
+        let src_path = path::open_script(&info.name, &info.ty, Some(true))?;
+        let dst_path = bundle.join(info.name.to_file_path_fallback(&info.ty).0);
+        util::cp(&src_path, &dst_path)?;
+        let id = env.handle_import(info).await?;
+        id_map.push((info.id, id));
+        println!("{}", info.name);
+    }
+
+    if with_history {
+        let home = path::get_home();
+        let event_map = env
+            .historian
+            .import_events(home, &tmp.0.join("history.db"), &id_map)
+            .await?;
+        for (src_id, new_id) in event_map.into_iter() {
+            let src_log = path::get_run_log_in(home, src_id);
+            if src_log.exists() {
+                util::cp(&src_log, &path::get_run_log_in(&bundle, new_id))?;
+            }
+        }
+    }
+    env.close().await;
+
+    log::info!("打包至 {:?}", output);
+    run_tool(
+        "tar",
+        &[
+            "-caf".as_ref(),
+            output.as_os_str(),
+            "-C".as_ref(),
+            bundle.as_os_str(),
+            ".".as_ref(),
+        ],
+    )
+}
@@ -1,6 +1,9 @@
 const HELP_KEY: &str = "[HS_HELP]:";
 const ENV_KEY: &str = "[HS_ENV]:";
 const ENV_HELP_KEY: &str = "[HS_ENV_HELP]:";
+const ARG_KEY: &str = "[HS_ARG]:";
+const FLAG_KEY: &str = "[HS_FLAG]:";
+const COMPLETE_KEY: &str = "[HS_COMPLETE]:";
 
 pub struct Iter<'a, 'b> {
     content: &'a str,
@@ -52,6 +55,26 @@ pub fn extract_help_from_content(content: &str) -> impl Iterator<Item = &str> {
     extract_msg_from_content(content, HELP_KEY).map(|s| trim_first_white(s))
 }
 
+/// 第一個布林值為真代表 HS_ARG，為假代表 HS_FLAG
+pub fn extract_arg_from_content(content: &str) -> impl Iterator<Item = (bool, &str)> {
+    let arg_iter = extract_msg_from_content(content, ARG_KEY).map(|s| (true, s));
+    let flag_iter = extract_msg_from_content(content, FLAG_KEY).map(|s| (false, s));
+    arg_iter.chain(flag_iter).filter_map(|(b, s)| {
+        let s = s.trim();
+        if s.is_empty() {
+            None
+        } else {
+            Some((b, s))
+        }
+    })
+}
+
+pub fn extract_complete_from_content(content: &str) -> impl Iterator<Item = &str> {
+    extract_msg_from_content(content, COMPLETE_KEY)
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+}
+
 fn extract_msg_from_content<'a, 'b>(content: &'a str, key: &'b str) -> Iter<'a, 'b> {
     Iter { content, key }
 }
@@ -54,6 +54,20 @@ impl<T> FuzzResult<T> {
 
 static MATCHER: State<SkimMatcherV2> = State::new();
 
+fn init_matcher() {
+    crate::set_once!(MATCHER, || {
+        let mut conf = SkimScoreConfig::default();
+        conf.bonus_consecutive *= 4;
+        SkimMatcherV2::default().score_config(conf)
+    });
+}
+
+/// 單一候選者的分數，和 `fuzz` 用的是同一套算法。不相符時回傳 `None`
+pub fn fuzz_score(choice: &str, pattern: &str, sep: &str) -> Option<i64> {
+    init_matcher();
+    my_fuzz(choice, pattern, sep, true)
+}
+
 pub trait FuzzKey {
     fn fuzz_key(&self) -> Cow<'_, str>;
 }
@@ -148,11 +162,7 @@ pub async fn fuzz_with_multifuzz_ratio<'a, T: FuzzKey + Send + 'a>(
     let sep = MyRaw::new(sep);
     let has_ratio = multifuzz_ratio.is_some();
 
-    crate::set_once!(MATCHER, || {
-        let mut conf = SkimScoreConfig::default();
-        conf.bonus_consecutive *= 4;
-        SkimMatcherV2::default().score_config(conf)
-    });
+    init_matcher();
 
     let score_fut = data_vec.iter_mut().map(|(score, data)| {
         let key = MyCow::new(data.fuzz_key());
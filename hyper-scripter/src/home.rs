@@ -0,0 +1,106 @@
+//! 管理多個腳本之家：全域的腳本之家，以及從當前目錄往上找到的專案腳本之家（`.hyper_scripter/`）
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::path::{self, HomeSource};
+use crate::util::{self, handle_fs_res};
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// 列出全域腳本之家及所有找得到的專案腳本之家，以 `*` 標出正在使用者
+pub fn ls() -> Result {
+    let current = path::get_home();
+    let mut homes = vec![];
+    let here = std::env::current_dir()?;
+    for p in path::find_project_homes(&here).into_iter() {
+        homes.push((p, HomeSource::Project));
+    }
+    homes.push(path::get_global_home()?);
+
+    let mut found_current = false;
+    for (p, source) in homes.into_iter() {
+        let p = path::normalize_path(p)?;
+        let redirect = path::read_redirect(&p)?;
+        let is_current = redirect.as_deref().unwrap_or(&p) == current;
+        found_current |= is_current;
+        // LOCALE
+        print!(
+            "{} {} ({})",
+            if is_current { '*' } else { ' ' },
+            p.display(),
+            source
+        );
+        if let Some(redirect) = redirect {
+            print!(" -> {}", redirect.display());
+        }
+        println!();
+    }
+    if !found_current {
+        println!("* {} ({})", current.display(), HomeSource::Flag);
+    }
+    Ok(())
+}
+
+/// 印出正在使用的腳本之家及其來源
+pub fn which(hs_home: Option<&String>) -> Result {
+    let (_, source) = path::compute_home_path_with_source(hs_home, false)?;
+    println!("{} ({})", path::get_home().display(), source);
+    Ok(())
+}
+
+/// 將全域腳本之家重導向至 `target`。未給定 `target`（或其即為全域腳本之家）時，移除重導向
+pub fn switch(target: Option<&str>) -> Result {
+    let global = path::normalize_path(path::get_global_home()?.0)?;
+    let redirect_file = global.join(path::HS_REDIRECT);
+    let target = match target {
+        Some(t) => Some(path::normalize_path(t)?),
+        None => None,
+    };
+    match target {
+        Some(target) if target != global => {
+            if !target.is_dir() {
+                return Err(Error::PathNotFound(vec![target]));
+            }
+            log::info!("將 {:?} 重導向至 {:?}", global, target);
+            handle_fs_res(&[&global], create_dir_all(&global))?;
+            util::write_file(&redirect_file, &target.to_string_lossy())?;
+        }
+        _ => {
+            if redirect_file.exists() {
+                log::info!("移除 {:?} 的重導向", global);
+                util::remove(&redirect_file)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 若正在使用的是專案腳本之家，且其設定要疊在全域腳本之家之上，回傳全域腳本之家的路徑
+pub fn layered_global(hs_home: Option<&String>) -> Result<Option<PathBuf>> {
+    if !Config::get().layer_global {
+        return Ok(None);
+    }
+    let (_, source) = path::compute_home_path_with_source(hs_home, false)?;
+    if source != HomeSource::Project {
+        log::warn!("layer_global 只對專案腳本之家有效，目前的來源是 {}", source);
+        return Ok(None);
+    }
+    let global = match path::compute_home_path(path::get_global_home()?.0, false) {
+        Ok(global) => global,
+        Err(Error::PathNotFound(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if global == path::get_home() {
+        return Ok(None);
+    }
+    Ok(Some(global))
+}
+
+/// 在 `home` 中重新執行同樣的命令，回傳其結束碼
+pub fn rerun_in(home: &Path, args: &[String]) -> Result<i32> {
+    log::info!("改在 {:?} 中執行 {:?}", home, args);
+    let mut cmd = Command::new(std::env::current_exe()?);
+    cmd.arg("-H").arg(home).args(args);
+    Ok(util::run_cmd(cmd)?.unwrap_or(0))
+}
@@ -0,0 +1,115 @@
+//! 腳本執行完後的鉤子命令。`post_run` 一定會執行，`on_success` 和 `on_failure` 則看腳本的回傳值。
+//! 可以設定在全域、腳本類別及標籤上，依此順序以 `sh -c` 執行。
+//! 鉤子的結果另外記錄，不會影響腳本本身的執行紀錄與回傳值
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::script_repo::RepoEntry;
+use crate::util::{self, TmplVal};
+use chrono::Utc;
+use hyper_scripter_historian::{ExecUsage, HookRecord};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_run: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_success: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.post_run.is_none() && self.on_success.is_none() && self.on_failure.is_none()
+    }
+    /// 依回傳值該執行的鉤子，附上鉤子的名字
+    fn iter(&self, success: bool) -> impl Iterator<Item = (&'static str, &str)> {
+        let outcome = if success {
+            ("on_success", &self.on_success)
+        } else {
+            ("on_failure", &self.on_failure)
+        };
+        [("post_run", &self.post_run), outcome]
+            .into_iter()
+            .filter_map(|(name, cmd)| Some((name, cmd.as_deref()?)))
+    }
+}
+
+/// 依序執行符合的鉤子並記錄結果。鉤子本身的錯誤只會警告，不會中止
+pub async fn run_hooks(
+    entry: &RepoEntry<'_>,
+    tmpl_val: &TmplVal<'_>,
+    run_id: i64,
+    code: i32,
+    usage: &ExecUsage,
+) -> Result {
+    let conf = Config::get();
+    let mut hooks = vec![&conf.hooks];
+    if let Ok(ty_conf) = conf.get_script_conf(&entry.ty) {
+        hooks.push(&ty_conf.hooks);
+    }
+    let mut tags: Vec<_> = entry.tags.iter().map(|t| t.as_ref()).collect();
+    tags.sort();
+    hooks.extend(tags.into_iter().filter_map(|t| conf.tag_hooks.get(t)));
+    if hooks.iter().all(|h| h.is_empty()) {
+        return Ok(());
+    }
+
+    let name = entry.name.key();
+    let env = conf.gen_env(tmpl_val, false)?;
+    for (hook, cmd) in hooks.iter().flat_map(|h| h.iter(code == 0)) {
+        log::info!("執行鉤子 {} {:?}", hook, cmd);
+        let mut command = util::create_cmd("sh", ["-c", cmd]);
+        command.envs(env.iter().map(|(a, b)| (a, b)));
+        command.env("HS_HOOK", hook);
+        command.env("HS_RUN_ID", run_id.to_string());
+        command.env("HS_EXIT_CODE", code.to_string());
+        command.env("HS_DURATION_MS", usage.elapsed_ms.to_string());
+        command.env("HS_NAME", name.as_ref());
+
+        let start = Instant::now();
+        let hook_code = match util::run_cmd(command) {
+            Ok(hook_code) => hook_code.unwrap_or_default(),
+            Err(err) => {
+                log::warn!("鉤子 {} 無法執行：{:?}", hook, err);
+                continue;
+            }
+        };
+        if hook_code != 0 {
+            log::warn!("鉤子 {} 回傳 {}", hook, hook_code);
+        }
+        let record = HookRecord {
+            main_event_id: run_id,
+            script_id: entry.id,
+            hook: hook.to_owned(),
+            cmd: cmd.to_owned(),
+            code: hook_code,
+            elapsed_ms: start.elapsed().as_millis() as i64,
+            time: Utc::now().naive_utc(),
+        };
+        entry.get_env().record_hook(&record).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hooks_iter() {
+        let hooks = Hooks {
+            post_run: Some("a".to_owned()),
+            on_success: None,
+            on_failure: Some("c".to_owned()),
+        };
+        let names = |success| hooks.iter(success).map(|(n, _)| n).collect::<Vec<_>>();
+        assert_eq!(names(true), ["post_run"]);
+        assert_eq!(names(false), ["post_run", "on_failure"]);
+        assert!(!hooks.is_empty());
+        assert!(Hooks::default().is_empty());
+    }
+}
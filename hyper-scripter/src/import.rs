@@ -0,0 +1,242 @@
+//! 從另一個腳本之家（或其 git 倉庫、壓縮檔）匯入腳本，連同其創建時間、執行次數及歷史
+
+use crate::args::ConflictPolicy;
+use crate::db;
+use crate::error::{Contextable, Error, Result};
+use crate::path;
+use crate::script::{IntoScriptName, ScriptInfo, ScriptName};
+use crate::script_repo::{load_script_infos, ScriptRepo, Visibility};
+use crate::script_type::ScriptType;
+use crate::util::{self, handle_fs_res};
+use fxhash::FxHashSet as HashSet;
+use std::fs::{create_dir_all, read_dir, remove_dir_all};
+use std::path::{Path, PathBuf};
+
+/// 匯入、匯出過程用到的暫存資料夾，結束時刪除
+pub(crate) struct TmpDir(pub PathBuf);
+impl TmpDir {
+    pub fn new(purpose: &str) -> Result<Self> {
+        let p = std::env::temp_dir().join(format!("hs-{}-{}", purpose, std::process::id()));
+        if p.exists() {
+            handle_fs_res(&[&p], remove_dir_all(&p))?;
+        }
+        handle_fs_res(&[&p], create_dir_all(&p))?;
+        Ok(TmpDir(p))
+    }
+}
+impl Drop for TmpDir {
+    fn drop(&mut self) {
+        if let Err(e) = remove_dir_all(&self.0) {
+            log::warn!("無法刪除暫存資料夾 {:?}：{}", self.0, e);
+        }
+    }
+}
+
+pub(crate) fn run_tool(name: &str, args: &[&std::ffi::OsStr]) -> Result {
+    let cmd = util::create_cmd(name, args);
+    if let Some(code) = util::run_cmd(cmd)? {
+        let mut cmd = vec![name.to_owned()];
+        cmd.extend(args.iter().map(|s| s.to_string_lossy().into_owned()));
+        return Err(Error::CommandError(code, cmd));
+    }
+    Ok(())
+}
+
+/// 將來源轉為本地的資料夾。資料夾直接使用，檔案視為壓縮檔，其它視為 git 網址
+fn resolve_source(src: &str, tmp: &Path) -> Result<PathBuf> {
+    let src_path = Path::new(src);
+    if src_path.is_dir() {
+        return path::normalize_path(src_path);
+    }
+    let dir = tmp.join("src");
+    if src_path.is_file() {
+        log::info!("解開壓縮檔 {:?}", src_path);
+        handle_fs_res(&[&dir], create_dir_all(&dir))?;
+        run_tool(
+            "tar",
+            &[
+                "-xf".as_ref(),
+                src_path.as_os_str(),
+                "-C".as_ref(),
+                dir.as_os_str(),
+            ],
+        )?;
+        // NOTE: 壓縮檔裡常常會多包一層資料夾
+        let mut entries = handle_fs_res(&[&dir], read_dir(&dir))?
+            .collect::<std::io::Result<Vec<_>>>()
+            .map_err(|e| util::handle_fs_err(&[&dir], e))?;
+        if entries.len() == 1 && entries[0].path().is_dir() {
+            return Ok(entries.pop().unwrap().path());
+        }
+        return Ok(dir);
+    }
+    log::info!("從 {} 複製 git 倉庫", src);
+    run_tool(
+        "git",
+        &[
+            "clone".as_ref(),
+            "--depth".as_ref(),
+            "1".as_ref(),
+            src.as_ref(),
+            dir.as_os_str(),
+        ],
+    )?;
+    Ok(dir)
+}
+
+fn is_same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+struct Plan {
+    info: ScriptInfo,
+    src_path: PathBuf,
+    new_name: ScriptName,
+    /// 被覆蓋的腳本
+    replace: Option<(i64, PathBuf)>,
+}
+
+fn with_namespace(name: &ScriptName, namespace: Option<&str>) -> Result<ScriptName> {
+    match namespace {
+        Some(ns) => format!("{}/{}", ns, name.key()).into_script_name(),
+        None => Ok(name.clone()),
+    }
+}
+
+fn local_path(name: &ScriptName, ty: &ScriptType) -> PathBuf {
+    path::get_home().join(name.to_file_path_fallback(ty).0)
+}
+
+/// 是否已被本地的腳本、檔案或本次匯入的其它腳本佔用
+fn is_taken(
+    repo: &mut ScriptRepo,
+    name: &ScriptName,
+    ty: &ScriptType,
+    planned: &HashSet<String>,
+) -> Result<bool> {
+    if planned.contains(name.key().as_ref()) || repo.get_mut(name, Visibility::All).is_some() {
+        return Ok(true);
+    }
+    Ok(local_path(name, ty).exists())
+}
+
+/// 匯入 `src` 中的所有腳本。會先決定好每支腳本的去向，才開始動到檔案和資料庫
+pub async fn import(
+    repo: &mut ScriptRepo,
+    src: &str,
+    namespace: Option<&str>,
+    policy: ConflictPolicy,
+    no_history: bool,
+) -> Result {
+    let tmp = TmpDir::new("import")?;
+    let src_dir = resolve_source(src, &tmp.0)?;
+    if is_same_dir(&src_dir, path::get_home()) {
+        return Err(Error::ImportFromSelf(src_dir));
+    }
+    log::info!("從 {:?} 匯入", src_dir);
+
+    let pool = db::open_other(&src_dir, &tmp.0.join("info.db")).await?;
+    let infos = load_script_infos(&pool).await?;
+    pool.close().await;
+
+    let anonymous_count = infos.iter().filter(|i| i.name.is_anonymous()).count();
+    let mut new_anonymous = path::new_anonymous_name(anonymous_count as u32, std::iter::empty())
+        .context("無法取得新匿名腳本")?;
+    let mut planned = HashSet::<String>::default();
+    let mut plans = vec![];
+    for info in infos.into_iter() {
+        let src_path = src_dir.join(info.name.to_file_path_fallback(&info.ty).0);
+        if !src_path.exists() {
+            log::warn!("{:?} 不存在，不匯入", src_path);
+            eprintln!("{} skipped: file not found", info.name);
+            continue;
+        }
+        let mut replace = None;
+        let new_name = if info.name.is_anonymous() {
+            // NOTE: 匿名腳本的編號沒有意義，一律取新的
+            new_anonymous.next().unwrap()
+        } else {
+            let name = with_namespace(&info.name, namespace)?;
+            if !is_taken(repo, &name, &info.ty, &planned)? {
+                name
+            } else {
+                match policy {
+                    ConflictPolicy::Skip => {
+                        eprintln!("{} skipped: {} already exists", info.name, name);
+                        continue;
+                    }
+                    ConflictPolicy::Rename => {
+                        let mut i = 1;
+                        loop {
+                            let candidate = format!("{}-{}", name.key(), i).into_script_name()?;
+                            if !is_taken(repo, &candidate, &info.ty, &planned)? {
+                                break candidate;
+                            }
+                            i += 1;
+                        }
+                    }
+                    ConflictPolicy::Overwrite => {
+                        if planned.contains(name.key().as_ref()) {
+                            eprintln!("{} skipped: {} is already imported", info.name, name);
+                            continue;
+                        }
+                        if let Some(old) = repo.get_mut(&name, Visibility::All) {
+                            replace = Some((old.id, local_path(&name, &old.ty)));
+                        }
+                        name
+                    }
+                }
+            }
+        };
+        planned.insert(new_name.key().into_owned());
+        plans.push(Plan {
+            info,
+            src_path,
+            new_name,
+            replace,
+        });
+    }
+
+    let mut id_map = vec![];
+    for plan in plans.into_iter() {
+        let Plan {
+            mut info,
+            src_path,
+            new_name,
+            replace,
+        } = plan;
+        if let Some((id, old_path)) = replace {
+            log::info!("覆蓋腳本 {}", new_name);
+            repo.remove(id).await?;
+            if old_path.exists() {
+                util::remove(&old_path)?;
+            }
+        }
+        let dst_path = local_path(&new_name, &info.ty);
+        util::cp(&src_path, &dst_path)?;
+
+        let src_id = info.id;
+        let src_name = std::mem::replace(&mut info.name, new_name);
+        let entry = repo.entry(&info.name).import(info).await?;
+        println!("{} -> {}", src_name, entry.name);
+        id_map.push((src_id, entry.id));
+    }
+
+    if no_history || id_map.is_empty() {
+        return Ok(());
+    }
+    let event_map = repo
+        .historian()
+        .import_events(&src_dir, &tmp.0.join("history.db"), &id_map)
+        .await?;
+    for (src_id, new_id) in event_map.into_iter() {
+        let src_log = path::get_run_log_in(&src_dir, src_id);
+        if src_log.exists() {
+            util::cp(&src_log, &path::get_run_log(new_id)?)?;
+        }
+    }
+    Ok(())
+}
@@ -2,28 +2,44 @@
 extern crate serde_json;
 #[macro_use]
 extern crate derive_more;
+pub mod arg_schema;
 pub mod args;
+pub mod collect;
 pub mod color;
 pub mod config;
+pub mod config_check;
+pub mod config_layer;
+pub mod cron;
 pub mod db;
+pub mod diff;
 pub mod env_pair;
 pub mod error;
 mod error_display;
+pub mod export;
 pub mod extract_msg;
 pub mod fuzzy;
+pub mod home;
+pub mod hook;
+pub mod import;
 pub mod list;
 pub mod migration;
 pub mod my_env_logger;
+pub mod output;
 pub mod path;
+pub mod picker;
 pub mod process_lock;
 pub mod query;
+pub mod schedule;
 pub mod script;
 pub mod script_repo;
 pub mod script_time;
 pub mod script_type;
 pub mod state;
+pub mod stats;
+pub mod systemd;
 pub mod tag;
 pub mod util;
+pub mod vcs;
 
 pub use std::borrow::Cow;
 
@@ -0,0 +1,90 @@
+use super::style;
+use crate::color::Color;
+use crate::error::Result;
+use crate::query::{do_list_query, ListQuery};
+use crate::script::ScriptInfo;
+use crate::script_repo::{ScriptRepo, Visibility};
+use crate::util::get_display_type;
+use std::cmp::Reverse;
+use std::io::Write;
+
+pub fn fmt_filter_explain<W: Write>(
+    w: &mut W,
+    repo: &ScriptRepo,
+    script: &ScriptInfo,
+    plain: bool,
+) -> Result {
+    let explain = repo.explain_filter(script);
+    let ty = get_display_type(&script.ty);
+    let ident = format!("{}({})", script.name, ty.display());
+    let ident = style(plain, ident, |s| {
+        s.color(ty.color()).bold();
+    });
+    let verdict = if explain.is_hidden() {
+        style(plain, "hidden", |s| {
+            s.color(Color::Red);
+        })
+    } else {
+        style(plain, "shown", |s| {
+            s.color(Color::Green);
+        })
+    };
+    writeln!(w, "{}: {}", ident, verdict)?;
+
+    let time_msg = match (explain.overtime, explain.archaeology) {
+        (false, false) => "pass",
+        (true, false) => "too old (try --timeless)",
+        (true, true) => "pass (archaeology)",
+        (false, true) => "too new for archaeology",
+    };
+    writeln!(w, "    recent: {}", time_msg)?;
+
+    for (i, step) in explain.tags.steps.iter().enumerate() {
+        let decisive = explain.tags.decisive == Some(i);
+        let line = format!(
+            "{} [{}] {} => {}",
+            if decisive { "*" } else { " " },
+            step.label.unwrap_or("-"),
+            step.selector,
+            step.result
+        );
+        let line = style(plain, line, |s| {
+            if decisive {
+                s.bold();
+            } else {
+                s.dimmed();
+            }
+        });
+        writeln!(w, "  {}", line)?;
+    }
+    if explain.tags.decisive.is_none() {
+        writeln!(w, "    no selector hit, hidden by default")?;
+    }
+    Ok(())
+}
+
+/// 若沒有給定查詢，則解釋所有的腳本，包含被篩掉的
+pub async fn fmt_explain_list<W: Write>(
+    w: &mut W,
+    script_repo: &mut ScriptRepo,
+    plain: bool,
+    queries: Vec<ListQuery>,
+) -> Result {
+    let mut scripts: Vec<ScriptInfo> = if queries.is_empty() {
+        script_repo
+            .iter_mut(Visibility::All)
+            .map(|e| e.into_inner().clone())
+            .collect()
+    } else {
+        do_list_query(script_repo, queries)
+            .await?
+            .into_iter()
+            .map(|e| e.into_inner().clone())
+            .collect()
+    };
+    scripts.sort_by_key(|s| Reverse(s.last_time()));
+    for script in scripts.iter() {
+        fmt_filter_explain(w, script_repo, script, plain)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use crate::error::{DisplayError, DisplayResult, Result};
+use crate::query::TimeQuery;
+use crate::script::ScriptInfo;
+use crate::script_repo::ScriptRepo;
+use chrono::NaiveDateTime;
+use fxhash::FxHashMap as HashMap;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::ops::Deref;
+use std::str::FromStr;
+
+pub const SORT_VALUES: &[&str] = &[
+    "recent",
+    "name",
+    "created",
+    "written",
+    "executed",
+    "exec-count",
+    "type",
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Default)]
+pub enum SortKey {
+    #[default]
+    Recent,
+    Name,
+    Created,
+    Written,
+    Executed,
+    ExecCount,
+    Type,
+}
+impl FromStr for SortKey {
+    type Err = DisplayError;
+    fn from_str(s: &str) -> DisplayResult<Self> {
+        let k = match s {
+            "recent" => SortKey::Recent,
+            "name" => SortKey::Name,
+            "created" => SortKey::Created,
+            "written" => SortKey::Written,
+            "executed" => SortKey::Executed,
+            "exec-count" => SortKey::ExecCount,
+            "type" => SortKey::Type,
+            _ => unreachable!(),
+        };
+        Ok(k)
+    }
+}
+
+/// 名稱和類型由小到大，其它的都是由大到小（最新、最多次的在前）
+pub fn sort_scripts<T: Deref<Target = ScriptInfo>>(v: &mut [T], key: SortKey, reverse: bool) {
+    match key {
+        SortKey::Recent => v.sort_by_key(|s| Reverse(s.last_time())),
+        SortKey::Name => v.sort_by(|a, b| a.name.key().cmp(&b.name.key())),
+        SortKey::Created => v.sort_by_key(|s| Reverse(*s.created_time)),
+        SortKey::Written => v.sort_by_key(|s| Reverse(*s.write_time)),
+        SortKey::Executed => v.sort_by_key(|s| Reverse(s.exec_time.as_ref().map(|t| **t))),
+        SortKey::ExecCount => v.sort_by_key(|s| Reverse(s.exec_count)),
+        SortKey::Type => v.sort_by(|a, b| {
+            let a = (a.ty.as_ref(), a.name.key());
+            let b = (b.ty.as_ref(), b.name.key());
+            a.cmp(&b)
+        }),
+    }
+    if reverse {
+        v.reverse();
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ListFilter {
+    pub failed: bool,
+    pub never_run: bool,
+    pub created_since: Option<TimeQuery>,
+    pub written_since: Option<TimeQuery>,
+}
+
+/// 已備妥所需資料的篩選器，見 `ListFilter::prepare`
+pub struct PreparedFilter {
+    exit_codes: Option<HashMap<i64, i32>>,
+    never_run: bool,
+    created_since: Option<NaiveDateTime>,
+    written_since: Option<NaiveDateTime>,
+}
+
+impl ListFilter {
+    /// 事先向史學家查詢最後的回傳值，並把時間轉換成 UTC
+    pub async fn prepare(&self, repo: &ScriptRepo) -> Result<PreparedFilter> {
+        let exit_codes = if self.failed {
+            let codes = repo.historian().last_exit_codes().await?;
+            Some(codes.into_iter().collect())
+        } else {
+            None
+        };
+        Ok(PreparedFilter {
+            exit_codes,
+            never_run: self.never_run,
+            created_since: self.created_since.map(|t| t.to_utc()),
+            written_since: self.written_since.map(|t| t.to_utc()),
+        })
+    }
+}
+
+impl PreparedFilter {
+    pub fn check(&self, script: &ScriptInfo) -> bool {
+        if let Some(exit_codes) = &self.exit_codes {
+            match exit_codes.get(&script.id) {
+                Some(code) if *code != 0 => (),
+                _ => return false,
+            }
+        }
+        if self.never_run && script.exec_time.is_some() {
+            return false;
+        }
+        if let Some(t) = self.created_since {
+            if *script.created_time < t {
+                return false;
+            }
+        }
+        if let Some(t) = self.written_since {
+            if *script.write_time < t {
+                return false;
+            }
+        }
+        true
+    }
+}
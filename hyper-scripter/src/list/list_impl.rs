@@ -1,8 +1,8 @@
 use super::{
-    exec_time_str, extract_help, get_screen_width, style, style_name,
+    exec_time_str, extract_help, get_screen_width, sort_scripts, style, style_name,
     table_lib::{Cell, Collumn, Table},
-    time_fmt, tree, DisplayIdentStyle, DisplayStyle, Grid, Grouping, ListOptions, LONG_LATEST_TXT,
-    SHORT_LATEST_TXT,
+    time_fmt, tree, DisplayIdentStyle, DisplayStyle, Grid, Grouping, ListOptions, SortKey,
+    LONG_LATEST_TXT, SHORT_LATEST_TXT,
 };
 use crate::error::Result;
 use crate::query::{do_list_query, ListQuery};
@@ -11,7 +11,6 @@ use crate::script_repo::{ScriptRepo, Visibility};
 use crate::tag::Tag;
 use crate::util::get_display_type;
 use fxhash::FxHashMap as HashMap;
-use std::cmp::Reverse;
 use std::hash::Hash;
 use std::io::Write;
 
@@ -62,10 +61,6 @@ impl TagsKey {
     }
 }
 
-fn sort_scripts(v: &mut Vec<&ScriptInfo>) {
-    v.sort_by_key(|s| Reverse(s.last_time()));
-}
-
 fn convert_opt<T>(opt: ListOptions, t: T) -> ListOptions<Table, T> {
     ListOptions {
         display_style: match opt.display_style {
@@ -81,6 +76,9 @@ fn convert_opt<T>(opt: ListOptions, t: T) -> ListOptions<Table, T> {
         grouping: opt.grouping,
         plain: opt.plain,
         limit: opt.limit,
+        sort: opt.sort,
+        reverse: opt.reverse,
+        filter: opt.filter,
     }
 }
 fn extract_table<U>(opt: ListOptions<Table, U>) -> Option<Table> {
@@ -134,10 +132,10 @@ enum ScriptsEither<'a, I> {
     V(Vec<&'a ScriptInfo>),
 }
 impl<'a, I: ExactSizeIterator<Item = &'a ScriptInfo>> ScriptsEither<'a, I> {
-    fn new(iter: I, limit: Option<usize>) -> Self {
+    fn new(iter: I, limit: Option<usize>, sort: SortKey, reverse: bool) -> Self {
         if let Some(limit) = limit {
             let mut v: Vec<_> = iter.collect();
-            sort_scripts(&mut v);
+            sort_scripts(&mut v, sort, reverse);
             v.truncate(limit);
             Self::V(v)
         } else {
@@ -195,11 +193,19 @@ pub async fn fmt_list<W: Write>(
         .latest_mut(1, Visibility::Normal)
         .map_or(-1, |s| s.id);
 
-    let scripts_iter = do_list_query(script_repo, queries)
+    let filter = opt.filter.prepare(script_repo).await?;
+    let scripts: Vec<_> = do_list_query(script_repo, queries)
         .await?
         .into_iter()
-        .map(|e| &*e.into_inner());
-    let scripts_either = ScriptsEither::new(scripts_iter, opt.limit.map(|l| l.get()));
+        .map(|e| &*e.into_inner())
+        .filter(|s| filter.check(s))
+        .collect();
+    let scripts_either = ScriptsEither::new(
+        scripts.into_iter(),
+        opt.limit.map(|l| l.get()),
+        opt.sort,
+        opt.reverse,
+    );
     let sorted = scripts_either.sorted();
 
     let final_table: Option<Table>;
@@ -280,7 +286,7 @@ fn fmt_group<W: Write>(
     opt: &mut ListOptionWithOutput,
 ) -> Result<()> {
     if !sorted {
-        sort_scripts(&mut scripts);
+        sort_scripts(&mut scripts, opt.sort, opt.reverse);
     }
     for script in scripts.into_iter() {
         let is_latest = script.id == latest_script_id;
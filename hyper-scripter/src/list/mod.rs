@@ -4,6 +4,12 @@ pub use list_impl::*;
 mod grid;
 pub use grid::Grid;
 
+mod explain;
+pub use explain::*;
+
+mod filter;
+pub use filter::*;
+
 mod table_lib;
 mod time_fmt;
 mod tree;
@@ -134,6 +140,9 @@ pub struct ListOptions<T = (), U = ()> {
     pub grouping: Grouping,
     pub plain: bool,
     pub limit: Option<NonZeroUsize>,
+    pub sort: SortKey,
+    pub reverse: bool,
+    pub filter: ListFilter,
     pub display_style: DisplayStyle<T, U>,
 }
 
@@ -0,0 +1,186 @@
+//! 機器可讀的輸出格式
+//!
+//! NOTE: 這裡的欄位名稱是對外承諾的介面，只可新增，不可修改或刪除。欄位說明見 README
+
+use crate::env_pair::EnvPair;
+use crate::error::{DisplayError, DisplayResult, Result};
+use crate::extract_msg::extract_help_from_content;
+use crate::script::ScriptInfo;
+use crate::script_type::ScriptType;
+use chrono::NaiveDateTime;
+use hyper_scripter_historian::ExecUsage;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const FORMAT_VALUES: &[&str] = &["human", "json", "ndjson"];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Default)]
+pub enum OutputFormat {
+    #[default]
+    Human,
+    Json,
+    Ndjson,
+}
+impl OutputFormat {
+    pub fn is_human(self) -> bool {
+        self == OutputFormat::Human
+    }
+}
+impl FromStr for OutputFormat {
+    type Err = DisplayError;
+    fn from_str(s: &str) -> DisplayResult<Self> {
+        let f = match s {
+            "human" => OutputFormat::Human,
+            "json" => OutputFormat::Json,
+            "ndjson" => OutputFormat::Ndjson,
+            _ => unreachable!(),
+        };
+        Ok(f)
+    }
+}
+
+/// `json` 印出一整個陣列，`ndjson` 則一行一筆
+pub fn write_records<W: Write, T: Serialize>(
+    w: &mut W,
+    format: OutputFormat,
+    records: impl IntoIterator<Item = T>,
+) -> Result {
+    match format {
+        OutputFormat::Human => panic!("人類可讀格式不該由此輸出"),
+        OutputFormat::Json => {
+            let records: Vec<_> = records.into_iter().collect();
+            serde_json::to_writer(&mut *w, &records)?;
+            writeln!(w)?;
+        }
+        OutputFormat::Ndjson => {
+            for record in records.into_iter() {
+                serde_json::to_writer(&mut *w, &record)?;
+                writeln!(w)?;
+            }
+        }
+    }
+    Ok(())
+}
+/// 單一物件，`json` 和 `ndjson` 的輸出相同
+pub fn write_record<W: Write, T: Serialize>(w: &mut W, record: T) -> Result {
+    serde_json::to_writer(&mut *w, &record)?;
+    writeln!(w)?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScriptRecord<'a> {
+    pub id: i64,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: &'a ScriptType,
+    pub tags: Vec<&'a str>,
+    pub path: PathBuf,
+    pub created_time: NaiveDateTime,
+    pub write_time: NaiveDateTime,
+    pub read_time: NaiveDateTime,
+    pub exec_time: Option<NaiveDateTime>,
+    pub exec_done_time: Option<NaiveDateTime>,
+    pub exec_count: u64,
+    pub last_exit_code: Option<i32>,
+    pub help: Vec<String>,
+}
+impl<'a> ScriptRecord<'a> {
+    /// `last_exit_code` 需從史學家取得，見 `Historian::last_exit_codes`
+    pub fn new(script: &'a ScriptInfo, last_exit_code: Option<i32>) -> Self {
+        let mut tags: Vec<_> = script.tags.iter().map(|t| t.as_ref()).collect();
+        tags.sort();
+        ScriptRecord {
+            id: script.id,
+            name: script.name.key().to_string(),
+            ty: &script.ty,
+            tags,
+            path: crate::path::get_home().join(script.file_path_fallback()),
+            created_time: *script.created_time,
+            write_time: *script.write_time,
+            read_time: *script.read_time,
+            exec_time: script.exec_time.as_ref().map(|t| **t),
+            exec_done_time: script.exec_done_time.as_ref().map(|t| **t),
+            exec_count: script.exec_count,
+            last_exit_code,
+            help: extract_all_help(script),
+        }
+    }
+}
+
+fn extract_all_help(script: &ScriptInfo) -> Vec<String> {
+    let content = crate::path::open_script(&script.name, &script.ty, Some(true))
+        .and_then(|p| crate::util::read_file(&p));
+    match content {
+        Ok(content) => extract_help_from_content(&content)
+            .map(|s| s.to_owned())
+            .collect(),
+        Err(e) => {
+            log::warn!("讀取腳本失敗{}，直接回空的幫助字串", e);
+            vec![]
+        }
+    }
+}
+
+/// `--display=env` 時沒有 `args` 欄位，`--display=args` 時沒有 `envs` 欄位，
+/// 沒有 `--usage` 時沒有 `last_run` 欄位。`event_id` 和 `time` 是該組參數最新一次執行的事件
+#[derive(Debug, Serialize)]
+pub struct HistoryRecord {
+    pub event_id: i64,
+    pub time: NaiveDateTime,
+    pub script_id: i64,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub envs: Option<Vec<EnvPair>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<LastRunRecord>,
+}
+
+/// 較舊的紀錄只有 `code`
+#[derive(Debug, Serialize)]
+pub struct LastRunRecord {
+    pub code: i32,
+    pub elapsed_ms: Option<i64>,
+    pub signal: Option<i32>,
+    pub user_time_ms: Option<i64>,
+    pub sys_time_ms: Option<i64>,
+    pub max_rss_kb: Option<i64>,
+}
+impl LastRunRecord {
+    pub fn new(code: i32, usage: Option<ExecUsage>) -> Self {
+        let usage = usage.as_ref();
+        LastRunRecord {
+            code,
+            elapsed_ms: usage.map(|u| u.elapsed_ms),
+            signal: usage.and_then(|u| u.signal),
+            user_time_ms: usage.and_then(|u| u.user_time_ms),
+            sys_time_ms: usage.and_then(|u| u.sys_time_ms),
+            max_rss_kb: usage.and_then(|u| u.max_rss_kb),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NamedSelectorRecord<'a> {
+    pub name: &'a str,
+    pub content: String,
+    pub inactivated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagsRecord<'a> {
+    pub known_tags: Vec<&'a str>,
+    pub caution_tags: String,
+    pub selectors: Vec<NamedSelectorRecord<'a>>,
+    pub main_selector: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeRecord<'a> {
+    pub name: &'a ScriptType,
+    pub sub_types: Vec<ScriptType>,
+}
@@ -8,8 +8,11 @@ use std::fs::{create_dir, create_dir_all, read_dir};
 use std::path::{Component, Path, PathBuf};
 
 pub const HS_REDIRECT: &str = ".hs_redirect";
+/// 專案腳本之家的資料夾名，會從當前目錄往上一路尋找，就像 `.git`
+pub const PROJECT_HOME: &str = ".hyper_scripter";
 pub const HS_PRE_RUN: &str = ".hs_prerun";
 const PROCESS_LOCK: &str = ".hs_process_lock";
+const RUN_LOG: &str = ".hs_run_logs";
 const TEMPLATE: &str = ".hs_templates";
 const HBS_EXT: &str = ".hbs";
 
@@ -18,6 +21,7 @@ macro_rules! hs_home_env {
         "HYPER_SCRIPTER_HOME"
     };
 }
+pub const HOME_ENV: &str = hs_home_env!();
 
 crate::local_global_state!(home_state, PathBuf, || { get_test_home() });
 
@@ -39,18 +43,60 @@ fn get_default_home() -> Result<PathBuf> {
     Ok(home.into())
 }
 
-fn get_sys_home() -> Result<PathBuf> {
+/// 腳本之家的來源，優先序由上而下。環境變數和預設路徑皆視為全域的腳本之家
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomeSource {
+    Flag,
+    Env,
+    Project,
+    Default,
+}
+impl std::fmt::Display for HomeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // LOCALE
+        let s = match self {
+            HomeSource::Flag => "flag",
+            HomeSource::Env => concat!("env ", hs_home_env!()),
+            HomeSource::Project => "project",
+            HomeSource::Default => "default",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// 全域的腳本之家，即不考慮專案腳本之家時會用的那個（尚未重導向）
+pub fn get_global_home() -> Result<(PathBuf, HomeSource)> {
     let p = match std::env::var(hs_home_env!()) {
         Ok(p) => {
             log::debug!("使用環境變數路徑：{}", p);
-            p.into()
+            (p.into(), HomeSource::Env)
         }
-        Err(std::env::VarError::NotPresent) => get_default_home()?,
+        Err(std::env::VarError::NotPresent) => (get_default_home()?, HomeSource::Default),
         Err(e) => return Err(e.into()),
     };
     Ok(p)
 }
 
+/// 從 `dir` 往上找出所有專案腳本之家，近者在前
+pub fn find_project_homes(dir: &Path) -> Vec<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(PROJECT_HOME))
+        .filter(|d| d.is_dir())
+        .collect()
+}
+
+/// 如同 git 的 `GIT_DIR`，明確給定的環境變數優先於往上找到的專案腳本之家
+fn get_sys_home() -> Result<(PathBuf, HomeSource)> {
+    if std::env::var_os(hs_home_env!()).is_none() {
+        let here = std::env::current_dir()?;
+        if let Some(p) = find_project_homes(&here).into_iter().next() {
+            log::debug!("使用專案腳本之家：{:?}", p);
+            return Ok((p, HomeSource::Project));
+        }
+    }
+    get_global_home()
+}
+
 fn join_here_abs<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
     let path = path.as_ref();
     if path.is_absolute() {
@@ -88,7 +134,7 @@ pub fn normalize_path<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
     Ok(ret)
 }
 
-fn compute_home_path<T: AsRef<Path>>(p: T, create_on_missing: bool) -> Result<PathBuf> {
+pub fn compute_home_path<T: AsRef<Path>>(p: T, create_on_missing: bool) -> Result<PathBuf> {
     let path = join_here_abs(p)?;
     log::debug!("計算路徑：{:?}", path);
     if !path.exists() {
@@ -99,10 +145,7 @@ fn compute_home_path<T: AsRef<Path>>(p: T, create_on_missing: bool) -> Result<Pa
             return Err(Error::PathNotFound(vec![path]));
         }
     } else {
-        let redirect = path.join(HS_REDIRECT);
-        if redirect.is_file() {
-            let redirect = read_file(&redirect)?;
-            let redirect = path.join(redirect.trim());
+        if let Some(redirect) = read_redirect(&path)? {
             log::info!("重導向至 {:?}", redirect);
             return compute_home_path(redirect, create_on_missing);
         }
@@ -113,10 +156,26 @@ pub fn compute_home_path_optional<T: AsRef<Path>>(
     p: Option<T>,
     create_on_missing: bool,
 ) -> Result<PathBuf> {
-    match p {
-        Some(p) => compute_home_path(p, create_on_missing),
-        None => compute_home_path(get_sys_home()?, create_on_missing),
+    Ok(compute_home_path_with_source(p, create_on_missing)?.0)
+}
+pub fn compute_home_path_with_source<T: AsRef<Path>>(
+    p: Option<T>,
+    create_on_missing: bool,
+) -> Result<(PathBuf, HomeSource)> {
+    let (p, source) = match p {
+        Some(p) => (p.as_ref().to_owned(), HomeSource::Flag),
+        None => get_sys_home()?,
+    };
+    Ok((compute_home_path(p, create_on_missing)?, source))
+}
+/// 讀取 `home` 中的重導向檔，沒有則回傳 `None`
+pub fn read_redirect(home: &Path) -> Result<Option<PathBuf>> {
+    let redirect = home.join(HS_REDIRECT);
+    if !redirect.is_file() {
+        return Ok(None);
     }
+    let redirect = read_file(&redirect)?;
+    Ok(Some(home.join(redirect.trim())))
 }
 pub fn set_home<T: AsRef<Path>>(p: Option<T>, create_on_missing: bool) -> Result {
     let path = compute_home_path_optional(p, create_on_missing)?;
@@ -255,6 +314,24 @@ pub fn get_process_lock(run_id: i64) -> Result<PathBuf> {
     Ok(get_process_lock_dir()?.join(run_id.to_string()))
 }
 
+pub fn get_run_log_dir() -> Result<PathBuf> {
+    let p = get_home().join(RUN_LOG);
+    if !p.exists() {
+        log::info!("找不到執行紀錄資料夾，創建之");
+        handle_fs_res(&[&p], create_dir_all(&p))?;
+    }
+    Ok(p)
+}
+
+pub fn get_run_log(run_id: i64) -> Result<PathBuf> {
+    Ok(get_run_log_dir()?.join(format!("{}.log", run_id)))
+}
+
+/// 另一個腳本之家中的執行紀錄，不檢查存在性
+pub fn get_run_log_in(home: &Path, run_id: i64) -> PathBuf {
+    home.join(RUN_LOG).join(format!("{}.log", run_id))
+}
+
 pub fn get_template_path(ty: &ScriptFullType) -> Result<PathBuf> {
     let p = get_home().join(TEMPLATE).join(format!("{}{}", ty, HBS_EXT));
     if let Some(dir) = p.parent() {
@@ -266,6 +343,10 @@ pub fn get_template_path(ty: &ScriptFullType) -> Result<PathBuf> {
     Ok(p)
 }
 
+pub fn get_template_dir() -> PathBuf {
+    get_home().join(TEMPLATE)
+}
+
 pub fn get_sub_types(ty: &ScriptType) -> Result<Vec<ScriptType>> {
     let dir = get_home().join(TEMPLATE).join(ty.as_ref());
     if !dir.exists() {
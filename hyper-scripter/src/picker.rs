@@ -0,0 +1,428 @@
+//! 內建的終端機選單：逐字以模糊搜尋篩選候選者，可多選，並附有預覽窗格
+
+use crate::color::{Color, Stylize};
+use crate::error::{DisplayError, DisplayResult, FormatCode, Result};
+use crate::fuzzy::fuzz_score;
+use crate::util::hijack_ctrlc_once;
+use console::{truncate_str, Key, Term};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::str::FromStr;
+
+const LIST_HEIGHT: usize = 10;
+const PREVIEW_HEIGHT: usize = 12;
+
+const CTRL_N: char = '\x0e';
+const CTRL_P: char = '\x10';
+const CTRL_T: char = '\x14';
+const CTRL_U: char = '\x15';
+/// 選單本身或終端機已佔用的控制鍵：ctrl-c、退格、tab、換行及上面那些
+const RESERVED_KEYS: &str = "chijmnptu";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PreviewKind {
+    Content,
+    Help,
+}
+impl PreviewKind {
+    fn toggle(self) -> Self {
+        match self {
+            PreviewKind::Content => PreviewKind::Help,
+            PreviewKind::Help => PreviewKind::Content,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Action {
+    Continue,
+    /// 以 enter 確認時為 `None`，以額外綁定的按鍵確認時為該綁定的索引
+    Confirm(Option<usize>),
+    Cancel,
+}
+
+/// `hs pick --bind` 的參數，如 `w:wait` 代表以 ctrl-w 確認，並印出 `wait`
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct PickBinding {
+    pub key: char,
+    pub label: String,
+}
+impl FromStr for PickBinding {
+    type Err = DisplayError;
+    fn from_str(s: &str) -> DisplayResult<Self> {
+        if let Some((key, label)) = s.split_once(':') {
+            if let [k @ b'a'..=b'z'] = key.as_bytes() {
+                if !label.is_empty() && !RESERVED_KEYS.contains(*k as char) {
+                    return Ok(PickBinding {
+                        key: (k - b'a' + 1) as char,
+                        label: label.to_owned(),
+                    });
+                }
+            }
+        }
+        FormatCode::PickBinding.to_display_res(s.to_owned())
+    }
+}
+
+/// 選單的結果
+#[derive(Debug, Eq, PartialEq)]
+pub struct Picked {
+    /// 以額外綁定的按鍵確認時，該綁定的索引
+    pub binding: Option<usize>,
+    pub indices: Vec<usize>,
+}
+
+/// 與終端機無關的選單狀態，方便測試
+struct PickerState<'a> {
+    items: &'a [String],
+    sep: &'a str,
+    multi: bool,
+    bindings: &'a [(char, String)],
+    query: String,
+    /// 符合搜尋的候選者索引，依分數排序
+    matched: Vec<usize>,
+    cursor: usize,
+    selected: Vec<bool>,
+    preview_kind: PreviewKind,
+}
+
+impl<'a> PickerState<'a> {
+    fn new(
+        items: &'a [String],
+        sep: &'a str,
+        multi: bool,
+        bindings: &'a [(char, String)],
+        query: String,
+    ) -> Self {
+        let mut state = PickerState {
+            items,
+            sep,
+            multi,
+            bindings,
+            query,
+            matched: vec![],
+            cursor: 0,
+            selected: vec![false; items.len()],
+            preview_kind: PreviewKind::Content,
+        };
+        state.refilter();
+        state
+    }
+    fn refilter(&mut self) {
+        if self.query.is_empty() {
+            self.matched = (0..self.items.len()).collect();
+        } else {
+            let mut scored: Vec<_> = self
+                .items
+                .iter()
+                .enumerate()
+                .filter_map(|(i, item)| {
+                    fuzz_score(item, &self.query, self.sep).map(|score| (i, score))
+                })
+                .collect();
+            // NOTE: 穩定排序，同分者維持原本的順序
+            scored.sort_by_key(|(_, score)| Reverse(*score));
+            self.matched = scored.into_iter().map(|(i, _)| i).collect();
+        }
+        self.cursor = 0;
+    }
+    fn current(&self) -> Option<usize> {
+        self.matched.get(self.cursor).copied()
+    }
+    fn move_cursor(&mut self, down: bool) {
+        let len = self.matched.len();
+        if len == 0 {
+            return;
+        }
+        self.cursor = if down {
+            (self.cursor + 1) % len
+        } else {
+            (self.cursor + len - 1) % len
+        };
+    }
+    fn can_confirm(&self) -> bool {
+        self.current().is_some() || self.selected.contains(&true)
+    }
+    fn handle_key(&mut self, key: Key) -> Action {
+        if let Key::Char(ch) = key {
+            if let Some(pos) = self.bindings.iter().position(|(k, _)| *k == ch) {
+                if self.can_confirm() {
+                    return Action::Confirm(Some(pos));
+                }
+                return Action::Continue;
+            }
+        }
+        match key {
+            Key::Enter if self.can_confirm() => {
+                return Action::Confirm(None);
+            }
+            Key::Escape => return Action::Cancel,
+            Key::ArrowUp | Key::Char(CTRL_P) => self.move_cursor(false),
+            Key::ArrowDown | Key::Char(CTRL_N) => self.move_cursor(true),
+            Key::Tab if self.multi => {
+                if let Some(i) = self.current() {
+                    self.selected[i] = !self.selected[i];
+                    self.move_cursor(true);
+                }
+            }
+            Key::Char(CTRL_T) => self.preview_kind = self.preview_kind.toggle(),
+            Key::Char(CTRL_U) => {
+                self.query.clear();
+                self.refilter();
+            }
+            Key::Backspace if !self.query.is_empty() => {
+                self.query.pop();
+                self.refilter();
+            }
+            Key::Char(ch) if !ch.is_control() => {
+                self.query.push(ch);
+                self.refilter();
+            }
+            _ => (),
+        }
+        Action::Continue
+    }
+    /// 多選時回傳所有選取者（依原本的順序），沒有選取任何東西則回傳游標所在者
+    fn result(&self) -> Vec<usize> {
+        let selected: Vec<_> = (0..self.items.len())
+            .filter(|i| self.selected[*i])
+            .collect();
+        if !selected.is_empty() {
+            selected
+        } else {
+            self.current().into_iter().collect()
+        }
+    }
+}
+
+type PreviewFn<'a> = Box<dyn FnMut(usize, PreviewKind) -> String + 'a>;
+
+pub struct Picker<'a> {
+    items: Vec<String>,
+    sep: &'a str,
+    multi: bool,
+    bindings: Vec<(char, String)>,
+    query: String,
+    cursor_on: Option<usize>,
+    preview: Option<PreviewFn<'a>>,
+}
+
+/// 選單畫在標準錯誤輸出上，故須其為終端機才能使用（按鍵則會改從 /dev/tty 讀）
+pub fn is_available() -> bool {
+    Term::stderr().features().is_attended()
+}
+
+impl<'a> Picker<'a> {
+    pub fn new(items: Vec<String>, sep: &'a str) -> Self {
+        Picker {
+            items,
+            sep,
+            multi: false,
+            bindings: vec![],
+            query: String::new(),
+            cursor_on: None,
+            preview: None,
+        }
+    }
+    pub fn multi(mut self, multi: bool) -> Self {
+        self.multi = multi;
+        self
+    }
+    /// 額外綁定一個控制鍵（如 `'\x05'` 即 ctrl-e）作為確認鍵，`label` 會顯示在說明列上
+    pub fn bind(mut self, key: char, label: impl Into<String>) -> Self {
+        self.bindings.push((key, label.into()));
+        self
+    }
+    /// 初始的搜尋字串
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.query = query.into();
+        self
+    }
+    /// 一開始游標停在哪個候選者上（若它符合初始的搜尋）
+    pub fn cursor_on(mut self, idx: usize) -> Self {
+        self.cursor_on = Some(idx);
+        self
+    }
+    pub fn preview(mut self, f: impl FnMut(usize, PreviewKind) -> String + 'a) -> Self {
+        self.preview = Some(Box::new(f));
+        self
+    }
+
+    /// 回傳被選取的候選者索引，使用者取消則回傳 `None`
+    pub fn pick(self) -> Result<Option<Vec<usize>>> {
+        Ok(self.pick_with_binding()?.map(|p| p.indices))
+    }
+
+    /// 同 `pick`，另外回傳是以哪個綁定的按鍵確認的
+    pub fn pick_with_binding(mut self) -> Result<Option<Picked>> {
+        let query = std::mem::take(&mut self.query);
+        let items = std::mem::take(&mut self.items);
+        let bindings = std::mem::take(&mut self.bindings);
+        let mut state = PickerState::new(&items, self.sep, self.multi, &bindings, query);
+        if let Some(idx) = self.cursor_on {
+            if let Some(pos) = state.matched.iter().position(|i| *i == idx) {
+                state.cursor = pos;
+            }
+        }
+
+        let term = Term::stderr();
+        hijack_ctrlc_once();
+        let mut drawn = 0;
+        let res = loop {
+            term.clear_last_lines(drawn)?;
+            drawn = self.draw(&term, &state)?;
+            let key = match term.read_key() {
+                Ok(key) => key,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => break None,
+                Err(e) => {
+                    term.clear_last_lines(drawn)?;
+                    return Err(e.into());
+                }
+            };
+            match state.handle_key(key) {
+                Action::Continue => (),
+                Action::Confirm(binding) => {
+                    break Some(Picked {
+                        binding,
+                        indices: state.result(),
+                    })
+                }
+                Action::Cancel => break None,
+            }
+        };
+        term.clear_last_lines(drawn)?;
+        log::info!("選單結果：{:?}", res);
+        Ok(res)
+    }
+
+    /// 回傳畫了幾行
+    fn draw(&mut self, term: &Term, state: &PickerState<'_>) -> Result<usize> {
+        let width = term.size().1 as usize;
+        let mut lines = vec![];
+        // NOTE: 過長的行一律截斷，畫面上的行數才會和 `lines` 一致，之後才清得乾淨
+        let fit = |s: &str| truncate_str(s, width.saturating_sub(2), "…").into_owned();
+
+        lines.push(fit(&format!("> {}", state.query)));
+        let start = state.cursor.saturating_sub(LIST_HEIGHT - 1);
+        for (pos, idx) in state
+            .matched
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(LIST_HEIGHT)
+        {
+            let mark = if state.selected[*idx] { "*" } else { " " };
+            let line = fit(&format!("{}{}", mark, state.items[*idx]));
+            if pos == state.cursor {
+                lines.push(format!(">{}", line.stylize().bold().color(Color::Yellow)));
+            } else {
+                lines.push(format!(" {}", line));
+            }
+        }
+        let mut help = "enter: confirm, esc: cancel, ctrl-t: content/help".to_owned();
+        if state.multi {
+            help += ", tab: select";
+        }
+        for (key, label) in state.bindings.iter() {
+            let key = (b'a' + *key as u8 - 1) as char;
+            help += &format!(", ctrl-{}: {}", key, label);
+        }
+        let status = format!("  {}/{} ({})", state.matched.len(), state.items.len(), help);
+        lines.push(fit(&status).stylize().dimmed().to_string());
+
+        if let (Some(preview), Some(idx)) = (self.preview.as_mut(), state.current()) {
+            let content = preview(idx, state.preview_kind);
+            lines.push("─".repeat(width.saturating_sub(1).min(40)));
+            for line in content.lines().take(PREVIEW_HEIGHT) {
+                lines.push(fit(line));
+            }
+        }
+
+        for line in lines.iter() {
+            term.write_line(line)?;
+        }
+        Ok(lines.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn items(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+    fn matched_names<'a>(state: &PickerState<'a>) -> Vec<&'a str> {
+        state
+            .matched
+            .iter()
+            .map(|i| state.items[*i].as_str())
+            .collect()
+    }
+    #[test]
+    fn test_picker_filter() {
+        let items = items(&["build", "deploy/prod", "deploy/dev", "test"]);
+        let mut state = PickerState::new(&items, "/", false, &[], String::new());
+        assert_eq!(state.matched, vec![0, 1, 2, 3]);
+        for ch in "dep".chars() {
+            assert_eq!(state.handle_key(Key::Char(ch)), Action::Continue);
+        }
+        assert_eq!(matched_names(&state), vec!["deploy/prod", "deploy/dev"]);
+        state.handle_key(Key::Char('d'));
+        state.handle_key(Key::Char('e'));
+        state.handle_key(Key::Char('v'));
+        assert_eq!(matched_names(&state)[0], "deploy/dev");
+        state.handle_key(Key::Char(CTRL_U));
+        assert_eq!(state.matched.len(), 4);
+        state.handle_key(Key::Char('z'));
+        assert!(state.matched.is_empty());
+        assert_eq!(state.handle_key(Key::Enter), Action::Continue);
+        state.handle_key(Key::Backspace);
+        assert_eq!(state.matched.len(), 4);
+    }
+    #[test]
+    fn test_picker_select() {
+        let items = items(&["a", "b", "c"]);
+        let mut state = PickerState::new(&items, "/", false, &[], String::new());
+        state.handle_key(Key::ArrowUp);
+        assert_eq!(state.current(), Some(2));
+        state.handle_key(Key::Char(CTRL_N));
+        assert_eq!(state.current(), Some(0));
+        state.handle_key(Key::Tab);
+        assert_eq!(state.result(), vec![0], "單選時 tab 沒有作用");
+
+        let mut state = PickerState::new(&items, "/", true, &[], String::new());
+        state.handle_key(Key::Tab);
+        state.handle_key(Key::Tab);
+        state.handle_key(Key::Tab);
+        state.handle_key(Key::ArrowUp);
+        state.handle_key(Key::Tab);
+        assert_eq!(state.result(), vec![0, 1]);
+        assert_eq!(state.handle_key(Key::Enter), Action::Confirm(None));
+        assert_eq!(state.handle_key(Key::Escape), Action::Cancel);
+    }
+    #[test]
+    fn test_pick_binding() {
+        let binding: PickBinding = "w:wait".parse().unwrap();
+        assert_eq!(binding.key, '\x17');
+        assert_eq!(binding.label, "wait");
+        for s in ["w", "w:", "ww:wait", "W:wait", "n:next", "1:one"] {
+            assert!(s.parse::<PickBinding>().is_err(), "{}", s);
+        }
+    }
+    #[test]
+    fn test_picker_binding() {
+        let items = items(&["a", "b"]);
+        let bindings = [('\x04', "rm".to_owned()), ('\x05', "edit".to_owned())];
+        let mut state = PickerState::new(&items, "/", false, &bindings, String::new());
+        assert_eq!(
+            state.handle_key(Key::Char('\x05')),
+            Action::Confirm(Some(1))
+        );
+        state.handle_key(Key::Char('z'));
+        assert_eq!(
+            state.handle_key(Key::Char('\x04')),
+            Action::Continue,
+            "沒有候選者時不能確認"
+        );
+    }
+}
@@ -13,6 +13,8 @@ mod util;
 pub use util::*;
 mod range_query;
 pub use range_query::*;
+mod time_query;
+pub use time_query::*;
 mod list_query_handler;
 mod the_multifuzz_algo;
 pub use list_query_handler::*;
@@ -0,0 +1,109 @@
+use crate::error::{DisplayError, DisplayResult, FormatCode::TimeQuery as TimeQueryCode};
+use crate::util::impl_ser_by_to_string;
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::str::FromStr;
+
+const DATE_TIME_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"];
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// 某個時間點，可以是本地時間的日期（`2024-01-31`、`2024-01-31 12:00`），
+/// 或是距今的一段時間（`30m`、`3d`、`2w`）
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimeQuery {
+    Date(NaiveDateTime, bool),
+    Ago(u32, char),
+}
+
+impl TimeQuery {
+    /// 轉換成 UTC 時間，以便和資料庫中的時間比較
+    pub fn to_utc(&self) -> NaiveDateTime {
+        match self {
+            TimeQuery::Date(t, _) => match Local.from_local_datetime(t).earliest() {
+                Some(t) => t.naive_utc(),
+                None => {
+                    log::warn!("本地時間 {} 不存在，直接視為 UTC", t);
+                    *t
+                }
+            },
+            TimeQuery::Ago(n, unit) => Utc::now().naive_utc() - duration(*n, *unit),
+        }
+    }
+    /// 同 `to_utc`，但一段時間是指從現在往後算，例如 `hs run --at 30m`
+    pub fn to_utc_later(&self) -> NaiveDateTime {
+        match self {
+            TimeQuery::Date(..) => self.to_utc(),
+            TimeQuery::Ago(n, unit) => Utc::now().naive_utc() + duration(*n, *unit),
+        }
+    }
+}
+
+fn duration(n: u32, unit: char) -> Duration {
+    let n = n as i64;
+    match unit {
+        's' => Duration::seconds(n),
+        'm' => Duration::minutes(n),
+        'h' => Duration::hours(n),
+        'd' => Duration::days(n),
+        'w' => Duration::weeks(n),
+        _ => unreachable!(),
+    }
+}
+
+impl FromStr for TimeQuery {
+    type Err = DisplayError;
+    fn from_str(s: &str) -> DisplayResult<Self> {
+        if let Some(unit) = s.chars().last() {
+            if "smhdw".contains(unit) {
+                let num = &s[..s.len() - 1];
+                if let Ok(num) = num.parse::<u32>() {
+                    return Ok(TimeQuery::Ago(num, unit));
+                }
+            }
+        }
+        for format in DATE_TIME_FORMATS.iter() {
+            if let Ok(t) = NaiveDateTime::parse_from_str(s, format) {
+                return Ok(TimeQuery::Date(t, true));
+            }
+        }
+        if let Ok(d) = NaiveDate::parse_from_str(s, DATE_FORMAT) {
+            return Ok(TimeQuery::Date(d.and_hms_opt(0, 0, 0).unwrap(), false));
+        }
+        log::error!("時間格式不符：{}", s);
+        TimeQueryCode.to_display_res(s.to_owned())
+    }
+}
+impl std::fmt::Display for TimeQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeQuery::Date(t, true) => write!(f, "{}", t.format(DATE_TIME_FORMATS[0])),
+            TimeQuery::Date(t, false) => write!(f, "{}", t.format(DATE_FORMAT)),
+            TimeQuery::Ago(n, unit) => write!(f, "{}{}", n, unit),
+        }
+    }
+}
+impl_ser_by_to_string!(TimeQuery);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_time_query() {
+        for s in ["3d", "10m", "2024-01-31", "2024-01-31 12:30:00"] {
+            let q: TimeQuery = s.parse().unwrap();
+            assert_eq!(q.to_string(), s);
+        }
+        let q: TimeQuery = "2024-01-31 12:30".parse().unwrap();
+        assert_eq!(q.to_string(), "2024-01-31 12:30:00");
+
+        let q: TimeQuery = "1h".parse().unwrap();
+        let t = q.to_utc();
+        let diff = Utc::now().naive_utc() - t;
+        assert!(diff >= Duration::hours(1) && diff < Duration::hours(2));
+        let diff = q.to_utc_later() - Utc::now().naive_utc();
+        assert!(diff > Duration::minutes(59) && diff <= Duration::hours(1));
+
+        for s in ["", "d", "3y", "-3d", "2024-13-01", "yesterday"] {
+            s.parse::<TimeQuery>().expect_err(s);
+        }
+    }
+}
@@ -3,12 +3,18 @@ use super::{ListQuery, ScriptQuery, ScriptQueryInner, StableRepo};
 use crate::color::Stylize;
 use crate::config::{Config, PromptLevel};
 use crate::error::{Error, Result};
+use crate::extract_msg::extract_help_from_content;
 use crate::fuzzy;
+use crate::path;
+use crate::picker::{self, Picker, PreviewKind};
+use crate::script::ScriptName;
 use crate::script_repo::{RepoEntry, Visibility};
-use crate::util::{get_display_type, prompt};
+use crate::script_type::ScriptType;
+use crate::util::{get_display_type, prompt, read_file};
 use crate::Either;
 use crate::SEP;
 use fxhash::FxHashSet as HashSet;
+use std::cmp::Reverse;
 
 fn compute_vis(bang: bool) -> Visibility {
     if bang {
@@ -124,6 +130,8 @@ pub async fn do_script_query<'b, R: StableRepo>(
                 Config::get_prompt_level()
             };
 
+            // FIXME: 一旦 NLL 進化就修掉這段 unsafe
+            let ptr = script_repo as *mut R;
             let iter = script_repo.iter_mut(visibility);
             let fuzz_res = fuzzy::fuzz(name, iter, SEP).await?;
             let mut is_low = false;
@@ -158,6 +166,15 @@ pub async fn do_script_query<'b, R: StableRepo>(
                 }
             };
             if need_prompt {
+                if picker::is_available() {
+                    let default = entry.name.clone();
+                    // SAFETY: 之後不再使用 `entry`
+                    let script_repo = unsafe { &mut *ptr };
+                    return match pick_script(name, &default, script_repo, visibility)? {
+                        Some(picked) => Ok(script_repo.get_mut(&picked, visibility)),
+                        None => Err(Error::DontFuzz),
+                    };
+                }
                 let ty = get_display_type(&entry.ty);
                 let msg = format!("{}({})?", entry.name, ty.display());
                 let yes = prompt(msg.stylize().color(ty.color()).bold(), true)?;
@@ -169,6 +186,55 @@ pub async fn do_script_query<'b, R: StableRepo>(
         }
     }
 }
+fn preview_script(name: &ScriptName, ty: &ScriptType, kind: PreviewKind) -> String {
+    let content = path::open_script(name, ty, Some(true)).and_then(|p| read_file(&p));
+    let content = match content {
+        Ok(content) => content,
+        Err(e) => {
+            log::warn!("預覽腳本 {:?} 失敗：{:?}", name, e);
+            return String::new();
+        }
+    };
+    match kind {
+        PreviewKind::Content => content,
+        PreviewKind::Help => {
+            let helps: Vec<_> = extract_help_from_content(&content).collect();
+            if helps.is_empty() {
+                "(no help message)".to_owned()
+            } else {
+                helps.join("\n")
+            }
+        }
+    }
+}
+
+/// 以選單從所有候選者中挑一個，游標一開始停在模糊搜尋的結果上。使用者取消則回傳 `None`
+fn pick_script<R: StableRepo>(
+    pattern: &str,
+    default: &ScriptName,
+    script_repo: &mut R,
+    visibility: Visibility,
+) -> Result<Option<ScriptName>> {
+    let mut candidates: Vec<_> = script_repo
+        .iter_mut(visibility)
+        .map(|e| (e.last_time(), e.name.clone(), e.ty.clone()))
+        .collect();
+    candidates.sort_by_key(|(time, ..)| Reverse(*time));
+    let keys = candidates
+        .iter()
+        .map(|(_, name, _)| name.key().to_string())
+        .collect();
+    let mut picker = Picker::new(keys, SEP).query(pattern).preview(|idx, kind| {
+        let (_, name, ty) = &candidates[idx];
+        preview_script(name, ty, kind)
+    });
+    if let Some(idx) = candidates.iter().position(|(_, name, _)| name == default) {
+        picker = picker.cursor_on(idx);
+    }
+    let picked = picker.pick()?;
+    Ok(picked.map(|v| candidates[v[0]].1.clone()))
+}
+
 pub async fn do_script_query_strict<'b, R: StableRepo>(
     script_query: &ScriptQuery,
     script_repo: &'b mut R,
@@ -0,0 +1,185 @@
+//! 排程執行：以 cron 表達式週期執行，或在某個時間點執行一次（`hs run --at`）。
+//! 排程存在腳本之家的資料庫中，由 `hs scheduler` 觸發，經由一般的 `run_n_times` 執行，
+//! 並在歷史中標記為排程觸發。
+//!
+//! 排程器停擺期間錯過的時間點不會補跑，重新啟動後只會觸發一次，再從當下找下一個時間點
+
+use crate::cron::CronExpr;
+use crate::db;
+use crate::error::{Error, Result};
+use crate::script_repo::ScriptRepo;
+use crate::util::main_util;
+use chrono::{Local, NaiveDateTime, TimeZone, Utc};
+use sqlx::SqlitePool;
+use std::time::Duration;
+
+/// 沒有排程要觸發時，最久隔多久重新讀一次資料庫，以發現新加入的排程
+const POLL_SECS: u64 = 60;
+
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub id: i64,
+    pub script_id: i64,
+    /// 沒有的話代表只執行一次
+    pub cron: Option<CronExpr>,
+    pub args: Vec<String>,
+    /// UTC 時間
+    pub next_time: NaiveDateTime,
+}
+
+/// 嚴格晚於 `after`（UTC）的下一個時間點。cron 表達式以本地時間解讀
+pub fn next_time(cron: &CronExpr, after: NaiveDateTime) -> Option<NaiveDateTime> {
+    let mut local = Local.from_utc_datetime(&after).naive_local();
+    loop {
+        local = cron.next_after(local)?;
+        match Local.from_local_datetime(&local).earliest() {
+            Some(t) => return Some(t.naive_utc()),
+            None => log::info!("本地時間 {} 不存在（夏令時間？），找下一個", local),
+        }
+    }
+}
+
+/// 以本地時間顯示資料庫中的 UTC 時間
+pub fn fmt_local_time(time: NaiveDateTime) -> String {
+    let time = Local.from_utc_datetime(&time).naive_local();
+    time.format("%Y-%m-%d %H:%M").to_string()
+}
+
+pub async fn add(
+    pool: &SqlitePool,
+    script_id: i64,
+    cron: Option<&CronExpr>,
+    args: &[String],
+    next_time: NaiveDateTime,
+) -> Result<i64> {
+    let cron = cron.map(|c| c.to_string());
+    let args = serde_json::to_string(args)?;
+    let res = sqlx::query!(
+        "INSERT INTO schedules (script_id, cron, args, next_time) VALUES(?, ?, ?, ?) RETURNING id",
+        script_id,
+        cron,
+        args,
+        next_time
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(res.id)
+}
+
+pub async fn load_all(pool: &SqlitePool) -> Result<Vec<Schedule>> {
+    let records = sqlx::query!(
+        "SELECT id, script_id, cron, args, next_time FROM schedules ORDER BY next_time, id"
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut schedules = vec![];
+    for r in records.into_iter() {
+        let cron = match r.cron {
+            Some(cron) => Some(cron.parse().map_err(Error::from)?),
+            None => None,
+        };
+        schedules.push(Schedule {
+            id: r.id,
+            script_id: r.script_id,
+            cron,
+            args: serde_json::from_str(&r.args)?,
+            next_time: r.next_time,
+        });
+    }
+    Ok(schedules)
+}
+
+/// 回傳是否真的有刪掉
+pub async fn remove(pool: &SqlitePool, id: i64) -> Result<bool> {
+    let res = sqlx::query!("DELETE FROM schedules WHERE id = ?", id)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// 週期排程移到下一個時間點，單次排程則直接刪除
+async fn advance(pool: &SqlitePool, schedule: &Schedule, now: NaiveDateTime) -> Result {
+    match schedule.cron.as_ref().and_then(|c| next_time(c, now)) {
+        Some(next) => {
+            log::debug!("排程 {} 的下一次執行：{}", schedule.id, next);
+            sqlx::query!(
+                "UPDATE schedules SET next_time = ? WHERE id = ?",
+                next,
+                schedule.id
+            )
+            .execute(pool)
+            .await?;
+        }
+        None => {
+            remove(pool, schedule.id).await?;
+        }
+    }
+    Ok(())
+}
+
+/// 執行所有到期的排程。找到腳本後、執行前就先推進排程，以免腳本出錯或執行太久時被重複觸發。
+/// 腳本依 id 查找，故被 `recent` 等時間條件藏起來的腳本照樣會執行，只有標籤篩選會擋下它。
+/// 腳本已不存在的排程會被刪除。腳本本身的錯誤會放進 `errs`，不影響其它排程
+pub async fn run_due(repo: &mut ScriptRepo, errs: &mut Vec<Error>) -> Result {
+    let now = Utc::now().naive_utc();
+    let pool = repo.info_pool().clone();
+    for schedule in load_all(&pool).await?.into_iter() {
+        if schedule.next_time > now {
+            break;
+        }
+        log::info!("觸發排程 {:?}", schedule);
+
+        let Some(info) = repo
+            .get_mut_by_id(schedule.script_id)
+            .map(|e| e.into_inner().clone())
+        else {
+            log::warn!("排程 {} 的腳本已不存在，刪除之", schedule.id);
+            remove(&pool, schedule.id).await?;
+            continue;
+        };
+        advance(&pool, &schedule, now).await?;
+        if !repo.explain_filter(&info).tags.verdict {
+            log::warn!("排程 {} 的腳本 {:?} 被標籤篩掉", schedule.id, info.name);
+            continue;
+        }
+        let mut entry = repo.get_mut_by_id(schedule.script_id).unwrap();
+        let res = main_util::run_n_times(&mut entry, schedule.args, errs, Default::default()).await;
+        if let Err(err) = res {
+            log::warn!("排程 {} 執行失敗：{:?}", schedule.id, err);
+            errs.push(err);
+        }
+    }
+    Ok(())
+}
+
+/// 常駐的排程器。每當有排程到期，就以 `--once` 重新執行自己，讓每次觸發都讀到最新的腳本之家
+pub async fn daemon() -> Result {
+    let exe = std::env::current_exe()?;
+    let args: Vec<_> = std::env::args()
+        .skip(1)
+        .chain(["--once".to_owned()])
+        .collect();
+    loop {
+        let mut need_journal = false;
+        let (pool, _) = db::get_pool(&mut need_journal).await?;
+        let schedules = load_all(&pool).await;
+        pool.close().await;
+
+        let now = Utc::now().naive_utc();
+        let wait = match schedules?.first() {
+            Some(s) if s.next_time <= now => {
+                log::info!("有排程到期，執行 {:?} {:?}", exe, args);
+                let status = std::process::Command::new(&exe).args(&args).status()?;
+                if !status.success() {
+                    log::warn!("排程執行結束，狀態 {}", status);
+                }
+                continue;
+            }
+            Some(s) => (s.next_time - now).to_std().unwrap_or_default(),
+            None => Duration::from_secs(POLL_SECS),
+        };
+        let wait = std::cmp::min(wait, Duration::from_secs(POLL_SECS));
+        log::debug!("等待 {:?}", wait);
+        tokio::time::sleep(wait).await;
+    }
+}
@@ -9,6 +9,7 @@ use crate::tag::{Tag, TagSelector};
 use crate::util::illegal_name;
 use chrono::NaiveDateTime;
 use fxhash::FxHashSet as HashSet;
+use hyper_scripter_historian::ExecUsage;
 use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::fmt::Write;
@@ -236,12 +237,13 @@ pub struct TimelessScriptInfo {
 pub struct ScriptInfo {
     pub humble_time: Option<NaiveDateTime>,
     pub read_time: ScriptTime,
-    pub write_time: ScriptTime,
+    /// 寫入後的內容
+    pub write_time: ScriptTime<Option<String>>,
     pub neglect_time: Option<ScriptTime>,
     /// (content, args, env_record, dir)
     pub exec_time: Option<ScriptTime<(String, String, String, Option<PathBuf>)>>,
-    /// (return code, main event id)
-    pub exec_done_time: Option<ScriptTime<(i32, i64)>>,
+    /// (return code, main event id, usage)
+    pub exec_done_time: Option<ScriptTime<(i32, i64, ExecUsage)>>,
     pub exec_count: u64,
     #[deref]
     /// 用來區隔「時間資料」和「其它元資料」，並偵測其它元資料的修改
@@ -302,10 +304,11 @@ impl ScriptInfo {
     pub fn read(&mut self) {
         self.read_time = ScriptTime::now(());
     }
-    pub fn write(&mut self) {
+    /// `content` 為寫入後的內容，只是移動腳本時不必給
+    pub fn write(&mut self, content: Option<String>) {
         let now = ScriptTime::now(());
-        self.read_time = now.clone();
-        self.write_time = now;
+        self.read_time = now;
+        self.write_time = now.with_data(content);
     }
     pub fn exec(
         &mut self,
@@ -320,9 +323,9 @@ impl ScriptInfo {
         // NOTE: no readtime, otherwise it will be hard to tell what event was caused by what operation.
         self.exec_count += 1;
     }
-    pub fn exec_done(&mut self, code: i32, main_event_id: i64) {
+    pub fn exec_done(&mut self, code: i32, main_event_id: i64, usage: ExecUsage) {
         log::trace!("{:?} 執行結果為 {}", self, code);
-        self.exec_done_time = Some(ScriptTime::now((code, main_event_id)));
+        self.exec_done_time = Some(ScriptTime::now((code, main_event_id, usage)));
     }
     pub fn neglect(&mut self) {
         self.neglect_time = Some(ScriptTime::now(()))
@@ -445,7 +448,7 @@ impl ScriptBuilder {
     pub fn build(self) -> ScriptInfo {
         let created_time = ScriptTime::new_or(self.created_time, ScriptTime::now(()));
         ScriptInfo {
-            write_time: ScriptTime::new_or(self.write_time, created_time),
+            write_time: ScriptTime::new_or(self.write_time, created_time.with_data(None)),
             read_time: ScriptTime::new_or(self.read_time, created_time),
             exec_time: self.exec_time.map(ScriptTime::new),
             exec_done_time: self.exec_done_time.map(ScriptTime::new),
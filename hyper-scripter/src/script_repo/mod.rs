@@ -2,10 +2,10 @@ use crate::config::Recent;
 use crate::error::Result;
 use crate::script::{IntoScriptName, ScriptInfo, ScriptName};
 use crate::script_type::ScriptType;
-use crate::tag::{Tag, TagSelectorGroup};
+use crate::tag::{SelectExplain, Tag, TagSelectorGroup};
 use chrono::{Duration, NaiveDateTime, Utc};
 use fxhash::FxHashMap as HashMap;
-use hyper_scripter_historian::{Event, EventData, Historian, LastTimeRecord};
+use hyper_scripter_historian::{Event, EventData, Historian, HookRecord, LastTimeRecord};
 use sqlx::SqlitePool;
 use std::collections::hash_map::Entry::{self, *};
 
@@ -17,11 +17,26 @@ pub struct RecentFilter {
     pub recent: Recent,
     pub archaeology: bool,
 }
+#[derive(Debug)]
 enum TimeBound {
     Timeless,
     Bound(Option<NaiveDateTime>),
 }
 impl TimeBound {
+    fn is_overtime(&self, script: &ScriptInfo) -> bool {
+        match self {
+            TimeBound::Timeless => false,
+            TimeBound::Bound(time_bound) => {
+                let neglect = script.neglect_time.as_ref().map(|t| **t);
+                let time_bound = std::cmp::max(*time_bound, neglect);
+                if let Some(time_bound) = time_bound {
+                    time_bound > script.last_major_time()
+                } else {
+                    false
+                }
+            }
+        }
+    }
     fn new(recent: Recent) -> Self {
         match recent {
             Recent::Timeless => TimeBound::Timeless,
@@ -78,6 +93,8 @@ pub struct DBEnv {
     pub historian: Historian,
     trace_opt: TraceOption,
     modifies_script: bool,
+    /// 由排程器觸發的執行，記錄事件時會帶上標記
+    scheduled: bool,
 }
 
 pub struct RepoEntryOptional<'b> {
@@ -96,6 +113,22 @@ impl<'b> RepoEntryOptional<'b> {
         }
         Ok(RepoEntry::new(info, self.env))
     }
+    /// 匯入他處的腳本，保留其創建時間、執行次數及最新事件的時間。
+    /// 若已有同名腳本則取而代之，呼叫者應先將其 `remove`
+    pub async fn import(self, mut info: ScriptInfo) -> Result<RepoEntry<'b>> {
+        log::debug!("匯入腳本 {:?}", info);
+        let id = self.env.handle_import(&info).await?;
+        info.id = 0; // NOTE: 來源的 id 在此沒有意義
+        info.set_id(id);
+        let info = match self.entry {
+            Occupied(mut e) => {
+                e.insert(info);
+                e.into_mut()
+            }
+            Vacant(e) => e.insert(info),
+        };
+        Ok(RepoEntry::new(info, self.env))
+    }
 }
 
 impl DBEnv {
@@ -112,7 +145,21 @@ impl DBEnv {
             historian,
             modifies_script,
             trace_opt: TraceOption::Normal,
+            scheduled: false,
+        }
+    }
+    pub fn info_pool(&self) -> &SqlitePool {
+        &self.info_pool
+    }
+    pub fn is_scheduled(&self) -> bool {
+        self.scheduled
+    }
+    pub async fn record_hook(&self, record: &HookRecord) -> Result {
+        if matches!(self.trace_opt, TraceOption::NoTrace) || record.main_event_id == 0 {
+            return Ok(());
         }
+        self.historian.record_hook(record).await?;
+        Ok(())
     }
     pub async fn handle_neglect(&self, id: i64) -> Result {
         let time = Utc::now().naive_utc();
@@ -198,6 +245,9 @@ impl DBEnv {
         sqlx::query!("DELETE FROM last_events WHERE script_id = ?", id)
             .execute(&self.info_pool)
             .await?;
+        sqlx::query!("DELETE FROM schedules WHERE script_id = ?", id)
+            .execute(&self.info_pool)
+            .await?;
         sqlx::query!("DELETE from script_infos where id = ?", id)
             .execute(&self.info_pool)
             .await?;
@@ -225,6 +275,52 @@ impl DBEnv {
         Ok(res.id)
     }
 
+    /// 同 `handle_insert`，但保留創建時間，並直接寫入最新事件的時間，不記錄任何事件
+    pub async fn handle_import(&self, info: &ScriptInfo) -> Result<i64> {
+        assert!(self.modifies_script);
+        let name_cow = info.name.key();
+        let name = name_cow.as_ref();
+        let ty = info.ty.as_ref();
+        let tags = join_tags(info.tags.iter());
+        let created_time = *info.created_time;
+        let res = sqlx::query!(
+            "
+            INSERT INTO script_infos (name, ty, tags, created_time)
+            VALUES(?, ?, ?, ?)
+            RETURNING id
+            ",
+            name,
+            ty,
+            tags,
+            created_time,
+        )
+        .fetch_one(&self.info_pool)
+        .await?;
+
+        let exec_time = info.exec_time.as_ref().map(|t| **t);
+        let exec_done_time = info.exec_done_time.as_ref().map(|t| **t);
+        let neglect_time = info.neglect_time.as_ref().map(|t| **t);
+        let exec_count = info.exec_count as i32;
+        sqlx::query!(
+            "
+            INSERT INTO last_events
+            (script_id, read, write, exec, exec_done, neglect, humble, exec_count)
+            VALUES(?, ?, ?, ?, ?, ?, ?, ?)
+            ",
+            res.id,
+            *info.read_time,
+            *info.write_time,
+            exec_time,
+            exec_done_time,
+            neglect_time,
+            info.humble_time,
+            exec_count
+        )
+        .execute(&self.info_pool)
+        .await?;
+        Ok(res.id)
+    }
+
     async fn handle_change(&self, info: &ScriptInfo) -> Result<i64> {
         log::debug!("開始修改資料庫 {:?}", info);
         if info.changed {
@@ -254,6 +350,7 @@ impl DBEnv {
                 self.historian.record(&Event {
                     script_id: info.id,
                     humble: matches!(self.trace_opt, TraceOption::Humble),
+                    scheduled: self.scheduled,
                     time: $time,
                     data: $data,
                 })
@@ -261,13 +358,14 @@ impl DBEnv {
         }
 
         if let Some(time) = info.exec_done_time.as_ref() {
-            if let Some(&(code, main_event_id)) = time.data() {
+            if let Some(&(code, main_event_id, usage)) = time.data() {
                 log::debug!("{:?} 的執行完畢事件", info.name);
                 last_event_id = record_event!(
                     **time,
                     EventData::ExecDone {
                         code,
                         main_event_id,
+                        usage,
                     }
                 )
                 .await?;
@@ -287,9 +385,10 @@ impl DBEnv {
             log::debug!("{:?} 的讀取事件", info.name);
             last_event_id = record_event!(*info.read_time, EventData::Read).await?;
         }
-        if info.write_time.has_changed() {
+        if let Some(content) = info.write_time.data() {
             log::debug!("{:?} 的寫入事件", info.name);
-            last_event_id = record_event!(*info.write_time, EventData::Write).await?;
+            let content = content.as_deref();
+            last_event_id = record_event!(*info.write_time, EventData::Write { content }).await?;
         }
         if let Some(time) = info.exec_time.as_ref() {
             if let Some((content, args, envs, dir)) = time.data() {
@@ -316,12 +415,83 @@ fn join_tags<'a, I: Iterator<Item = &'a Tag>>(tags: I) -> String {
     tags_arr.join(",")
 }
 
+/// 腳本為何通過或未通過篩選
+#[derive(Debug)]
+pub struct FilterExplain<'a> {
+    pub overtime: bool,
+    pub archaeology: bool,
+    pub tags: SelectExplain<'a>,
+}
+impl FilterExplain<'_> {
+    pub fn is_hidden(&self) -> bool {
+        (self.archaeology ^ self.overtime) || !self.tags.verdict
+    }
+}
+
+/// 從資料庫載入所有腳本資訊，不做任何篩選
+pub async fn load_script_infos(pool: &SqlitePool) -> Result<Vec<ScriptInfo>> {
+    let mut infos = vec![];
+    let scripts = sqlx::query!(
+        "SELECT * FROM script_infos si LEFT JOIN last_events le ON si.id = le.script_id"
+    )
+    .fetch_all(pool)
+    .await?;
+    for record in scripts.into_iter() {
+        log::trace!("載入腳本：{} {} {}", record.name, record.ty, record.tags);
+        let script_name = record.name.into_script_name_unchecked()?; // NOTE: 從資料庫撈出來就別檢查了吧
+
+        let mut builder = ScriptInfo::builder(
+            record.id,
+            script_name,
+            ScriptType::new_unchecked(record.ty),
+            record.tags.split(',').filter_map(|s| {
+                if s.is_empty() {
+                    None
+                } else {
+                    Some(Tag::new_unchecked(s.to_string()))
+                }
+            }),
+        );
+
+        builder.created_time(record.created_time);
+        builder.exec_count(record.exec_count.unwrap_or_default() as u64);
+        if let Some(time) = record.write {
+            builder.write_time(time);
+        }
+        if let Some(time) = record.read {
+            builder.read_time(time);
+        }
+        if let Some(time) = record.exec {
+            builder.exec_time(time);
+        }
+        if let Some(time) = record.exec_done {
+            builder.exec_done_time(time);
+        }
+        if let Some(time) = record.neglect {
+            builder.neglect_time(time);
+        }
+        if let Some(time) = record.humble {
+            builder.humble_time(time);
+        }
+
+        let script = builder.build();
+        if let Some(neglect) = record.neglect {
+            log::debug!("腳本 {} 曾於 {} 被忽略", script.name, neglect);
+        }
+        infos.push(script);
+    }
+    Ok(infos)
+}
+
 #[derive(Debug)]
 pub struct ScriptRepo {
     map: HashMap<String, ScriptInfo>,
     hidden_map: HashMap<String, ScriptInfo>,
     latest_name: Option<String>,
     db_env: DBEnv,
+    selector: TagSelectorGroup,
+    time_bound: TimeBound,
+    archaeology: bool,
 }
 
 macro_rules! iter_by_vis {
@@ -348,6 +518,9 @@ impl ScriptRepo {
     pub fn historian(&self) -> &Historian {
         &self.db_env.historian
     }
+    pub fn info_pool(&self) -> &SqlitePool {
+        self.db_env.info_pool()
+    }
     pub async fn new(
         recent: RecentFilter,
         db_env: DBEnv,
@@ -357,66 +530,9 @@ impl ScriptRepo {
         let mut map: HashMap<String, ScriptInfo> = Default::default();
         let time_bound = TimeBound::new(recent.recent);
 
-        let scripts = sqlx::query!(
-            "SELECT * FROM script_infos si LEFT JOIN last_events le ON si.id = le.script_id"
-        )
-        .fetch_all(&db_env.info_pool)
-        .await?;
-        for record in scripts.into_iter() {
-            let name = record.name;
-            log::trace!("載入腳本：{} {} {}", name, record.ty, record.tags);
-            let script_name = name.clone().into_script_name_unchecked()?; // NOTE: 從資料庫撈出來就別檢查了吧
-
-            let mut builder = ScriptInfo::builder(
-                record.id,
-                script_name,
-                ScriptType::new_unchecked(record.ty),
-                record.tags.split(',').filter_map(|s| {
-                    if s.is_empty() {
-                        None
-                    } else {
-                        Some(Tag::new_unchecked(s.to_string()))
-                    }
-                }),
-            );
-
-            builder.created_time(record.created_time);
-            builder.exec_count(record.exec_count.unwrap_or_default() as u64);
-            if let Some(time) = record.write {
-                builder.write_time(time);
-            }
-            if let Some(time) = record.read {
-                builder.read_time(time);
-            }
-            if let Some(time) = record.exec {
-                builder.exec_time(time);
-            }
-            if let Some(time) = record.exec_done {
-                builder.exec_done_time(time);
-            }
-            if let Some(time) = record.neglect {
-                builder.neglect_time(time);
-            }
-            if let Some(time) = record.humble {
-                builder.humble_time(time);
-            }
-
-            let script = builder.build();
-            if let Some(neglect) = record.neglect {
-                log::debug!("腳本 {} 曾於 {} 被忽略", script.name, neglect);
-            }
-
-            let overtime = match time_bound {
-                TimeBound::Timeless => false,
-                TimeBound::Bound(time_bound) => {
-                    let time_bound = std::cmp::max(time_bound, record.neglect);
-                    if let Some(time_bound) = time_bound {
-                        time_bound > script.last_major_time()
-                    } else {
-                        false
-                    }
-                }
-            };
+        for script in load_script_infos(&db_env.info_pool).await?.into_iter() {
+            let name = script.name.key().into_owned();
+            let overtime = time_bound.is_overtime(&script);
             let mut hide = recent.archaeology ^ overtime;
 
             if !hide {
@@ -435,14 +551,28 @@ impl ScriptRepo {
             hidden_map,
             latest_name: None,
             db_env,
+            selector: selector.clone(),
+            time_bound,
+            archaeology: recent.archaeology,
         })
     }
+    /// 重新計算一次篩選，並記錄每個環節的結果
+    pub fn explain_filter<'a>(&'a self, script: &ScriptInfo) -> FilterExplain<'a> {
+        FilterExplain {
+            overtime: self.time_bound.is_overtime(script),
+            archaeology: self.archaeology,
+            tags: self.selector.explain(&script.tags, &script.ty),
+        }
+    }
     pub fn no_trace(&mut self) {
         self.db_env.trace_opt = TraceOption::NoTrace;
     }
     pub fn humble(&mut self) {
         self.db_env.trace_opt = TraceOption::Humble;
     }
+    pub fn scheduled(&mut self) {
+        self.db_env.scheduled = true;
+    }
     // fn latest_mut_no_cache(&mut self) -> Option<&mut ScriptInfo<'a>> {
     //     let latest = self.map.iter_mut().max_by_key(|(_, info)| info.last_time());
     //     if let Some((name, info)) = latest {
@@ -49,6 +49,13 @@ impl<T> ScriptTime<T> {
             changed: None,
         }
     }
+    /// 換掉帶著的資料，時間不變
+    pub fn with_data<U>(self, data: U) -> ScriptTime<U> {
+        ScriptTime {
+            time: self.time,
+            changed: self.changed.map(|_| data),
+        }
+    }
     pub fn data(&self) -> Option<&T> {
         self.changed.as_ref()
     }
@@ -1,4 +1,5 @@
 use crate::error::{DisplayError, DisplayResult, Error, FormatCode::ScriptType as TypeCode};
+use crate::hook::Hooks;
 use crate::util::illegal_name;
 use crate::util::impl_ser_by_to_string;
 use fxhash::FxHashMap as HashMap;
@@ -111,28 +112,13 @@ history_arr = history_arr.select do |d|
   end
 end
 
-require_relative \"#{ENV['HS_HOME']}/util/selector.rb\"
-selector = Selector.new
-selector.load(history_arr + dirs_set.to_a)
-is_dot = false
-selector.register_keys('.', lambda { |_, _|
-  is_dot = true
-}, msg: 'go to \".\"')
-
-dir = begin
-  content = selector.run.options[0]
-  if is_dot
-    '.'
-  else
-    content
-  end
-rescue Selector::Empty
-  warn 'empty'
-  exit 1
-rescue Selector::Quit
+label, picked = HS_ENV.pick(history_arr + dirs_set.to_a, bind: { 'b' => 'base' })
+if picked.nil?
   warn 'quit'
   exit
 end
+dir = label == 'base' ? '.' : picked[0]
+exit 1 if dir.nil?
 
 HS_ENV.do_hs(\"run --dummy =#{HS_ENV.env_var(:name)}! #{dir}\", false)
 cd(dir)";
@@ -245,6 +231,8 @@ pub struct ScriptTypeConfig {
     args: Vec<String>,
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
 }
 
 impl ScriptTypeConfig {
@@ -318,7 +306,8 @@ create_default_types! {
         color: "bright magenta".to_owned(),
         cmd: Some("bash".to_owned()),
         args: vec!["{{path}}".to_owned()],
-        env: Default::default()
+        env: Default::default(),
+        hooks: Default::default(),
     }, []),
     ("tmux", TMUX_WELCOME_MSG, ScriptTypeConfig {
         ext: Some("sh".to_owned()),
@@ -326,6 +315,7 @@ create_default_types! {
         cmd: Some("bash".to_owned()),
         args: vec!["{{path}}".to_owned()],
         env: Default::default(),
+        hooks: Default::default(),
     }, []),
     ("js", JS_WELCOME_MSG, ScriptTypeConfig {
         ext: Some("js".to_owned()),
@@ -336,6 +326,7 @@ create_default_types! {
             "NODE_PATH",
             "{{{home}}}/node_modules",
         )]),
+        hooks: Default::default(),
     }, []),
     ("js-i", JS_WELCOME_MSG, ScriptTypeConfig {
         ext: Some("js".to_owned()),
@@ -346,6 +337,7 @@ create_default_types! {
             "NODE_PATH",
             "{{{home}}}/node_modules",
         )]),
+        hooks: Default::default(),
     }, []),
     ("rb", RB_WELCOME_MSG, ScriptTypeConfig {
         ext: Some("rb".to_owned()),
@@ -353,6 +345,7 @@ create_default_types! {
         cmd: Some("ruby".to_owned()),
         args: vec!["{{path}}".to_owned()],
         env: Default::default(),
+        hooks: Default::default(),
     }, ["traverse": RB_TRAVERSE_WELCOME_MSG, "cd": RB_CD_WELCOME_MSG]),
     ("txt", DEFAULT_WELCOME_MSG, ScriptTypeConfig {
         ext: None,
@@ -360,5 +353,6 @@ create_default_types! {
         cmd: Some("cat".to_owned()),
         args: vec!["{{path}}".to_owned()],
         env: Default::default(),
+        hooks: Default::default(),
     }, [])
 }
@@ -0,0 +1,246 @@
+//! 以史學家的執行紀錄計算腳本的統計資料
+
+use crate::error::Result;
+use crate::script::ScriptInfo;
+use crate::to_display_args;
+use chrono::NaiveDateTime;
+use fxhash::FxHashMap as HashMap;
+use hyper_scripter_historian::{ExecRecord, ExecUsage};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::io::Write;
+
+const TOP_N: usize = 3;
+const SECS_PER_DAY: f64 = 86400.0;
+
+#[derive(Debug, Serialize)]
+pub struct ArgsCount {
+    pub args: Vec<String>,
+    pub count: usize,
+}
+#[derive(Debug, Serialize)]
+pub struct DirCount {
+    pub dir: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScriptStats {
+    pub script_id: i64,
+    pub name: String,
+    pub total_runs: usize,
+    pub success: usize,
+    pub failure: usize,
+    /// 沒有執行完畢事件的執行，可能仍在執行中或被強制中止
+    pub unfinished: usize,
+    /// 由 `hs scheduler` 觸發的執行
+    pub scheduled: usize,
+    pub success_rate: Option<f64>,
+    pub mean_ms: Option<i64>,
+    pub p50_ms: Option<i64>,
+    pub p95_ms: Option<i64>,
+    pub runs_per_day: f64,
+    pub top_args: Vec<ArgsCount>,
+    pub top_dirs: Vec<DirCount>,
+}
+
+fn top_n<'a>(iter: impl Iterator<Item = &'a str>) -> Vec<(&'a str, usize)> {
+    let mut map: HashMap<&str, usize> = Default::default();
+    for s in iter {
+        *map.entry(s).or_default() += 1;
+    }
+    let mut v: Vec<_> = map.into_iter().collect();
+    v.sort_by_key(|(s, count)| (Reverse(*count), *s));
+    v.truncate(TOP_N);
+    v
+}
+
+/// 最近秩法，`durations` 須已排序且非空
+fn percentile(durations: &[i64], p: usize) -> i64 {
+    let rank = (durations.len() * p).div_ceil(100);
+    durations[rank.max(1) - 1]
+}
+
+impl ScriptStats {
+    /// `records` 須依時間排序，見 `Historian::exec_records`
+    pub fn new(
+        script: &ScriptInfo,
+        records: &[&ExecRecord],
+        since: Option<NaiveDateTime>,
+        now: NaiveDateTime,
+    ) -> Self {
+        let mut success = 0;
+        let mut failure = 0;
+        let mut durations = vec![];
+        for record in records.iter() {
+            match record.code {
+                Some(0) => success += 1,
+                Some(_) => failure += 1,
+                None => (),
+            }
+            if let Some(elapsed_ms) = record.elapsed_ms {
+                durations.push(elapsed_ms);
+            } else if let Some(done_time) = record.done_time {
+                durations.push((done_time - record.time).num_milliseconds());
+            }
+        }
+        durations.sort_unstable();
+        let finished = success + failure;
+
+        let (mean_ms, p50_ms, p95_ms) = if durations.is_empty() {
+            (None, None, None)
+        } else {
+            let mean = durations.iter().sum::<i64>() / durations.len() as i64;
+            (
+                Some(mean),
+                Some(percentile(&durations, 50)),
+                Some(percentile(&durations, 95)),
+            )
+        };
+
+        let start = since.or_else(|| records.first().map(|r| r.time));
+        let days = start.map_or(1.0, |start| {
+            let secs = (now - start).num_seconds() as f64;
+            (secs / SECS_PER_DAY).max(1.0)
+        });
+
+        let top_args = top_n(records.iter().map(|r| r.args.as_str()))
+            .into_iter()
+            .map(|(args, count)| {
+                let args = serde_json::from_str(args).unwrap_or_else(|e| {
+                    log::warn!("無法解析參數 {}：{}", args, e);
+                    vec![]
+                });
+                ArgsCount { args, count }
+            })
+            .collect();
+        let dirs = records
+            .iter()
+            .map(|r| r.dir.as_str())
+            .filter(|d| !d.is_empty());
+        let top_dirs = top_n(dirs)
+            .into_iter()
+            .map(|(dir, count)| DirCount {
+                dir: dir.to_owned(),
+                count,
+            })
+            .collect();
+
+        ScriptStats {
+            script_id: script.id,
+            name: script.name.key().to_string(),
+            total_runs: records.len(),
+            success,
+            failure,
+            unfinished: records.len() - finished,
+            scheduled: records.iter().filter(|r| r.scheduled).count(),
+            success_rate: if finished == 0 {
+                None
+            } else {
+                Some(success as f64 / finished as f64)
+            },
+            mean_ms,
+            p50_ms,
+            p95_ms,
+            runs_per_day: records.len() as f64 / days,
+            top_args,
+            top_dirs,
+        }
+    }
+}
+
+pub fn fmt_ms(ms: i64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else if ms < 60 * 1000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        let secs = ms / 1000;
+        format!("{}m{}s", secs / 60, secs % 60)
+    }
+}
+
+/// 如 `exit 0, 1.2s, user 300ms, sys 50ms, max rss 12.3MB`，較舊的紀錄只有回傳值
+pub fn fmt_last_run(code: i32, usage: Option<&ExecUsage>) -> String {
+    let mut s = match usage.and_then(|u| u.signal) {
+        Some(signal) => format!("killed by signal {}", signal),
+        None => format!("exit {}", code),
+    };
+    if let Some(usage) = usage {
+        s += &format!(", {}", fmt_ms(usage.elapsed_ms));
+        if let (Some(user), Some(sys)) = (usage.user_time_ms, usage.sys_time_ms) {
+            s += &format!(", user {}, sys {}", fmt_ms(user), fmt_ms(sys));
+        }
+        if let Some(rss) = usage.max_rss_kb {
+            s += &format!(", max rss {:.1}MB", rss as f64 / 1024.0);
+        }
+    }
+    s
+}
+
+pub fn fmt_stats<W: Write>(w: &mut W, stats: &ScriptStats) -> Result {
+    writeln!(w, "{}", stats.name)?;
+    write!(
+        w,
+        "  runs: {} ({} ok, {} failed, {} unfinished)",
+        stats.total_runs, stats.success, stats.failure, stats.unfinished
+    )?;
+    if stats.scheduled > 0 {
+        write!(w, ", {} scheduled", stats.scheduled)?;
+    }
+    if let Some(rate) = stats.success_rate {
+        write!(w, ", success rate {:.1}%", rate * 100.0)?;
+    }
+    writeln!(w)?;
+    if let (Some(mean), Some(p50), Some(p95)) = (stats.mean_ms, stats.p50_ms, stats.p95_ms) {
+        writeln!(
+            w,
+            "  duration: mean {}, p50 {}, p95 {}",
+            fmt_ms(mean),
+            fmt_ms(p50),
+            fmt_ms(p95)
+        )?;
+    }
+    writeln!(w, "  runs per day: {:.2}", stats.runs_per_day)?;
+
+    write!(w, "  top args:")?;
+    for (i, ArgsCount { args, count }) in stats.top_args.iter().enumerate() {
+        let sep = if i == 0 { " " } else { ", " };
+        if args.is_empty() {
+            write!(w, "{}{}x (none)", sep, count)?;
+        } else {
+            let args: Vec<_> = args.iter().map(|s| to_display_args(s)).collect();
+            write!(w, "{}{}x `{}`", sep, count, args.join(" "))?;
+        }
+    }
+    writeln!(w)?;
+
+    if !stats.top_dirs.is_empty() {
+        write!(w, "  top dirs:")?;
+        for (i, DirCount { dir, count }) in stats.top_dirs.iter().enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            write!(w, "{}{}x {}", sep, count, dir)?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_percentile() {
+        let v: Vec<i64> = (1..=20).collect();
+        assert_eq!(percentile(&v, 50), 10);
+        assert_eq!(percentile(&v, 95), 19);
+        assert_eq!(percentile(&[7], 95), 7);
+        assert_eq!(percentile(&[1, 2], 50), 1);
+    }
+    #[test]
+    fn test_fmt_ms() {
+        assert_eq!(fmt_ms(350), "350ms");
+        assert_eq!(fmt_ms(1250), "1.2s");
+        assert_eq!(fmt_ms(192_000), "3m12s");
+    }
+}
@@ -0,0 +1,221 @@
+//! 把排程匯出成 systemd 的使用者單元檔，每個排程一組 `.service` 和 `.timer`。
+//! 由 systemd 以 `hs run =name!` 觸發，不需要常駐的 `hs scheduler`
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::path;
+use crate::schedule::Schedule;
+use crate::script::ScriptInfo;
+use crate::util::{self, handle_fs_res, TmplVal};
+use fxhash::FxHashSet as HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs::{create_dir_all, read_dir};
+use std::path::{Path, PathBuf};
+
+const PREFIX: &str = "hs-";
+const HEADER: &str = "# Generated by `hs export-units`, changes will be overwritten\n";
+
+#[derive(Debug)]
+pub struct Unit {
+    pub file_name: String,
+    pub content: String,
+}
+
+#[derive(Debug)]
+pub enum Drift {
+    /// 該有的單元檔不存在
+    Missing(PathBuf),
+    /// 單元檔和產生出來的不同
+    Changed(PathBuf),
+    /// 單元檔對應的排程已被刪除
+    Orphaned(PathBuf),
+}
+
+impl Display for Drift {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // LOCALE
+        match self {
+            Drift::Missing(p) => write!(f, "missing   {}", p.display()),
+            Drift::Changed(p) => write!(f, "changed   {}", p.display()),
+            Drift::Orphaned(p) => write!(f, "orphaned  {} (schedule removed)", p.display()),
+        }
+    }
+}
+
+/// 雙引號字串中的跳脫，`%` 是 systemd 的特殊字元
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('%', "%%")
+}
+
+/// `ExecStart=` 中的一個參數，`$` 會被 systemd 展開所以也要跳脫
+fn quote_arg(s: &str) -> String {
+    let escaped = escape(s).replace('$', "$$");
+    if s.is_empty() || s.chars().any(|c| c.is_whitespace() || "\"'\\;".contains(c)) {
+        format!("\"{}\"", escaped)
+    } else {
+        escaped
+    }
+}
+
+fn unit_stem(info: &ScriptInfo, schedule: &Schedule) -> String {
+    let name: String = info
+        .name
+        .key()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "_.-".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}-{}", PREFIX, name, schedule.id)
+}
+
+/// 從單元檔名解析出排程編號，不是由 hs 產生的單元檔則回傳 `None`
+fn parse_schedule_id(file_name: &str) -> Option<i64> {
+    let stem = file_name.strip_prefix(PREFIX)?;
+    let stem = stem
+        .strip_suffix(".service")
+        .or_else(|| stem.strip_suffix(".timer"))?;
+    stem.rsplit_once('-')?.1.parse().ok()
+}
+
+/// 產生一個排程的 `.service` 和 `.timer`。環境變數由 `Config::gen_env` 渲染，
+/// 此時還沒有執行編號和內容，渲染結果為空的變數就略過
+pub fn gen_units(info: &ScriptInfo, schedule: &Schedule) -> Result<[Unit; 2]> {
+    let home = path::get_home();
+    let exe = std::env::current_exe()?;
+    let script_path = home.join(info.file_path_fallback());
+    let name = info.name.key();
+    let mut tags: Vec<_> = info.tags.iter().map(|t| t.as_ref()).collect();
+    tags.sort();
+    let tmpl_val = TmplVal::with_script(&name, tags, &script_path);
+    let mut env = Config::get().gen_env(&tmpl_val, false)?;
+    env.retain(|(_, val)| !val.is_empty());
+    env.sort();
+
+    let stem = unit_stem(info, schedule);
+    let desc = format!("hs {} (schedule {})", name, schedule.id).replace('%', "%%");
+
+    let mut service = format!(
+        "{}[Unit]\nDescription={}\n\n[Service]\nType=oneshot\n",
+        HEADER, desc
+    );
+    let home_env = format!("{}={}", path::HOME_ENV, home.to_string_lossy());
+    let env = env.iter().map(|(key, val)| format!("{}={}", key, val));
+    for pair in std::iter::once(home_env).chain(env) {
+        service += &format!("Environment=\"{}\"\n", escape(&pair));
+    }
+    let exec: Vec<_> = [
+        exe.to_string_lossy().into_owned(),
+        "--no-caution".to_owned(),
+        "run".to_owned(),
+        format!("={}!", name),
+    ]
+    .iter()
+    .chain(schedule.args.iter())
+    .map(|s| quote_arg(s))
+    .collect();
+    service += &format!("ExecStart={}\n", exec.join(" "));
+
+    let on_calendar = match &schedule.cron {
+        Some(cron) => cron.to_on_calendar(),
+        None => vec![schedule
+            .next_time
+            .format("%Y-%m-%d %H:%M:%S UTC")
+            .to_string()],
+    };
+    let mut timer = format!("{}[Unit]\nDescription={}\n\n[Timer]\n", HEADER, desc);
+    for on_calendar in on_calendar.iter() {
+        timer += &format!("OnCalendar={}\n", on_calendar);
+    }
+    timer += "Persistent=true\n\n[Install]\nWantedBy=timers.target\n";
+
+    Ok([
+        Unit {
+            file_name: format!("{}.service", stem),
+            content: service,
+        },
+        Unit {
+            file_name: format!("{}.timer", stem),
+            content: timer,
+        },
+    ])
+}
+
+/// 比對產生的單元檔和 `dir` 中的檔案。`schedule_ids` 是所有現存的排程，
+/// 用來找出排程已被刪除的單元檔
+pub fn check(dir: &Path, units: &[Unit], schedule_ids: &HashSet<i64>) -> Result<Vec<Drift>> {
+    let mut drifts = vec![];
+    for unit in units.iter() {
+        let p = dir.join(&unit.file_name);
+        if !p.exists() {
+            drifts.push(Drift::Missing(p));
+        } else if util::read_file(&p)? != unit.content {
+            drifts.push(Drift::Changed(p));
+        }
+    }
+    if dir.is_dir() {
+        let mut orphans = vec![];
+        for entry in handle_fs_res(&[dir], read_dir(dir))? {
+            let entry = entry.map_err(|e| util::handle_fs_err(&[dir], e))?;
+            let file_name = entry.file_name();
+            let Some(id) = file_name.to_str().and_then(parse_schedule_id) else {
+                continue;
+            };
+            if !schedule_ids.contains(&id) {
+                orphans.push(entry.path());
+            }
+        }
+        orphans.sort();
+        drifts.extend(orphans.into_iter().map(Drift::Orphaned));
+    }
+    Ok(drifts)
+}
+
+/// 寫入有缺或有變的單元檔。排程已被刪除的單元檔不會動到，以免誤刪使用者的檔案
+pub fn write(dir: &Path, units: &[Unit], drifts: &[Drift]) -> Result {
+    handle_fs_res(&[dir], create_dir_all(dir))?;
+    for unit in units.iter() {
+        let p = dir.join(&unit.file_name);
+        let need_write = drifts.iter().any(|d| match d {
+            Drift::Missing(d) | Drift::Changed(d) => *d == p,
+            Drift::Orphaned(_) => false,
+        });
+        if need_write {
+            log::info!("寫入單元檔 {:?}", p);
+            util::write_file(&p, &unit.content)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_quote_arg() {
+        assert_eq!(quote_arg("plain"), "plain");
+        assert_eq!(quote_arg("a b"), "\"a b\"");
+        assert_eq!(quote_arg(""), "\"\"");
+        assert_eq!(quote_arg("50%"), "50%%");
+        assert_eq!(quote_arg("$HOME"), "$$HOME");
+        assert_eq!(quote_arg("say \"hi\""), "\"say \\\"hi\\\"\"");
+    }
+
+    #[test]
+    fn test_parse_schedule_id() {
+        assert_eq!(parse_schedule_id("hs-backup-3.service"), Some(3));
+        assert_eq!(parse_schedule_id("hs-my-job-12.timer"), Some(12));
+        assert_eq!(parse_schedule_id("hs-.1-4.timer"), Some(4));
+        assert_eq!(parse_schedule_id("hs-backup-3.conf"), None);
+        assert_eq!(parse_schedule_id("other-3.service"), None);
+        assert_eq!(parse_schedule_id("hs-backup.service"), None);
+    }
+}
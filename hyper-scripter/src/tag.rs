@@ -1,4 +1,4 @@
-use crate::error::{DisplayError, DisplayResult, FormatCode::Tag as TagCode};
+use crate::error::{Contextable, DisplayError, DisplayResult, FormatCode::Tag as TagCode, Result};
 use crate::script_type::ScriptType;
 use crate::util::illegal_name;
 use crate::util::{impl_de_by_from_str, impl_ser_by_to_string};
@@ -8,19 +8,26 @@ use std::str::FromStr;
 
 pub type TagSet = HashSet<Tag>;
 
+/// 每個選擇器可附帶一個標籤（如具名選擇器的名字），僅供解釋篩選結果之用
 #[derive(Debug, Clone, Eq, PartialEq, Default)]
-pub struct TagSelectorGroup(Vec<TagSelector>);
+pub struct TagSelectorGroup(Vec<(Option<String>, TagSelector)>);
 impl TagSelectorGroup {
     pub fn push(&mut self, selector: TagSelector) {
+        self.push_inner(None, selector);
+    }
+    pub fn push_labeled(&mut self, label: String, selector: TagSelector) {
+        self.push_inner(Some(label), selector);
+    }
+    fn push_inner(&mut self, label: Option<String>, selector: TagSelector) {
         if selector.append {
-            self.0.push(selector);
+            self.0.push((label, selector));
         } else {
-            self.0 = vec![selector];
+            self.0 = vec![(label, selector)];
         }
     }
     pub fn select(&self, tags: &TagSet, ty: &ScriptType) -> bool {
         let mut pass = false;
-        for f in self.0.iter() {
+        for (_, f) in self.0.iter() {
             let res = f.select(tags, ty);
             match res {
                 SelectResult::MandatoryFalse => return false,
@@ -30,10 +37,44 @@ impl TagSelectorGroup {
         }
         pass
     }
+    /// 同 `select`，但不會提早結束，並記錄每個選擇器的結果
+    pub fn explain(&self, tags: &TagSet, ty: &ScriptType) -> SelectExplain<'_> {
+        let mut steps = vec![];
+        let mut decisive = None;
+        let mut pass = false;
+        let mut mandatory_false = false;
+        for (i, (label, f)) in self.0.iter().enumerate() {
+            let result = f.select(tags, ty);
+            if !mandatory_false {
+                match result {
+                    SelectResult::MandatoryFalse => {
+                        mandatory_false = true;
+                        decisive = Some(i);
+                        pass = false;
+                    }
+                    SelectResult::Normal(res) => {
+                        decisive = Some(i);
+                        pass = res;
+                    }
+                    SelectResult::None => (),
+                }
+            }
+            steps.push(SelectStep {
+                label: label.as_deref(),
+                selector: f,
+                result,
+            });
+        }
+        SelectExplain {
+            steps,
+            decisive,
+            verdict: pass,
+        }
+    }
 }
 impl From<TagSelector> for TagSelectorGroup {
     fn from(t: TagSelector) -> Self {
-        TagSelectorGroup(vec![t])
+        TagSelectorGroup(vec![(None, t)])
     }
 }
 
@@ -45,10 +86,23 @@ pub struct TagSelector {
 impl_de_by_from_str!(TagSelector);
 impl_ser_by_to_string!(TagSelector);
 
-#[derive(Debug, Clone, Eq, PartialEq, Default)]
-pub struct TagGroup(Vec<TagControl>);
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TagGroup(TagGroupInner);
 impl_de_by_from_str!(TagGroup);
 impl_ser_by_to_string!(TagGroup);
+impl Default for TagGroup {
+    fn default() -> Self {
+        TagGroup(TagGroupInner::Flat(vec![]))
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum TagGroupInner {
+    /// 簡易版，如 `all,^hide,^build`
+    Flat(Vec<TagControl>),
+    /// 終極版，如 `(all & !hide) | (!build & !setup)`。第二個值為是否強制，即整個表達式加上 `!` 後綴，如 `(a | b)!`
+    Expr(TagExpr, bool),
+}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct TagControl {
@@ -155,35 +209,37 @@ impl Display for TagSelector {
 }
 
 impl TagSelector {
+    /// NOTE: 若其中一方為表達式，附加的結果會是兩者的交集，此時 `!` 強制標記（包括表達式的）會被捨棄
+    /// 目前只有在計算新腳本的標籤時會用到，故無傷大雅
     pub fn push(&mut self, other: Self) {
-        if other.append {
-            self.tags.0.extend(other.tags.0.into_iter());
-        } else {
-            *self = other
+        if !other.append {
+            *self = other;
+            return;
+        }
+        match (&mut self.tags.0, other.tags.0) {
+            (TagGroupInner::Flat(me), TagGroupInner::Flat(other)) => me.extend(other),
+            (me, other) => {
+                let me_expr = std::mem::replace(me, TagGroupInner::Flat(vec![])).into_expr();
+                let mut exprs = match me_expr {
+                    TagExpr::And(v) => v,
+                    e => vec![e],
+                };
+                exprs.push(other.into_expr());
+                *me = TagGroupInner::Expr(TagExpr::And(exprs), false);
+            }
         }
     }
     pub fn fill_allowed_map<U>(self, set: &mut std::collections::HashSet<Tag, U>)
     where
         U: std::hash::BuildHasher,
     {
-        for control in self.tags.0.into_iter() {
-            let tag = match control.tag {
-                TagOrType::Type(_) => continue, // 類型篩選，跳過
-                TagOrType::Tag(t) => t,
-            };
-            if control.allow {
-                // NOTE: `match_all` 是特殊的，不用被外界知道，雖然知道了也不會怎樣
-                if tag.match_all() {
-                    continue;
+        match self.tags.0 {
+            TagGroupInner::Flat(controls) => {
+                for control in controls.into_iter() {
+                    fill_one(control.allow, control.tag, set);
                 }
-                set.insert(tag);
-            } else {
-                if tag.match_all() {
-                    set.clear(); // XXX: is this the right thing to do?
-                    continue;
-                }
-                set.remove(&tag);
             }
+            TagGroupInner::Expr(expr, _) => expr.fill_allowed_map(set),
         }
     }
     pub fn into_allowed_iter(self) -> impl Iterator<Item = Tag> {
@@ -196,23 +252,72 @@ impl TagSelector {
     }
 }
 
+fn fill_one<U>(allow: bool, tag: TagOrType, set: &mut std::collections::HashSet<Tag, U>)
+where
+    U: std::hash::BuildHasher,
+{
+    let tag = match tag {
+        TagOrType::Type(_) => return, // 類型篩選，跳過
+        TagOrType::Tag(t) => t,
+    };
+    if allow {
+        // NOTE: `match_all` 是特殊的，不用被外界知道，雖然知道了也不會怎樣
+        if tag.match_all() {
+            return;
+        }
+        set.insert(tag);
+    } else {
+        if tag.match_all() {
+            set.clear(); // XXX: is this the right thing to do?
+            return;
+        }
+        set.remove(&tag);
+    }
+}
+
+fn is_expr(s: &str) -> bool {
+    // NOTE: 簡易版中 `!` 只會出現在結尾，且標籤本身不可含空白
+    s.trim_start().starts_with('!')
+        || s.contains(|c: char| matches!(c, '(' | ')' | '&' | '|') || c.is_whitespace())
+}
+
 impl FromStr for TagGroup {
     type Err = DisplayError;
     fn from_str(s: &str) -> DisplayResult<Self> {
+        if is_expr(s) {
+            // NOTE: 強制後綴只能加在括號後，以免和表達式中的 `!` 混淆
+            let (s, mandatory) = match s.trim_end().strip_suffix(MANDATORY_SUFFIX) {
+                Some(rest) if rest.ends_with(')') => (rest, true),
+                _ => (s, false),
+            };
+            let expr = TagExpr::parse(s)?;
+            return Ok(TagGroup(TagGroupInner::Expr(expr, mandatory)));
+        }
         let mut tags = vec![];
         if !s.is_empty() {
             for ctrl in s.split(',') {
                 tags.push(ctrl.parse()?);
             }
         }
-        Ok(TagGroup(tags))
+        Ok(TagGroup(TagGroupInner::Flat(tags)))
     }
 }
 
 impl Display for TagGroup {
     fn fmt(&self, w: &mut Formatter<'_>) -> FmtResult {
+        let controls = match &self.0 {
+            TagGroupInner::Flat(controls) => controls,
+            TagGroupInner::Expr(expr, true) => {
+                return write!(w, "({}){}", expr, MANDATORY_SUFFIX);
+            }
+            TagGroupInner::Expr(TagExpr::Atom(atom), false) => {
+                // NOTE: 單一標籤的表達式須加上括號，否則會被當成簡易版
+                return write!(w, "({})", atom);
+            }
+            TagGroupInner::Expr(expr, false) => return write!(w, "{}", expr),
+        };
         let mut first = true;
-        for f in self.0.iter() {
+        for f in controls.iter() {
             if !first {
                 write!(w, ",")?;
             }
@@ -229,9 +334,13 @@ impl Display for TagGroup {
     }
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Display)]
 pub enum SelectResult {
+    #[display(fmt = "None")]
     None,
+    #[display(fmt = "MandatoryFalse")]
     MandatoryFalse,
+    #[display(fmt = "Normal({})", _0)]
     Normal(bool),
 }
 impl SelectResult {
@@ -240,14 +349,49 @@ impl SelectResult {
     }
 }
 
+#[derive(Debug)]
+pub struct SelectStep<'a> {
+    pub label: Option<&'a str>,
+    pub selector: &'a TagSelector,
+    pub result: SelectResult,
+}
+
+#[derive(Debug)]
+pub struct SelectExplain<'a> {
+    pub steps: Vec<SelectStep<'a>>,
+    /// 決定最終結果的選擇器，若為 `None` 代表沒有任何選擇器命中
+    pub decisive: Option<usize>,
+    pub verdict: bool,
+}
+
+impl TagOrType {
+    fn hit(&self, tags: &TagSet, ty: &ScriptType) -> bool {
+        match self {
+            TagOrType::Type(t) => ty == t,
+            TagOrType::Tag(t) => t.match_all() || tags.contains(t),
+        }
+    }
+}
+
 impl TagGroup {
+    /// 表達式不通過時的結果取決於其極性：單純的表達式如同沒命中的 `tag`，不做決定；
+    /// 最外層為否定（如 `!hide`）或加上 `!` 後綴者，則視同強制排除（如同 `^tag!`），以免被之後的選擇器蓋掉
     pub fn select(&self, tags: &TagSet, ty: &ScriptType) -> SelectResult {
+        let controls = match &self.0 {
+            TagGroupInner::Flat(controls) => controls,
+            TagGroupInner::Expr(expr, mandatory) => {
+                return if expr.eval(tags, ty) {
+                    SelectResult::Normal(true)
+                } else if *mandatory || matches!(expr, TagExpr::Not(_)) {
+                    SelectResult::MandatoryFalse
+                } else {
+                    SelectResult::None
+                };
+            }
+        };
         let mut pass = SelectResult::None;
-        for ctrl in self.0.iter() {
-            let hit = match &ctrl.tag {
-                TagOrType::Type(t) => ty == t,
-                TagOrType::Tag(t) => t.match_all() || tags.contains(t),
-            };
+        for ctrl in controls.iter() {
+            let hit = ctrl.tag.hit(tags, ty);
             if ctrl.mandatory {
                 if ctrl.allow {
                     if !hit {
@@ -266,3 +410,359 @@ impl TagGroup {
         pass
     }
 }
+
+impl TagGroupInner {
+    fn into_expr(self) -> TagExpr {
+        match self {
+            TagGroupInner::Expr(expr, _) => expr,
+            TagGroupInner::Flat(controls) => {
+                let exprs = controls
+                    .into_iter()
+                    .map(|ctrl| {
+                        let atom = TagExpr::Atom(ctrl.tag);
+                        if ctrl.allow {
+                            atom
+                        } else {
+                            TagExpr::Not(Box::new(atom))
+                        }
+                    })
+                    .collect();
+                TagExpr::And(exprs)
+            }
+        }
+    }
+}
+
+/// 標籤篩選語言的終極版，優先序為 `!` > `&` > `|`
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum TagExpr {
+    Atom(TagOrType),
+    Not(Box<TagExpr>),
+    And(Vec<TagExpr>),
+    Or(Vec<TagExpr>),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Token<'a> {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Atom(&'a str),
+}
+
+fn tokenize(s: &str) -> Vec<Token<'_>> {
+    fn is_special(c: char) -> bool {
+        matches!(c, '(' | ')' | '&' | '|' | '!') || c.is_whitespace()
+    }
+    let mut tokens = vec![];
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        let token = match c {
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '&' => Token::And,
+            '|' => Token::Or,
+            '!' => Token::Not,
+            _ if c.is_whitespace() => {
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+            _ => {
+                let len = rest.find(is_special).unwrap_or(rest.len());
+                tokens.push(Token::Atom(&rest[..len]));
+                rest = &rest[len..];
+                continue;
+            }
+        };
+        tokens.push(token);
+        rest = &rest[c.len_utf8()..];
+    }
+    tokens
+}
+
+struct ExprParser<'a> {
+    src: &'a str,
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+impl<'a> ExprParser<'a> {
+    fn err<T>(&self, msg: &str) -> Result<T> {
+        TagCode.to_res(self.src.to_owned()).context(format!(
+            "{}（第 {} 個符號）",
+            msg,
+            self.pos + 1
+        ))
+    }
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+    fn parse_binary(
+        &mut self,
+        op: Token<'a>,
+        mut sub: impl FnMut(&mut Self) -> Result<TagExpr>,
+        wrap: fn(Vec<TagExpr>) -> TagExpr,
+    ) -> Result<TagExpr> {
+        let mut exprs = vec![sub(self)?];
+        while self.peek() == Some(op) {
+            self.pos += 1;
+            exprs.push(sub(self)?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.pop().unwrap()
+        } else {
+            wrap(exprs)
+        })
+    }
+    fn parse_or(&mut self) -> Result<TagExpr> {
+        self.parse_binary(Token::Or, Self::parse_and, TagExpr::Or)
+    }
+    fn parse_and(&mut self) -> Result<TagExpr> {
+        self.parse_binary(Token::And, Self::parse_unary, TagExpr::And)
+    }
+    fn parse_unary(&mut self) -> Result<TagExpr> {
+        let token = match self.peek() {
+            Some(token) => token,
+            None => return self.err("表達式不完整"),
+        };
+        self.pos += 1;
+        match token {
+            Token::Not => Ok(TagExpr::Not(Box::new(self.parse_unary()?))),
+            Token::LParen => {
+                let expr = self.parse_or()?;
+                if self.peek() != Some(Token::RParen) {
+                    return self.err("括號未閉合");
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Token::Atom(s) if s.contains(',') => {
+                self.pos -= 1;
+                self.err("表達式中不可使用逗號")
+            }
+            Token::Atom(s) => Ok(TagExpr::Atom(s.parse()?)),
+            _ => {
+                self.pos -= 1;
+                self.err("非預期的符號")
+            }
+        }
+    }
+}
+
+impl TagExpr {
+    fn parse(s: &str) -> Result<Self> {
+        let mut parser = ExprParser {
+            src: s,
+            tokens: tokenize(s),
+            pos: 0,
+        };
+        let expr = parser.parse_or()?;
+        if parser.pos != parser.tokens.len() {
+            return parser.err("非預期的符號");
+        }
+        log::debug!("解析標籤表達式 {} 為 {:?}", s, expr);
+        Ok(expr)
+    }
+    fn eval(&self, tags: &TagSet, ty: &ScriptType) -> bool {
+        match self {
+            TagExpr::Atom(atom) => atom.hit(tags, ty),
+            TagExpr::Not(expr) => !expr.eval(tags, ty),
+            TagExpr::And(exprs) => exprs.iter().all(|e| e.eval(tags, ty)),
+            TagExpr::Or(exprs) => exprs.iter().any(|e| e.eval(tags, ty)),
+        }
+    }
+    /// 只有單純的交集（如 `a & !b & @ty`）能推得確切的標籤，其它情況一律忽略
+    fn fill_allowed_map<U>(self, set: &mut std::collections::HashSet<Tag, U>)
+    where
+        U: std::hash::BuildHasher,
+    {
+        match self {
+            TagExpr::Atom(atom) => fill_one(true, atom, set),
+            TagExpr::Not(expr) => match *expr {
+                TagExpr::Atom(atom) => fill_one(false, atom, set),
+                expr => log::warn!("無法從表達式 !{} 推得標籤，忽略之", expr.wrapped()),
+            },
+            TagExpr::And(exprs) => {
+                for expr in exprs.into_iter() {
+                    expr.fill_allowed_map(set);
+                }
+            }
+            expr @ TagExpr::Or(_) => log::warn!("無法從表達式 {} 推得標籤，忽略之", expr),
+        }
+    }
+    fn wrapped(&self) -> Wrapped<'_> {
+        Wrapped(self)
+    }
+}
+
+/// 複合的子表達式一律加上括號
+struct Wrapped<'a>(&'a TagExpr);
+impl Display for Wrapped<'_> {
+    fn fmt(&self, w: &mut Formatter<'_>) -> FmtResult {
+        match self.0 {
+            TagExpr::And(_) | TagExpr::Or(_) => write!(w, "({})", self.0),
+            _ => write!(w, "{}", self.0),
+        }
+    }
+}
+
+impl Display for TagExpr {
+    fn fmt(&self, w: &mut Formatter<'_>) -> FmtResult {
+        let (exprs, sep) = match self {
+            TagExpr::Atom(atom) => return write!(w, "{}", atom),
+            TagExpr::Not(expr) => return write!(w, "!{}", expr.wrapped()),
+            TagExpr::And(exprs) => (exprs, " & "),
+            TagExpr::Or(exprs) => (exprs, " | "),
+        };
+        let mut first = true;
+        for expr in exprs.iter() {
+            if !first {
+                write!(w, "{}", sep)?;
+            }
+            first = false;
+            write!(w, "{}", expr.wrapped())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn tag_set(tags: &[&str]) -> TagSet {
+        tags.iter().map(|t| t.parse().unwrap()).collect()
+    }
+    fn select(selector: &str, tags: &[&str], ty: &str) -> bool {
+        let selector: TagSelector = selector.parse().unwrap();
+        let group: TagSelectorGroup = selector.into();
+        group.select(&tag_set(tags), &ScriptType::new_unchecked(ty.to_owned()))
+    }
+    #[test]
+    fn test_expr_select() {
+        const EXPR: &str = "(all & !hide) | (!build & !setup)";
+        assert!(select(EXPR, &["a"], "sh"));
+        assert!(select(EXPR, &["hide"], "sh"));
+        assert!(select(EXPR, &["build"], "sh"));
+        assert!(!select(EXPR, &["hide", "build"], "sh"));
+        assert!(!select(EXPR, &["hide", "setup"], "sh"));
+
+        assert!(select("a | b & c", &["a"], "sh"));
+        assert!(!select("(a | b) & c", &["a"], "sh"));
+        assert!(select("!!a", &["a"], "sh"));
+        assert!(select("@rb & !hide", &[], "rb"));
+        assert!(!select("@rb & !hide", &[], "sh"));
+        assert!(!select("@rb & !hide", &["hide"], "rb"));
+
+        let ty = ScriptType::new_unchecked("sh".to_owned());
+        let mut group = TagSelectorGroup::default();
+        group.push("+a | b".parse().unwrap());
+        group.push("+all".parse().unwrap());
+        assert!(
+            group.select(&tag_set(&["c"]), &ty),
+            "單純的表達式不通過時不該排除"
+        );
+
+        let mut group = TagSelectorGroup::default();
+        group.push("+(a & !b)!".parse().unwrap());
+        group.push("+!c".parse().unwrap());
+        group.push("+all".parse().unwrap());
+        assert!(group.select(&tag_set(&["a"]), &ty));
+        assert!(
+            !group.select(&tag_set(&["a", "b"]), &ty),
+            "後面的 +all 不該蓋掉強制的表達式"
+        );
+        assert!(
+            !group.select(&tag_set(&["a", "c"]), &ty),
+            "後面的 +all 不該蓋掉最外層的否定"
+        );
+        group.push("c & !d".parse().unwrap());
+        assert!(group.select(&tag_set(&["b", "c"]), &ty));
+    }
+    #[test]
+    fn test_explain() {
+        let mut group = TagSelectorGroup::default();
+        group.push_labeled("pin".to_owned(), "+pin,util".parse().unwrap());
+        group.push_labeled("no-hidden".to_owned(), "+^hide!".parse().unwrap());
+        group.push_labeled("main".to_owned(), "+all".parse().unwrap());
+        let ty = ScriptType::new_unchecked("sh".to_owned());
+
+        let tags = tag_set(&["hide", "pin"]);
+        let explain = group.explain(&tags, &ty);
+        let results: Vec<_> = explain.steps.iter().map(|s| s.result).collect();
+        assert_eq!(
+            vec![
+                SelectResult::Normal(true),
+                SelectResult::MandatoryFalse,
+                SelectResult::Normal(true)
+            ],
+            results
+        );
+        assert_eq!(Some(1), explain.decisive);
+        assert_eq!(group.select(&tags, &ty), explain.verdict);
+        assert_eq!(Some("no-hidden"), explain.steps[1].label);
+
+        let tags = tag_set(&["a"]);
+        let explain = group.explain(&tags, &ty);
+        assert_eq!(Some(2), explain.decisive);
+        assert!(explain.verdict);
+
+        let group: TagSelectorGroup = "x".parse::<TagSelector>().unwrap().into();
+        let explain = group.explain(&tags, &ty);
+        assert_eq!(None, explain.decisive);
+        assert!(!explain.verdict);
+    }
+    #[test]
+    fn test_expr_display() {
+        fn assert_display(s: &str, expected: &str) {
+            let selector: TagSelector = s.parse().unwrap();
+            assert_eq!(expected, selector.to_string());
+            let reparsed: TagSelector = expected.parse().unwrap();
+            assert_eq!(selector, reparsed);
+        }
+        assert_display(
+            "(all&!hide)|(!build&!setup)",
+            "(all & !hide) | (!build & !setup)",
+        );
+        assert_display("a | b & c", "a | (b & c)");
+        assert_display("+!(a | b)", "+!(a | b)");
+        assert_display("(a & (b & c))", "a & (b & c)");
+        assert_display("(a)", "(a)");
+        assert_display("  @rb ", "(@rb)");
+        assert_display("a,^b!,@rb", "a,^b!,@rb");
+        assert_display("(a | b)!", "(a | b)!");
+        assert_display("+(a)!", "+(a)!");
+    }
+    #[test]
+    fn test_expr_error() {
+        for s in [
+            "(a", "a)", "a &", "a & | b", "a & b!", "()", "a b", "a,b & c", "!",
+        ] {
+            s.parse::<TagSelector>()
+                .expect_err(&format!("{} 不該是合法的表達式", s));
+        }
+    }
+    #[test]
+    fn test_expr_allowed() {
+        fn allowed(s: &str) -> Vec<String> {
+            let selector: TagSelector = s.parse().unwrap();
+            let mut v: Vec<_> = selector
+                .into_allowed_iter()
+                .map(|t| t.to_string())
+                .collect();
+            v.sort();
+            v
+        }
+        assert_eq!(vec!["a", "c"], allowed("a & !b & c & @rb"));
+        assert_eq!(vec!["c"], allowed("(a | b) & c"));
+
+        let mut selector: TagSelector = "a,b".parse().unwrap();
+        selector.push("+c & !a".parse().unwrap());
+        let mut v: Vec<_> = selector
+            .into_allowed_iter()
+            .map(|t| t.to_string())
+            .collect();
+        v.sort();
+        assert_eq!(vec!["b", "c"], v);
+    }
+}
@@ -1,14 +1,21 @@
-use super::{init_repo, print_iter};
+use super::{init_repo, print_iter, read_file};
+use crate::arg_schema::ArgSchema;
 use crate::args::{AliasRoot, Completion, Root, Subs};
 use crate::config::Config;
-use crate::error::{Error, Result};
+use crate::error::{Contextable, Error, Result};
+use crate::extract_msg::extract_complete_from_content;
 use crate::fuzzy::{fuzz_with_multifuzz_ratio, is_prefix, FuzzResult};
 use crate::path;
+use crate::query::do_script_query;
 use crate::script_repo::{RepoEntry, ScriptRepo, Visibility};
 use crate::SEP;
 use crate::{to_display_args, Either};
 use clap::Parser;
 use std::cmp::Reverse;
+use std::process::{Command, Stdio};
+
+/// 補全腳本參數時，最多參考幾組歷史參數
+const HISTORY_ARGS_LIMIT: u32 = 100;
 
 fn sort(v: &mut Vec<RepoEntry<'_>>) {
     v.sort_by_key(|s| Reverse(s.last_time()));
@@ -61,6 +68,76 @@ async fn fuzz_arr<'a>(
     })
 }
 
+/// 找出腳本第 `words.len()` 個參數的候選值，依序來自參數宣告、歷史參數（依出現次數排序）與 `[HS_COMPLETE]` 指令
+async fn script_args_candidates(
+    entry: &RepoEntry<'_>,
+    words: &[String],
+    cur: &str,
+) -> Result<Vec<String>> {
+    let script_path = path::open_script(&entry.name, &entry.ty, Some(true))?;
+    let content = read_file(&script_path)?;
+    let mut candidates = vec![];
+
+    match ArgSchema::from_content(&content) {
+        Ok(schema) => candidates.extend(schema.candidates(words, cur)),
+        Err(e) => log::warn!("參數宣告有誤，略過：{:?}", e),
+    }
+
+    let historian = &entry.get_env().historian;
+    let here = path::normalize_path(".").ok();
+    let mut list = historian
+        .args_counts(entry.id, HISTORY_ARGS_LIMIT, here.as_deref())
+        .await?;
+    if list.is_empty() && here.is_some() {
+        log::debug!("此目錄下無歷史參數，改查所有目錄");
+        list = historian
+            .args_counts(entry.id, HISTORY_ARGS_LIMIT, None)
+            .await?;
+    }
+    // NOTE: 歷史參數依時間排序，次數相同者較新的在前
+    let mut counts: Vec<(String, i64)> = vec![];
+    for (args, n) in list.into_iter() {
+        let args: Vec<String> =
+            serde_json::from_str(&args).context(format!("反序列失敗 {}", args))?;
+        let Some(word) = args.into_iter().nth(words.len()) else {
+            continue;
+        };
+        match counts.iter_mut().find(|(w, _)| *w == word) {
+            Some((_, count)) => *count += n,
+            None => counts.push((word, n)),
+        }
+    }
+    counts.sort_by_key(|(_, count)| Reverse(*count));
+    candidates.extend(counts.into_iter().map(|(w, _)| w));
+
+    for cmd in extract_complete_from_content(&content) {
+        log::info!("執行補全指令 {}", cmd);
+        let output = Command::new("sh")
+            .args(["-c", cmd, "sh"])
+            .args(words)
+            .arg(cur)
+            .env("HS_PATH", &script_path)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                candidates.extend(stdout.lines().map(str::to_owned));
+            }
+            res => log::warn!("補全指令 {} 失敗：{:?}", cmd, res),
+        }
+    }
+
+    let mut ret: Vec<String> = vec![];
+    for c in candidates.into_iter() {
+        if !c.is_empty() && c.starts_with(cur) && !ret.contains(&c) {
+            ret.push(c);
+        }
+    }
+    Ok(ret)
+}
+
 pub async fn handle_completion(comp: Completion, repo: &mut Option<ScriptRepo>) -> Result {
     match comp {
         Completion::LS {
@@ -123,13 +200,50 @@ pub async fn handle_completion(comp: Completion, repo: &mut Option<ScriptRepo>)
 
             let home = path::compute_home_path_optional(root.root_args.hs_home.as_ref(), false)?;
             let conf = Config::load(&home)?;
-            if let Some(Either::One(new_args)) = root.expand_alias(&args, &conf) {
-                print_iter(new_args.map(to_display_args), " ");
+            if let Some(Either::One(new_args)) = root.expand_alias(&args, &conf)? {
+                print_iter(new_args.iter().map(|s| to_display_args(s)), " ");
             } else {
                 log::info!("並非別名");
                 return Err(Error::Completion);
             };
         }
+        Completion::ScriptArgs { mut args } => {
+            let alias_root = parse_alias_root(&args)?;
+            if !alias_root.root_args.no_alias {
+                let hs_home = alias_root.root_args.hs_home.as_ref();
+                let home = path::compute_home_path_optional(hs_home, false)?;
+                let conf = Config::load(&home)?;
+                if let Some(Either::One(new_args)) = alias_root.expand_alias(&args, &conf)? {
+                    log::info!("展開別名為 {:?}", new_args);
+                    args = new_args;
+                }
+            }
+
+            let mut root = Root::try_parse_from(args).map_err(|e| {
+                log::warn!("補全時出錯 {}", e);
+                Error::Completion
+            })?;
+            root.sanitize()?;
+            let (script_query, mut words) = match root.subcmd.take() {
+                Some(Subs::Run {
+                    script_query, args, ..
+                }) => (script_query, args),
+                res => {
+                    log::warn!("非執行指令 {:?}", res);
+                    return Err(Error::Completion);
+                }
+            };
+            // NOTE: 最後一個字是正在輸入的參數，沒有的話代表還在輸入腳本名
+            let cur = words.pop().ok_or(Error::Completion)?;
+
+            root.set_home_unless_from_alias(false)?;
+            *repo = Some(init_repo(root.root_args, false).await?);
+            let entry = do_script_query(&script_query, repo.as_mut().unwrap(), false, true)
+                .await?
+                .ok_or(Error::Completion)?;
+            let candidates = script_args_candidates(&entry, &words, &cur).await?;
+            print_iter(candidates.iter(), "\n");
+        }
         Completion::Home { args } => {
             let root = parse_alias_root(&args)?;
             let home = root.root_args.hs_home.ok_or_else(|| Error::Completion)?;
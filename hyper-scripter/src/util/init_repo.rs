@@ -1,6 +1,6 @@
 use super::main_util;
 use crate::args::RootArgs;
-use crate::config::{Config, Recent};
+use crate::config::{Config, Recent, ARG_SELECTOR_LABEL};
 use crate::error::{Contextable, Error, Result};
 use crate::path;
 use crate::script_repo::{DBEnv, RecentFilter, ScriptRepo};
@@ -50,7 +50,7 @@ pub async fn init_repo(args: RootArgs, need_journal: bool) -> Result<ScriptRepo>
             return Err(Error::TagSelectorNotFound(name));
         }
         for select in select.into_iter() {
-            tag_group.push(select);
+            tag_group.push_labeled(ARG_SELECTOR_LABEL.to_owned(), select);
         }
         tag_group
     };
@@ -1,11 +1,12 @@
 use super::PrepareRespond;
-use crate::args::Subs;
+use crate::args::{self, BrowseAction, Subs};
 use crate::color::Stylize;
 use crate::config::Config;
 use crate::env_pair::EnvPair;
 use crate::error::{Contextable, Error, RedundantOpt, Result};
 use crate::extract_msg::extract_env_from_content_help_aware;
 use crate::path;
+use crate::picker::Picker;
 use crate::process_lock::{ProcessLockRead, ProcessLockWrite};
 use crate::query::{
     self, do_list_query_with_handler, EditQuery, ListQuery, ListQueryHandler, ScriptQuery,
@@ -13,12 +14,20 @@ use crate::query::{
 };
 use crate::script::{IntoScriptName, ScriptInfo, ScriptName};
 use crate::script_repo::{RepoEntry, ScriptRepo, Visibility};
+use crate::script_time::ScriptTime;
 use crate::script_type::{iter_default_templates, ScriptFullType, ScriptType};
 use crate::tag::{Tag, TagSelector, TagSelectorGroup};
+use crate::{to_display_args, APP_NAME, SEP};
 use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
-use std::fs::{create_dir_all, read_dir};
+use hyper_scripter_historian::{ExecEvent, ExecUsage, Historian, LastTimeRecord};
+use std::fs::{create_dir_all, read_dir, File};
+use std::io::Write;
+use std::num::NonZeroU64;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
+
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
 
 pub struct EditTagArgs {
     pub content: TagSelector,
@@ -61,7 +70,7 @@ pub async fn mv(
             if let Some(tags) = tags {
                 info.append_tags(tags);
             }
-            info.write();
+            info.write(None);
         })
         .await?;
     Ok(())
@@ -244,13 +253,15 @@ pub async fn edit_or_create(
     }
 }
 
+/// 回傳腳本的回傳值（若非成功）及資源用量，預腳本的錯誤則直接回傳 `Err`
 fn run(
     script_path: &Path,
     info: &ScriptInfo,
     remaining: &[String],
     hs_tmpl_val: &super::TmplVal<'_>,
     remaining_envs: &[EnvPair],
-) -> Result<()> {
+    log: Option<&Path>,
+) -> Result<(Option<i32>, ExecUsage)> {
     let conf = Config::get();
     let ty = &info.ty;
 
@@ -297,26 +308,80 @@ fn run(
     let mut cmd = super::create_cmd(&cmd_str, full_args);
     set_cmd_envs(&mut cmd);
 
-    let code = super::run_cmd(cmd)?;
-    log::info!("程式執行結果：{:?}", code);
-    if let Some(code) = code {
-        Err(Error::ScriptError(code))
-    } else {
-        Ok(())
+    let (code, usage) = super::run_cmd_with_usage(cmd, log)?;
+    log::info!("程式執行結果：{:?} {:?}", code, usage);
+    Ok((code, usage))
+}
+
+/// 若腳本符合 `caution_tags`，詢問使用者是否確定要執行
+pub fn confirm_caution(entry: &RepoEntry<'_>, args: &[String]) -> Result {
+    if Config::get_no_caution()
+        || !Config::get()
+            .caution_tags
+            .select(&entry.tags, &entry.ty)
+            .is_true()
+    {
+        return Ok(());
+    }
+    let ty = super::get_display_type(&entry.ty);
+    let mut first_part = entry.name.to_string();
+    for arg in args.iter() {
+        first_part += " ";
+        first_part += arg;
+    }
+    let msg = format!(
+        "{} requires extra caution. Are you sure?",
+        first_part.stylize().color(ty.color()).bold()
+    );
+    let yes = super::prompt(msg, false)?;
+    if !yes {
+        return Err(Error::Caution);
+    }
+    Ok(())
+}
+
+pub struct RunOptions {
+    pub repeat: u64,
+    /// 只記錄執行，不真的執行腳本
+    pub dummy: bool,
+    /// 接在前一次執行的參數後面
+    pub use_previous: bool,
+    /// 沒有前一次的參數時報錯，而不是當作空的
+    pub error_no_previous: bool,
+    /// 只找在這個目錄下執行過的前一次參數
+    pub dir: Option<PathBuf>,
+    /// 蓋過前一次執行的環境變數
+    pub env_overrides: Vec<EnvPair>,
+}
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            repeat: 1,
+            dummy: false,
+            use_previous: false,
+            error_no_previous: false,
+            dir: None,
+            env_overrides: vec![],
+        }
     }
 }
+
 pub async fn run_n_times(
-    repeat: u64,
-    dummy: bool,
     entry: &mut RepoEntry<'_>,
     mut args: Vec<String>,
     res: &mut Vec<Error>,
-    use_previous: bool,
-    error_no_previous: bool,
-    dir: Option<PathBuf>,
+    opts: RunOptions,
 ) -> Result {
     log::info!("執行 {:?}", entry.name);
     super::hijack_ctrlc_once();
+    let RunOptions {
+        repeat,
+        dummy,
+        use_previous,
+        error_no_previous,
+        dir,
+        env_overrides,
+    } = opts;
 
     let mut env_vec = vec![];
     if use_previous {
@@ -337,31 +402,16 @@ pub async fn run_n_times(
             }
         }
     }
+    EnvPair::merge(&mut env_vec, env_overrides);
 
     let here = path::normalize_path(".").ok();
     let script_path = path::open_script(&entry.name, &entry.ty, Some(true))?;
     let content = super::read_file(&script_path)?;
+    let arg_envs = crate::arg_schema::check_args(&entry.name.key(), &content, &args)?;
 
-    if !Config::get_no_caution()
-        && Config::get()
-            .caution_tags
-            .select(&entry.tags, &entry.ty)
-            .is_true()
-    {
-        let ty = super::get_display_type(&entry.ty);
-        let mut first_part = entry.name.to_string();
-        for arg in args.iter() {
-            first_part += " ";
-            first_part += arg;
-        }
-        let msg = format!(
-            "{} requires extra caution. Are you sure?",
-            first_part.stylize().color(ty.color()).bold()
-        );
-        let yes = super::prompt(msg, false)?;
-        if !yes {
-            return Err(Error::Caution);
-        }
+    // NOTE: 排程觸發的執行已在加入排程時確認過
+    if !entry.get_env().is_scheduled() {
+        confirm_caution(entry, &args)?;
     }
 
     let mut hs_env_desc = vec![];
@@ -373,6 +423,8 @@ pub async fn run_n_times(
     }
     EnvPair::sort(&mut env_vec);
     let env_record = serde_json::to_string(&env_vec)?;
+    // NOTE: 參數宣告產生的環境變數可以從參數推得，不必記錄
+    env_vec.extend(arg_envs.into_iter().map(|(key, val)| EnvPair { key, val }));
 
     let run_id = entry
         .update(|info| info.exec(content, &args, env_record, here))
@@ -398,22 +450,38 @@ pub async fn run_n_times(
     hs_tmpl_val.content = Some(unsafe { &*content });
     // End packing hs tmpl val
 
+    let log_path = if Config::get()
+        .log_tags
+        .select(&entry.tags, &entry.ty)
+        .is_true()
+    {
+        Some(path::get_run_log(run_id)?)
+    } else {
+        None
+    };
+
     let mut lock = ProcessLockWrite::new(run_id, entry.id, hs_name, &args)?;
     let guard = lock.try_write_info()?;
     for _ in 0..repeat {
-        let run_res = run(&script_path, &*entry, &args, &hs_tmpl_val, &env_vec);
-        let ret_code: i32;
-        match run_res {
-            Err(Error::ScriptError(code)) => {
-                ret_code = code;
-                res.push(run_res.unwrap_err());
+        let (code, usage) = run(
+            &script_path,
+            &*entry,
+            &args,
+            &hs_tmpl_val,
+            &env_vec,
+            log_path.as_deref(),
+        )?;
+        let ret_code = match code {
+            Some(code) => {
+                res.push(Error::ScriptError(code));
+                code
             }
-            Err(e) => return Err(e),
-            Ok(_) => ret_code = 0,
-        }
+            None => 0,
+        };
         entry
-            .update(|info| info.exec_done(ret_code, run_id))
+            .update(|info| info.exec_done(ret_code, run_id, usage))
             .await?;
+        crate::hook::run_hooks(entry, &hs_tmpl_val, run_id, ret_code, &usage).await?;
     }
     if res.is_empty() {
         ProcessLockWrite::mark_sucess(guard);
@@ -494,6 +562,15 @@ pub fn need_write(arg: &Subs) -> bool {
         CP { .. } => true,
         RM { .. } => true,
         LoadUtils { .. } => true,
+        Restore { .. } => true,
+        Import { .. } => true,
+        Collect { apply, interactive } => *apply || *interactive,
+        Vcs {
+            subcmd: args::Vcs::Restore { .. },
+        } => true,
+        Schedule {
+            subcmd: args::Schedule::Add { .. } | args::Schedule::RM { .. },
+        } => true,
         MV {
             ty,
             tags,
@@ -531,7 +608,8 @@ pub async fn after_script(
         }
     }
     if record_write {
-        entry.update(|info| info.write()).await?;
+        let content = super::read_file(path)?;
+        entry.update(|info| info.write(Some(content))).await?;
     }
     Ok(())
 }
@@ -584,3 +662,390 @@ pub fn get_all_active_process_locks() -> Result<Vec<ProcessLockRead>> {
 
     Ok(ret)
 }
+
+/// 取得腳本的某次執行，未指定時取最後一次
+pub async fn get_run_event(entry: &RepoEntry<'_>, run_id: Option<u64>) -> Result<ExecEvent> {
+    let historian = &entry.get_env().historian;
+    let run_id = match run_id {
+        Some(run_id) => run_id as i64,
+        None => match historian.exec_ids(Some(entry.id)).await?.first() {
+            Some(run_id) => *run_id,
+            None => return Err(Error::NeverRun(entry.name.key().to_string())),
+        },
+    };
+    match historian.exec_event(run_id).await? {
+        Some(event) if event.script_id == entry.id => Ok(event),
+        _ => Err(Error::NoRunEvent(run_id)),
+    }
+}
+
+/// 第 `n` 新的執行編號，1 即最後一次
+pub async fn get_previous_run_id(entry: &RepoEntry<'_>, n: usize) -> Result<u64> {
+    let historian = &entry.get_env().historian;
+    let run_ids = historian.exec_ids(Some(entry.id)).await?;
+    if run_ids.is_empty() {
+        return Err(Error::NeverRun(entry.name.key().to_string()));
+    }
+    match run_ids.get(n - 1) {
+        Some(run_id) => Ok(*run_id as u64),
+        None => Err(Error::NotEnoughRuns(
+            entry.name.key().to_string(),
+            run_ids.len(),
+        )),
+    }
+}
+
+/// 把腳本內容還原成某次執行當下的樣子。給定 `new_name` 則還原成一支新腳本，同 `cp`
+pub async fn restore(
+    script_repo: &mut ScriptRepo,
+    script_query: &ScriptQuery,
+    run_id: Option<u64>,
+    previous: Option<usize>,
+    force: bool,
+    new_name: Option<ScriptName>,
+) -> Result {
+    let mut entry = query::do_script_query_strict(script_query, script_repo).await?;
+    let run_id = match previous {
+        Some(n) => Some(get_previous_run_id(&entry, n).await?),
+        None => run_id,
+    };
+    let event = get_run_event(&entry, run_id).await?;
+    let content = get_run_content(&event)?;
+    log::info!("還原 {:?} 至執行 {} 的內容", entry.name, event.id);
+
+    if let Some(new_name) = new_name {
+        let new_info = entry.cp(new_name);
+        let new_path = path::open_script(&new_info.name, &entry.ty, Some(false))?;
+        // NOTE: 創建資料夾
+        if let Some(parent) = new_path.parent() {
+            super::handle_fs_res(&[&new_path], create_dir_all(parent))?;
+        }
+        super::write_file(&new_path, content)?;
+        let mut new_entry = script_repo
+            .entry(&new_info.name)
+            .or_insert(new_info)
+            .await?;
+        let content = content.to_owned();
+        new_entry.update(|info| info.write(Some(content))).await?;
+        return Ok(());
+    }
+
+    let script_path = path::open_script(&entry.name, &entry.ty, Some(true))?;
+    let cur_content = super::read_file(&script_path)?;
+    if cur_content == content {
+        log::info!("內容相同，不必還原");
+        return Ok(());
+    }
+    if !force {
+        let historian = &entry.get_env().historian;
+        let latest = historian.latest_content(entry.id).await?;
+        if latest.as_deref() != Some(cur_content.as_str()) {
+            log::warn!("現有內容未被任何執行或寫入記錄下來");
+            return Err(Error::UncommittedChanges(entry.name.key().to_string()));
+        }
+    }
+    super::write_file(&script_path, content)?;
+    let content = content.to_owned();
+    entry.update(|info| info.write(Some(content))).await?;
+    Ok(())
+}
+
+/// 取得某次執行當下的腳本內容
+pub fn get_run_content(event: &ExecEvent) -> Result<&str> {
+    match &event.content {
+        Some(content) => Ok(content),
+        None => Err(Error::NoRunContent(event.id)),
+    }
+}
+
+fn is_running(run_id: i64) -> Result<bool> {
+    let lock_path = path::get_process_lock(run_id)?;
+    if !lock_path.exists() {
+        return Ok(false);
+    }
+    let mut builder = ProcessLockRead::builder(lock_path, &run_id.to_string())?;
+    Ok(!builder.get_can_write()?)
+}
+
+/// 印出某次執行的輸出紀錄。若 `follow` 則持續印出新的輸出，直到該次執行結束
+pub fn print_run_log(run_id: i64, follow: bool) -> Result {
+    let log_path = path::get_run_log(run_id)?;
+    let mut file = super::handle_fs_res(&[&log_path], File::open(&log_path))?;
+    let mut stdout = std::io::stdout();
+    loop {
+        // NOTE: 先檢查再讀取，才不會漏掉結束前最後寫入的輸出
+        let running = follow && is_running(run_id)?;
+        std::io::copy(&mut file, &mut stdout)?;
+        stdout.flush()?;
+        if !running {
+            return Ok(());
+        }
+        std::thread::sleep(FOLLOW_INTERVAL);
+    }
+}
+
+/// 刪除執行事件已不存在的輸出紀錄
+pub fn tidy_run_logs(existing_run_ids: &HashSet<i64>) -> Result {
+    let dir_path = path::get_run_log_dir()?;
+    let dir = super::handle_fs_res(&[&dir_path], read_dir(&dir_path))?;
+    for entry in dir {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let run_id = file_name
+            .to_str()
+            .and_then(|s| s.strip_suffix(".log"))
+            .and_then(|s| s.parse::<i64>().ok());
+        match run_id {
+            Some(run_id) if existing_run_ids.contains(&run_id) => (),
+            Some(_) => {
+                log::info!("刪除過期的執行紀錄 {:?}", file_name);
+                super::remove(&entry.path())?;
+            }
+            None => log::warn!("執行紀錄資料夾中有不明檔案 {:?}", file_name),
+        }
+    }
+    Ok(())
+}
+
+fn check_time_changed(entry: &RepoEntry<'_>, ignrore_res: &LastTimeRecord) -> bool {
+    let s_exec_time = entry.exec_time.as_ref().map(|t| **t);
+    let s_exec_done_time = entry.exec_done_time.as_ref().map(|t| **t);
+    (s_exec_time, s_exec_done_time, entry.humble_time)
+        != (
+            ignrore_res.exec_time,
+            ignrore_res.exec_done_time,
+            ignrore_res.humble_time,
+        )
+}
+
+/// 忽略歷史後，把史學家算出的新時間寫回腳本
+pub async fn update_last_times(
+    scripts: &mut [RepoEntry<'_>],
+    res_vec: Vec<LastTimeRecord>,
+) -> Result {
+    // TODO: 測試多個腳本的狀況
+    for (entry, res) in scripts.iter_mut().zip(res_vec) {
+        // TODO: 平行？
+        if check_time_changed(entry, &res) {
+            log::debug!(
+                "刪除後時間不同 {:?} {:?} {:?} v.s. {:?}",
+                entry.exec_time,
+                entry.exec_done_time,
+                entry.humble_time,
+                res
+            );
+            entry
+                .update(|info| {
+                    info.exec_time = res.exec_time.map(ScriptTime::new);
+                    info.exec_done_time = res.exec_done_time.map(ScriptTime::new);
+                    info.humble_time = res.humble_time;
+                })
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// 歷史瀏覽器中的一筆：某組參數與環境變數最新一次的執行
+pub struct BrowseRecord {
+    pub event_id: i64,
+    pub script_id: i64,
+    pub args: Vec<String>,
+    pub envs: Vec<EnvPair>,
+}
+
+/// 歷史瀏覽器的所有紀錄，連同查詢的條件，刪除時才能以同樣的條件找到紀錄
+pub struct BrowseList {
+    pub records: Vec<BrowseRecord>,
+    dir: Option<PathBuf>,
+    no_humble: bool,
+}
+
+pub async fn load_browse_records(
+    historian: &Historian,
+    ids: &[i64],
+    limit: u32,
+    no_humble: bool,
+    dir: Option<PathBuf>,
+) -> Result<BrowseList> {
+    let list = historian
+        .previous_args_list_with_envs(ids, limit, 0, no_humble, dir.as_deref())
+        .await?;
+    let mut records = Vec::with_capacity(list.len());
+    for (event_id, _, script_id, args, envs) in list {
+        records.push(BrowseRecord {
+            event_id,
+            script_id,
+            args: serde_json::from_str(&args).context(format!("反序列失敗 {}", args))?,
+            envs: serde_json::from_str(&envs).context(format!("反序列失敗 {}", envs))?,
+        });
+    }
+    Ok(BrowseList {
+        records,
+        dir,
+        no_humble,
+    })
+}
+
+fn find_script<'a, 'b>(scripts: &'a mut [RepoEntry<'b>], id: i64) -> &'a mut RepoEntry<'b> {
+    // NOTE: 史學家只會給出查詢到的腳本的歷史
+    scripts.iter_mut().find(|s| s.id == id).unwrap()
+}
+
+fn fmt_args(args: &[String]) -> String {
+    let args: Vec<_> = args.iter().map(|s| to_display_args(s)).collect();
+    args.join(" ")
+}
+
+/// 重現這筆歷史的命令行，例如 `VAR=1 hs =name! -- arg`
+fn browse_cmd_line(name: &ScriptName, record: &BrowseRecord) -> String {
+    let mut line = String::new();
+    for env in record.envs.iter() {
+        line += &to_display_args(&env.to_string());
+        line += " ";
+    }
+    line += &format!("{} ={}! --", APP_NAME, name.key());
+    if !record.args.is_empty() {
+        line += " ";
+        line += &fmt_args(&record.args);
+    }
+    line
+}
+
+/// 以選單挑選歷史，回傳以哪個動作確認的及被挑選的索引。使用者取消則回傳 `None`
+pub fn pick_browse_records(
+    scripts: &[RepoEntry<'_>],
+    records: &[BrowseRecord],
+    default_action: BrowseAction,
+) -> Result<Option<(BrowseAction, Vec<usize>)>> {
+    const BINDINGS: &[(char, &str, BrowseAction)] = &[
+        ('\x05', "amend", BrowseAction::Amend),
+        ('\x0f', "humble", BrowseAction::Humble),
+        ('\x04', "rm", BrowseAction::RM),
+        ('\x19', "copy", BrowseAction::Copy),
+    ];
+    let with_name = scripts.len() > 1;
+    let keys = records
+        .iter()
+        .map(|r| {
+            let mut line = String::new();
+            if with_name {
+                let script = scripts.iter().find(|s| s.id == r.script_id).unwrap();
+                line += &format!("({}) ", script.name.key());
+            }
+            if !r.envs.is_empty() {
+                let envs: Vec<_> = r.envs.iter().map(|e| e.to_string()).collect();
+                line += &format!("({}) ", envs.join(" "));
+            }
+            line + &fmt_args(&r.args)
+        })
+        .collect();
+    let mut picker = Picker::new(keys, SEP).multi(true);
+    for (key, label, _) in BINDINGS.iter() {
+        picker = picker.bind(*key, *label);
+    }
+    let picked = picker.pick_with_binding()?;
+    Ok(picked.map(|p| {
+        let action = match p.binding {
+            Some(i) => BINDINGS[i].2,
+            None => default_action,
+        };
+        (action, p.indices)
+    }))
+}
+
+fn prompt_amend_args(name: &ScriptName, record: &BrowseRecord) -> Result<Vec<String>> {
+    use console::Term;
+    let term = Term::stderr();
+    term.write_str(&format!("{}> ", name.key()))?;
+    let line = term.read_line_initial_text(&fmt_args(&record.args))?;
+    term.write_line("")?;
+    shlex::split(&line).ok_or_else(|| Error::msg(format!("無法解析參數：{}", line)))
+}
+
+/// 對挑選出的歷史執行動作。`picked` 為 `list.records` 中的索引
+pub async fn browse_history(
+    scripts: &mut [RepoEntry<'_>],
+    list: &BrowseList,
+    picked: Vec<usize>,
+    action: BrowseAction,
+    amend_args: Vec<String>,
+    res: &mut Vec<Error>,
+) -> Result {
+    log::info!("對歷史 {:?} 執行 {:?}", picked, action);
+    let records = &list.records;
+    match action {
+        BrowseAction::Run => {
+            for i in picked.into_iter() {
+                let record = &records[i];
+                let entry = find_script(scripts, record.script_id);
+                let (args, envs) = (record.args.clone(), record.envs.clone());
+                let opts = RunOptions {
+                    env_overrides: envs,
+                    ..Default::default()
+                };
+                run_n_times(entry, args, res, opts).await?;
+            }
+        }
+        BrowseAction::Amend => {
+            for i in picked.into_iter() {
+                let record = &records[i];
+                let entry = find_script(scripts, record.script_id);
+                let args = if amend_args.is_empty() {
+                    prompt_amend_args(&entry.name, record)?
+                } else {
+                    amend_args.clone()
+                };
+                let args = serde_json::to_string(&args)?;
+                // NOTE: 事件 id 必不為零
+                let event_id = (record.event_id as u64).try_into().unwrap();
+                let historian = &entry.get_env().historian;
+                historian.amend_args_by_id(event_id, &args, None).await?;
+            }
+        }
+        BrowseAction::Humble => {
+            for i in picked.into_iter() {
+                let record = &records[i];
+                let entry = find_script(scripts, record.script_id);
+                let event_id = (record.event_id as u64).try_into().unwrap();
+                let env = entry.get_env();
+                if let Some(last) = env.historian.humble_args_by_id(event_id).await? {
+                    env.update_last_time_directly(last).await?;
+                }
+            }
+        }
+        BrowseAction::RM => {
+            // NOTE: 有歷史就必有腳本
+            let historian = scripts[0].get_env().historian.clone();
+            let ids: Vec<_> = scripts.iter().map(|s| s.id).collect();
+            let mut picked = picked;
+            // NOTE: 由後往前刪，前面的位置才不會跑掉
+            picked.sort_unstable_by(|a, b| b.cmp(a));
+            let mut res_vec = vec![];
+            for i in picked.into_iter() {
+                let min = NonZeroU64::new(i as u64 + 1).unwrap();
+                let max = NonZeroU64::new(i as u64 + 2);
+                res_vec = historian
+                    .ignore_args_range(
+                        &ids,
+                        list.dir.as_deref(),
+                        list.no_humble,
+                        true,
+                        true,
+                        min,
+                        max,
+                    )
+                    .await?;
+            }
+            update_last_times(scripts, res_vec).await?;
+        }
+        BrowseAction::Copy => {
+            for i in picked.into_iter() {
+                let record = &records[i];
+                let entry = find_script(scripts, record.script_id);
+                println!("{}", browse_cmd_line(&entry.name, record));
+            }
+        }
+    }
+    Ok(())
+}
@@ -6,18 +6,22 @@ use crate::script::ScriptInfo;
 use crate::script_type::{get_default_template, ScriptFullType, ScriptType};
 use ::serde::Serialize;
 use chrono::{DateTime, Utc};
+use hyper_scripter_historian::ExecUsage;
 use shlex::Shlex;
 use std::borrow::Cow;
 use std::ffi::OsStr;
-use std::fs::{create_dir_all, remove_file, rename, File};
+use std::fs::{create_dir_all, remove_file, rename, File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Instant;
 
 pub mod completion_util;
 pub mod holder;
 pub mod main_util;
 pub mod shebang_handle;
+mod tee;
+mod wait;
 pub mod writable;
 
 pub mod init_repo;
@@ -41,16 +45,44 @@ pub fn illegal_name(s: &str) -> bool {
         || s.is_empty()
 }
 
-pub fn run_cmd(mut cmd: Command) -> Result<Option<i32>> {
+pub fn run_cmd(cmd: Command) -> Result<Option<i32>> {
+    run_cmd_with_usage(cmd, None).map(|(code, _)| code)
+}
+/// 同 `run_cmd`，另外回傳子程序的耗時與資源用量。
+/// 若子程序被信號終止，回傳值比照 shell 的慣例為 128 + 信號。
+/// 若給定 `log`，子程序的輸出會另外附加到該檔案中
+pub fn run_cmd_with_usage(
+    mut cmd: Command,
+    log: Option<&Path>,
+) -> Result<(Option<i32>, ExecUsage)> {
     log::debug!("執行命令 {:?}", cmd);
+    let log_file = if let Some(log) = log {
+        log::info!("將輸出記錄至 {:?}", log);
+        let file = OpenOptions::new().create(true).append(true).open(log);
+        tee::Tee::pipe(&mut cmd);
+        Some(handle_fs_res(&[log], file)?)
+    } else {
+        None
+    };
+    let start = Instant::now();
     let res = cmd.spawn();
     let program = cmd.get_program();
     let mut child = handle_fs_res(&[program], res)?;
-    let stat = handle_fs_res(&[program], child.wait())?;
+    let tee = log_file.map(|f| tee::Tee::start(&mut child, f));
+    let res = wait::wait_with_usage(&mut child, start);
+    if let Some(tee) = tee {
+        tee.join();
+    }
+    let (stat, usage) = handle_fs_res(&[program], res)?;
     if stat.success() {
-        Ok(None)
+        Ok((None, usage))
     } else {
-        Ok(Some(stat.code().unwrap_or_default()))
+        let code = match (stat.code(), usage.signal) {
+            (Some(code), _) => code,
+            (None, Some(signal)) => 128 + signal,
+            (None, None) => 0,
+        };
+        Ok((Some(code), usage))
     }
 }
 #[cfg(not(target_os = "linux"))]
@@ -464,4 +496,25 @@ impl<'a> TmplVal<'a> {
             content: None,
         }
     }
+    /// 不在執行中的腳本，沒有執行編號及內容，例如用來產生 systemd 單元檔
+    pub fn with_script(name: &'a str, tags: Vec<&'a str>, path: &'a Path) -> Self {
+        TmplVal {
+            path: Some(path),
+            tags,
+            name: Some(name),
+            ..Self::new()
+        }
+    }
+    /// 每個欄位都有值的假資料，用來檢查模板能否渲染
+    pub fn dummy() -> Self {
+        TmplVal {
+            path: Some(Path::new("dummy")),
+            run_id: Some(0),
+            tags: vec!["dummy"],
+            env_desc: vec!["VAR dummy".to_owned()],
+            name: Some("dummy"),
+            content: Some("dummy"),
+            ..Self::new()
+        }
+    }
 }
@@ -18,7 +18,7 @@ macro_rules! impl_de_by_from_str {
             where
                 D: serde::Deserializer<'de>,
             {
-                let s: &str = serde::Deserialize::deserialize(deserializer)?;
+                let s: String = serde::Deserialize::deserialize(deserializer)?;
                 s.parse().map_err(serde::de::Error::custom)
             }
         }
@@ -0,0 +1,69 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+const BUF_SIZE: usize = 4096;
+
+/// 把子程序的標準輸出和標準錯誤原樣轉印到終端機，同時寫進紀錄檔。
+/// NOTE: 子程序的輸出不再是終端機，有些程式會因此關掉顏色或改變緩衝行為
+pub struct Tee {
+    handles: Vec<JoinHandle<()>>,
+}
+
+fn copy_thread<R, W>(mut from: R, mut to: W, log: Arc<Mutex<File>>) -> JoinHandle<()>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let mut buf = [0; BUF_SIZE];
+        loop {
+            let n = match from.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    log::warn!("讀取子程序輸出失敗：{}", e);
+                    break;
+                }
+            };
+            let data = &buf[..n];
+            if let Err(e) = to.write_all(data).and_then(|_| to.flush()) {
+                log::warn!("轉印子程序輸出失敗：{}", e);
+            }
+            let mut log = log.lock().unwrap();
+            if let Err(e) = log.write_all(data) {
+                log::warn!("寫入執行紀錄失敗：{}", e);
+            }
+        }
+    })
+}
+
+impl Tee {
+    /// 須在 `spawn` 之前呼叫
+    pub fn pipe(cmd: &mut Command) {
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+    }
+    pub fn start(child: &mut Child, log: File) -> Self {
+        let log = Arc::new(Mutex::new(log));
+        let mut handles = vec![];
+        if let Some(stdout) = child.stdout.take() {
+            handles.push(copy_thread(stdout, std::io::stdout(), log.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            handles.push(copy_thread(stderr, std::io::stderr(), log));
+        }
+        Tee { handles }
+    }
+    /// 等到輸出都被讀完為止。若子程序的子程序仍握有管道，會一直等到它們也結束
+    pub fn join(self) {
+        for handle in self.handles.into_iter() {
+            if handle.join().is_err() {
+                log::warn!("轉印執行緒崩潰");
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use hyper_scripter_historian::ExecUsage;
+use std::io::Result;
+use std::process::{Child, ExitStatus};
+use std::time::Instant;
+
+/// 等待子程序結束，並盡可能取得其資源用量。`start` 為子程序開始的時間
+#[cfg(unix)]
+pub fn wait_with_usage(child: &mut Child, start: Instant) -> Result<(ExitStatus, ExecUsage)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid = child.id() as libc::pid_t;
+    let mut status: libc::c_int = 0;
+    // SAFETY: rusage 是純資料結構，全零是合法的值
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: 傳入的指標皆指向存活的區域變數
+        let res = unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) };
+        if res == pid {
+            break;
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+        log::debug!("wait4 被信號中斷，重試");
+    }
+    let elapsed_ms = start.elapsed().as_millis() as i64;
+    let status = ExitStatus::from_raw(status);
+
+    // NOTE: time_t 和 suseconds_t 的大小依平台而異
+    #[allow(clippy::unnecessary_cast)]
+    fn to_ms(t: libc::timeval) -> i64 {
+        t.tv_sec as i64 * 1000 + t.tv_usec as i64 / 1000
+    }
+    // NOTE: macOS 上的單位是位元組，linux 上則是 KB
+    #[cfg(target_os = "macos")]
+    let max_rss_kb = rusage.ru_maxrss as i64 / 1024;
+    #[cfg(not(target_os = "macos"))]
+    let max_rss_kb = rusage.ru_maxrss as i64;
+
+    let usage = ExecUsage {
+        elapsed_ms,
+        signal: status.signal(),
+        user_time_ms: Some(to_ms(rusage.ru_utime)),
+        sys_time_ms: Some(to_ms(rusage.ru_stime)),
+        max_rss_kb: Some(max_rss_kb),
+    };
+    Ok((status, usage))
+}
+
+#[cfg(not(unix))]
+pub fn wait_with_usage(child: &mut Child, start: Instant) -> Result<(ExitStatus, ExecUsage)> {
+    let status = child.wait()?;
+    let usage = ExecUsage {
+        elapsed_ms: start.elapsed().as_millis() as i64,
+        ..Default::default()
+    };
+    Ok((status, usage))
+}
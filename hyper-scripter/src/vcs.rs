@@ -0,0 +1,181 @@
+//! 以 git 記錄腳本之家的版本。資料庫會先匯出成 `.script_info.sql` 再提交，
+//! 所有操作都只在本地進行，不需要遠端倉庫
+
+use crate::collect;
+use crate::db;
+use crate::error::{Error, Result};
+use crate::path;
+use crate::script::ANONYMOUS;
+use crate::util;
+use std::path::Path;
+use std::process::Command;
+
+const GIT_IGNORE: &str = ".gitignore";
+const IGNORED: &[&str] = &[
+    ".script_info.db",
+    ".script_info.db-journal",
+    ".script_history.db",
+    ".script_history.db-journal",
+    ".hs_process_lock/",
+    ".hs_run_logs/",
+];
+
+fn git_cmd(args: &[&str]) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(path::get_home()).args(args);
+    cmd
+}
+
+fn to_err(code: Option<i32>, args: &[&str]) -> Error {
+    let mut cmd = vec!["git".to_owned()];
+    cmd.extend(args.iter().map(|s| s.to_string()));
+    Error::CommandError(code.unwrap_or(-1), cmd)
+}
+
+/// 執行 git 並回傳其標準輸出
+fn git_output(args: &[&str]) -> Result<String> {
+    log::debug!("執行 git {:?}", args);
+    let output = git_cmd(args).output()?;
+    if !output.status.success() {
+        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        return Err(to_err(output.status.code(), args));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// 執行 git，輸出直接印到終端機
+fn git(args: &[&str]) -> Result {
+    log::debug!("執行 git {:?}", args);
+    let status = git_cmd(args).status()?;
+    if !status.success() {
+        return Err(to_err(status.code(), args));
+    }
+    Ok(())
+}
+
+fn git_ok(args: &[&str]) -> bool {
+    git_cmd(args)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// 若腳本之家不在 git 倉庫中，或被外層倉庫忽略了，就在腳本之家建立新的倉庫
+fn ensure_repo() -> Result {
+    if git_ok(&["rev-parse", "--is-inside-work-tree"]) && !git_ok(&["check-ignore", "-q", "."]) {
+        return Ok(());
+    }
+    log::info!("在腳本之家建立 git 倉庫");
+    git_output(&["init", "-q"])?;
+    let ignore = path::get_home().join(GIT_IGNORE);
+    if !ignore.exists() {
+        let mut content = IGNORED.join("\n");
+        content.push('\n');
+        util::write_file(&ignore, &content)?;
+    }
+    Ok(())
+}
+
+/// 由相對於腳本之家的路徑推回腳本名，不像腳本的檔案回傳 `None`
+fn script_name_of(file: &str) -> Option<String> {
+    if file.starts_with('.') && !file.starts_with(ANONYMOUS) {
+        return None;
+    }
+    let file = Path::new(file);
+    match collect::identify(file) {
+        Some((name, _)) => Some(name.to_string()),
+        None => Some(file.with_extension("").to_string_lossy().into_owned()),
+    }
+}
+
+/// 由暫存區的改動產生提交訊息，例如 `[hs] Edit a, b; Remove c`
+fn gen_message() -> Result<Option<String>> {
+    let diff = git_output(&["diff", "--cached", "--name-status", "-M", "--relative"])?;
+    if diff.trim().is_empty() {
+        return Ok(None);
+    }
+    let mut groups: Vec<(&str, Vec<String>)> = vec![];
+    let mut push = |action: &'static str, name: String| {
+        match groups.iter_mut().find(|(a, _)| *a == action) {
+            Some((_, names)) => names.push(name),
+            None => groups.push((action, vec![name])),
+        };
+    };
+    for line in diff.lines() {
+        let mut fields = line.split('\t');
+        let status = fields.next().unwrap_or_default();
+        let file = fields.next().unwrap_or_default();
+        let name = match script_name_of(file) {
+            Some(name) => name,
+            None => continue,
+        };
+        match status.chars().next() {
+            Some('A') => push("Add", name),
+            Some('D') => push("Remove", name),
+            Some('R') => {
+                let new_name = fields.next().and_then(script_name_of).unwrap_or_default();
+                push("Move", format!("{} -> {}", name, new_name));
+            }
+            _ => push("Edit", name),
+        }
+    }
+    let msg = if groups.is_empty() {
+        "Update script info".to_owned()
+    } else {
+        groups
+            .into_iter()
+            .map(|(action, names)| format!("{} {}", action, names.join(", ")))
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+    Ok(Some(format!("[hs] {}", msg)))
+}
+
+/// 提交腳本之家的所有改動。沒有改動就什麼都不做
+pub async fn commit(message: Option<&str>) -> Result {
+    ensure_repo()?;
+    db::dump_sql().await?;
+    git_output(&["add", "-A", "."])?;
+    let msg = match (gen_message()?, message) {
+        (None, _) => {
+            log::info!("沒有改動，不提交");
+            return Ok(());
+        }
+        (Some(_), Some(msg)) => msg.to_owned(),
+        (Some(msg), None) => msg,
+    };
+    log::info!("提交：{}", msg);
+    // NOTE: 指定路徑，以免提交到外層倉庫中其它暫存的改動
+    git_output(&["commit", "-q", "-m", &msg, "--", "."])?;
+    Ok(())
+}
+
+/// 列出腳本之家的提交。給定 `file` 時只列出和它有關者
+pub fn log(file: Option<&Path>, limit: usize) -> Result {
+    let limit = format!("--max-count={}", limit);
+    let mut args = vec!["log", "--format=%h %ad %s", "--date=short", &limit];
+    let file = file.map(|f| f.to_string_lossy());
+    match &file {
+        Some(file) => args.extend(["--follow", "--", file.as_ref()]),
+        None => args.extend(["--", "."]),
+    }
+    git(&args)
+}
+
+/// 將 `file` 還原到 `commit` 時的樣子。未給定 `file` 時還原整個腳本之家，並重建資料庫
+pub async fn restore(commit: &str, file: Option<&Path>) -> Result {
+    let source = format!("--source={}", commit);
+    match file {
+        Some(file) => {
+            let file = file.to_string_lossy();
+            git_output(&["restore", &source, "--", file.as_ref()])?;
+        }
+        None => {
+            git_output(&["restore", &source, "--staged", "--worktree", "--", "."])?;
+            db::rebuild_from_sql().await?;
+        }
+    }
+    Ok(())
+}
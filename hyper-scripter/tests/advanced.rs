@@ -4,6 +4,7 @@ mod tool;
 pub use tool::*;
 
 use hyper_scripter::{
+    path::normalize_path,
     script_type::ScriptFullType,
     util::{remove, write_file},
 };
@@ -246,6 +247,234 @@ fn test_shell_alias() {
     ); // run the script without hs, should not have env variables
 }
 
+#[test]
+fn test_alias_params() {
+    let _g = setup();
+    run!("e -T sh =print-args | echo \"$@\"").unwrap();
+
+    run!("alias pa print-args --stage '${{1:-dev}}' '--out=$2'").unwrap();
+    assert_eq!(
+        "--stage prod --out=src extra",
+        run!("pa prod src extra").unwrap()
+    );
+    assert_eq!("--stage dev --out=", run!("pa").unwrap());
+    run!("alias all print-args first '$@' last").unwrap();
+    assert_eq!("first a b last", run!("all a b").unwrap());
+
+    run!("alias nested pa stage").unwrap();
+    assert_eq!("--stage stage --out=x y", run!("nested x y").unwrap());
+    assert_eq!(
+        "print-args --stage stage --out=x y",
+        run!("alias --explain nested x y").unwrap()
+    );
+
+    run!("alias sh-args '!echo [$2]'").unwrap();
+    assert_eq!("[b] c", run!("sh-args a b c").unwrap());
+    assert_eq!(
+        "sh -c 'echo [b] c'",
+        run!("alias --explain sh-args a b c").unwrap()
+    );
+
+    run!("alias loop-a loop-b").unwrap();
+    run!("alias loop-b loop-a x").unwrap();
+    run!("loop-a").expect_err("別名繞成圈");
+    run!("alias --explain loop-b").expect_err("別名繞成圈");
+    run!("alias --explain no-such-alias").expect_err("沒有這個別名");
+}
+
+#[test]
+fn test_arg_schema() {
+    let _g = setup();
+    const CONTENT: &str = "
+    # [HS_HELP]: deploy things
+    # [HS_ARG]: stage: dev|prod = dev -> Where to deploy
+    # [HS_ARG]: count?: int
+    # [HS_FLAG]: -n, --dry-run -> Print only
+    # [HS_FLAG]: --region!: str
+    echo $HS_ARG_STAGE:$HS_ARG_COUNT:$HS_ARG_DRY_RUN:$HS_ARG_REGION";
+    ScriptTest::new("deploy", None, Some(CONTENT));
+
+    assert_eq!("dev:::us", run!("deploy --region us").unwrap());
+    assert_eq!("prod:3:1:eu", run!("deploy -n prod --region=eu 3").unwrap());
+    run!("deploy qa --region us").expect_err("不在選項中");
+    run!("deploy prod x --region us").expect_err("不是整數");
+    run!("deploy prod").expect_err("缺少必填旗標");
+    run!("deploy --force --region us").expect_err("未宣告的旗標");
+    run!("deploy prod 1 2 --region us").expect_err("多餘的參數");
+    // 參數錯誤時不該留下執行紀錄
+    assert_eq!("prod:3:1:eu", run!("run -p deploy").unwrap());
+
+    let help = run!("help deploy").unwrap();
+    assert!(help.contains("deploy things\n\nUSAGE:"), "{}", help);
+    assert!(
+        help.contains("hs deploy --region <str> [OPTIONS] [stage] [count]"),
+        "{}",
+        help
+    );
+    assert!(
+        help.contains("Where to deploy [default: dev] [possible values: dev, prod]"),
+        "{}",
+        help
+    );
+
+    run!("e -T sh =bad-schema | # [HS_ARG]: count: integer").unwrap();
+    run!("bad-schema").expect_err("宣告格式錯誤");
+}
+
+#[test]
+fn test_schedule() {
+    let _g = setup();
+    ScriptTest::new("tick", None, Some("echo tick $@"));
+
+    run!("schedule add --cron '*/5 * * * *' tick -- a b").unwrap();
+    run!("schedule add --cron '61 * * * *' tick").expect_err("cron 格式錯誤");
+    let ls = run!("schedule ls").unwrap();
+    assert!(ls.starts_with("1\t*/5 * * * *\t"), "{}", ls);
+    assert!(ls.ends_with("\ttick a b"), "{}", ls);
+    assert_eq!("", run!("scheduler --once").unwrap(), "還沒到期");
+
+    run!("run --at 0s tick x").unwrap();
+    run!("run --at 0s --repeat 2 tick").expect_err("排程不能重複執行");
+    run!("run --at 0s -p --dir . tick").expect_err("排程不記錄執行目錄");
+    assert_eq!(2, run!("schedule ls").unwrap().lines().count());
+    assert_eq!("tick x", run!("scheduler --once").unwrap());
+    assert_eq!(
+        "",
+        run!("scheduler --once").unwrap(),
+        "單次排程執行後就刪除"
+    );
+    assert_eq!(1, run!("schedule ls").unwrap().lines().count());
+
+    let stats = run!("stats").unwrap();
+    assert!(stats.contains(", 1 scheduled"), "{}", stats);
+    // 排程觸發的執行也能被 --previous 沿用
+    assert_eq!("tick x", run!("run -p tick").unwrap());
+
+    // 被時間篩選藏起來的腳本照樣依排程執行
+    run!("run --at 0s tick y").unwrap();
+    run!("history neglect tick").unwrap();
+    let ls = run!("schedule ls").unwrap();
+    assert!(ls.lines().any(|l| l.ends_with("\ttick y")), "{}", ls);
+    assert_eq!("tick y", run!("scheduler --once").unwrap());
+
+    run!("schedule rm 1").unwrap();
+    run!("schedule rm 1").expect_err("排程已刪除");
+    assert_eq!("", run!("schedule ls").unwrap());
+}
+
+#[test]
+fn test_export_units() {
+    let _g = setup();
+    let home = get_home();
+    let dir = home.join("units");
+    let dir_str = dir.to_string_lossy();
+    ScriptTest::new("backup", None, Some("echo backup $@"));
+    ScriptTest::new("other", None, None);
+
+    run!("schedule add --cron '0 9 * * mon-fri' backup -- --to 'my disk'").unwrap();
+    run!("run --at 1h other").unwrap();
+    run!("export-units --dir {} --check backup", dir_str).expect_err("還沒產生");
+    run!("export-units --dir {} backup", dir_str).unwrap();
+
+    let service = std::fs::read_to_string(dir.join("hs-backup-1.service")).unwrap();
+    let exe = normalize_path(get_exe()).unwrap();
+    let exec = format!(
+        "ExecStart={} --no-caution run =backup! --to \"my disk\"\n",
+        exe.to_string_lossy()
+    );
+    assert!(service.contains(&exec), "{}", service);
+    let home_env = format!(
+        "Environment=\"HYPER_SCRIPTER_HOME={}\"\n",
+        home.to_string_lossy()
+    );
+    assert!(service.contains(&home_env), "{}", service);
+    assert!(
+        service.contains("Environment=\"NAME=backup\"\n"),
+        "{}",
+        service
+    );
+    let timer = std::fs::read_to_string(dir.join("hs-backup-1.timer")).unwrap();
+    assert!(
+        timer.contains("OnCalendar=Mon..Fri *-*-* 09:00:00\n"),
+        "{}",
+        timer
+    );
+    assert!(!dir.join("hs-other-2.timer").exists(), "不在查詢中");
+
+    assert_eq!(
+        "",
+        run!("export-units --dir {} --check backup", dir_str).unwrap()
+    );
+    let timer_path = dir.join("hs-backup-1.timer");
+    let gone_path = dir.join("hs-gone-9.service");
+    write_file(&timer_path, "edited").unwrap();
+    write_file(&gone_path, "").unwrap();
+    run!("export-units --dir {} --check backup", dir_str).expect_err("單元檔有變");
+    let report = run!("export-units --dir {} backup", dir_str).unwrap();
+    assert_eq!(
+        format!(
+            "write     {}\norphaned  {} (schedule removed)",
+            timer_path.display(),
+            gone_path.display()
+        ),
+        report
+    );
+    assert!(gone_path.exists(), "不刪除使用者的檔案");
+}
+
+#[test]
+fn test_complete_script_args() {
+    let _g = setup();
+    const CONTENT: &str = "
+    # [HS_ARG]: stage: dev|staging|prod
+    # [HS_FLAG]: --region: str
+    # [HS_COMPLETE]: echo hook-$#-$1
+    echo $HS_ARG_STAGE";
+    ScriptTest::new("deploy", None, Some(CONTENT));
+    run!("deploy prod --region us").unwrap();
+    run!("deploy prod --region eu").unwrap();
+    run!("deploy dev --region eu").unwrap();
+
+    let complete = |words: &[&str]| -> Option<String> {
+        let home = get_home();
+        let out = std::process::Command::new(normalize_path(get_exe()).unwrap())
+            .args(["completion", "script-args", "--", "hs", "-H"])
+            .arg(home)
+            .args(words)
+            .output()
+            .unwrap();
+        if !out.status.success() {
+            return None;
+        }
+        let out = String::from_utf8(out.stdout).unwrap();
+        Some(out.lines().collect::<Vec<_>>().join(" "))
+    };
+
+    assert_eq!(
+        Some("dev staging prod hook-1-".to_owned()),
+        complete(&["deploy", ""])
+    );
+    assert_eq!(Some("prod".to_owned()), complete(&["run", "deploy", "p"]));
+    // 歷史參數依出現次數排序
+    assert_eq!(
+        Some("eu us hook-3-prod".to_owned()),
+        complete(&["deploy", "prod", "--region", ""])
+    );
+    assert_eq!(
+        Some("hook-2-prod".to_owned()),
+        complete(&["deploy", "prod", "h"])
+    );
+    // 同一組參數執行多次也要算進次數
+    run!("deploy prod --region us").unwrap();
+    run!("deploy prod --region us").unwrap();
+    assert_eq!(
+        Some("us eu hook-3-prod".to_owned()),
+        complete(&["deploy", "prod", "--region", ""])
+    );
+    assert_eq!(None, complete(&["dep"]), "還在輸入腳本名");
+    assert_eq!(None, complete(&["ls", ""]), "不是執行指令");
+}
+
 #[test]
 fn test_special_anonymous_query() {
     let _g = setup();
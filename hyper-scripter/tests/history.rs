@@ -14,6 +14,11 @@ fn assert_list(actual: &str, expected: &[&str]) {
     assert_eq!(expected, actual_v);
 }
 
+fn get_run_id(res: &str) -> u64 {
+    let line = res.lines().find(|l| l.starts_with("run-")).unwrap();
+    line["run-".len()..].parse().unwrap()
+}
+
 fn init_dir(s: &str) -> (String, String) {
     let tmp_dir = std::env::temp_dir();
     let p = tmp_dir.join(s);
@@ -730,3 +735,319 @@ fn test_multi_history() {
         h.ls();
     }
 }
+
+#[test]
+fn test_hooks() {
+    let _g = setup();
+    run!("config set hooks.post_run 'echo post:$HS_EXIT_CODE:$HS_NAME'").unwrap();
+    run!("config set types.sh.hooks.on_failure 'echo fail:$HS_HOOK; exit 3'").unwrap();
+    run!("config set tag_hooks.notify.on_success 'echo ok:${{HS_DURATION_MS:+ms}}; exit 7'")
+        .unwrap();
+    run!("e -T sh -t notify flaky | exit $1").unwrap();
+    run!("e -T sh other | echo other").unwrap();
+
+    assert_eq!("post:0:flaky\nok:ms", run!("flaky 0").unwrap());
+    let hooks = run!("history hooks flaky").unwrap();
+    let hooks: Vec<_> = hooks
+        .lines()
+        .map(|l| l.split('\t').collect::<Vec<_>>())
+        .collect();
+    assert_eq!(hooks.len(), 2, "{:?}", hooks);
+    assert_eq!(hooks[0][0], "post_run");
+    assert!(hooks[0][1].starts_with("exit 0, "), "{:?}", hooks);
+    assert_eq!(hooks[1][0], "on_success");
+    assert!(hooks[1][1].starts_with("exit 7, "), "{:?}", hooks);
+    assert_eq!(hooks[1][2], "echo ok:${HS_DURATION_MS:+ms}; exit 7");
+
+    run!("flaky 1").expect_err("腳本應該要執行失敗");
+    let hooks = run!("history hooks flaky").unwrap();
+    let hooks: Vec<_> = hooks
+        .lines()
+        .map(|l| l.split('\t').next().unwrap())
+        .collect();
+    assert_eq!(hooks, ["post_run", "on_failure"]);
+
+    // 鉤子的結果不影響腳本本身的紀錄
+    let res = run!("stats --format ndjson flaky").unwrap();
+    let stats: serde_json::Value = serde_json::from_str(&res).unwrap();
+    assert_eq!(stats["success"], 1);
+    assert_eq!(stats["failure"], 1);
+
+    assert_eq!(
+        "other\npost:0:other",
+        run!("other").unwrap(),
+        "沒有標籤的鉤子"
+    );
+    run!("--no-trace other").unwrap();
+    assert_eq!(1, run!("history hooks other").unwrap().lines().count());
+
+    run!("config set tag_hooks.notify.oops 'echo'").unwrap();
+    let out = std::process::Command::new(get_exe())
+        .args(["-H", get_home().to_str().unwrap(), "config", "check"])
+        .output()
+        .unwrap();
+    let out = String::from_utf8(out.stdout).unwrap();
+    assert!(
+        out.lines()
+            .any(|l| l.ends_with(": tag_hooks.notify.oops: unknown key")),
+        "{}",
+        out
+    );
+}
+
+#[test]
+fn test_stats() {
+    let _g = setup();
+    run!("e flaky | [ \"${{1:-}}\" = fail ] && exit 1; exit 0").unwrap();
+    run!("e idle | echo idle").unwrap();
+    run!("flaky ok").unwrap();
+    run!("flaky ok").unwrap();
+    run!("flaky fail").expect_err("腳本應該要執行失敗");
+    run!("flaky fail").expect_err("腳本應該要執行失敗");
+    run!("flaky").unwrap();
+
+    let res = run!("stats --format ndjson").unwrap();
+    let lines: Vec<serde_json::Value> = res
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 1, "沒執行過的腳本不該出現在統計中");
+    let stats = &lines[0];
+    assert_eq!(stats["name"], "flaky");
+    assert_eq!(stats["total_runs"], 5);
+    assert_eq!(stats["success"], 3);
+    assert_eq!(stats["failure"], 2);
+    assert_eq!(stats["success_rate"], 0.6);
+    assert_eq!(stats["top_args"][0]["count"], 2);
+    assert_eq!(stats["top_args"][2]["args"], serde_json::json!([]));
+
+    let res = run!("stats --since 1h flaky").unwrap();
+    assert!(
+        res.contains("runs: 5 (3 ok, 2 failed, 0 unfinished)"),
+        "{}",
+        res
+    );
+    let res = run!("stats --since 2999-01-01").unwrap();
+    assert_eq!(res, "");
+}
+
+#[test]
+fn test_exec_usage() {
+    let _g = setup();
+    run!("e -f sleepy | sleep 0.2; exit ${{1:-0}}").unwrap();
+    run!("sleepy").unwrap();
+    run!(allow_other_error: true, "sleepy 3").expect_err("腳本應該要執行失敗");
+    run!("run --repeat 2 sleepy 0").unwrap();
+
+    let res = run!("history show --usage --format json sleepy").unwrap();
+    let res: serde_json::Value = serde_json::from_str(&res).unwrap();
+    let records = res.as_array().unwrap();
+    assert_eq!(records.len(), 3);
+    for record in records.iter() {
+        let last_run = &record["last_run"];
+        let code = if record["args"] == serde_json::json!(["3"]) {
+            3
+        } else {
+            0
+        };
+        assert_eq!(last_run["code"], code, "{}", record);
+        assert!(
+            last_run["elapsed_ms"].as_i64().unwrap() >= 200,
+            "{}",
+            record
+        );
+        assert!(last_run["signal"].is_null());
+        #[cfg(unix)]
+        assert!(last_run["max_rss_kb"].as_i64().unwrap() > 0);
+    }
+    let res = run!("history show --usage sleepy").unwrap();
+    assert!(res.contains("(exit 3, "), "{}", res);
+
+    #[cfg(unix)]
+    {
+        run!("e -f killed | kill -9 $$").unwrap();
+        run!(allow_other_error: true, "killed").expect_err("腳本應該要被殺掉");
+        let res = run!("history show --usage killed").unwrap();
+        assert!(res.contains("(killed by signal 9, "), "{}", res);
+    }
+}
+
+#[test]
+fn test_run_log() {
+    let _g = setup();
+    run!("e -f -t log logged | echo run-$HS_RUN_ID; echo err-${{1:-}} >&2; exit ${{2:-0}}")
+        .unwrap();
+    run!("e -f quiet | echo quiet").unwrap();
+
+    let res = run!("logged a").unwrap();
+    let first_id = get_run_id(&res);
+    run!(allow_other_error: true, "logged b 3").expect_err("腳本應該要執行失敗");
+    run!("quiet").unwrap();
+
+    let res = run!("log logged").unwrap();
+    assert!(res.contains("err-b"), "{}", res);
+    assert!(!res.contains("err-a"), "{}", res);
+    run!("log quiet").expect_err("沒標 log 的腳本不該有紀錄");
+
+    let res = run!("log --run-id {} logged", first_id).unwrap();
+    assert!(res.contains("err-a"), "{}", res);
+    assert_eq!(get_run_id(&res), first_id);
+    run!("log --run-id {} quiet", first_id).expect_err("執行編號不屬於該腳本");
+
+    let res = run!("logged a").unwrap();
+    let second_id = get_run_id(&res);
+    run!("history tidy").unwrap();
+    run!("log --run-id {} logged", first_id).expect_err("整理後舊的紀錄應被刪除");
+    run!("log --run-id {} logged", second_id).unwrap();
+}
+
+#[test]
+fn test_env_override_and_diff() {
+    let _g = setup();
+    const CONTENT: &str = r#"
+    # [HS_ENV]: FOO
+    # [HS_ENV]: BAR
+    echo run-$HS_RUN_ID
+    echo "FOO=$FOO BAR=$BAR""#;
+    ScriptTest::new("env-diff", None, Some(CONTENT));
+
+    let custom_env = vec![
+        ("FOO".to_owned(), "1".to_owned()),
+        ("BAR".to_owned(), "x".to_owned()),
+    ];
+    let res = run!(custom_env: custom_env, "env-diff arg").unwrap();
+    let first = get_run_id(&res);
+
+    // 只覆蓋其中一個環境變數，其餘沿用上次的
+    let res = run!("run -p -e FOO=2 -e NEW=3 env-diff").unwrap();
+    assert!(res.contains("FOO=2 BAR=x"), "{}", res);
+    let second = get_run_id(&res);
+    let recorded = run!("history show --display=all env-diff").unwrap();
+    assert_list(
+        &recorded,
+        &["arg", "BAR=x", "FOO=2", "NEW=3", "arg", "BAR=x", "FOO=1"],
+    );
+
+    let diff = run!("history diff {} {}", first, second).unwrap();
+    assert_eq!(diff, "env:\n- FOO=1\n+ FOO=2\n+ NEW=3");
+
+    run!("e -f env-diff | echo changed").unwrap();
+    let res = run!("run -p env-diff other").unwrap();
+    let third = get_run_id(&res);
+    let diff = run!("history diff {} {}", second, third).unwrap();
+    assert!(diff.starts_with("args:\n- arg\n+ arg other\n"), "{}", diff);
+    assert!(!diff.contains("env:"), "{}", diff);
+    assert!(diff.contains("+echo changed"), "{}", diff);
+
+    run!("history diff {} 9999", first).expect_err("不存在的執行");
+}
+
+#[test]
+fn test_content_drift() {
+    let _g = setup();
+    run!("e -f drift | echo run-$HS_RUN_ID").unwrap();
+    run!("history content drift").expect_err("還沒執行過");
+
+    let first = get_run_id(&run!("drift").unwrap());
+    run!("e -f drift | echo v2").unwrap();
+    let second = get_run_id(&run!("drift").unwrap());
+    run!("e -f drift | echo v3").unwrap();
+
+    let content = run!("history content drift").unwrap();
+    assert!(content.ends_with("echo v2"), "{}", content);
+    let content = run!("history content --run-id {} drift", first).unwrap();
+    assert!(!content.contains("echo v2"), "{}", content);
+
+    let diff = run!("diff drift").unwrap();
+    let expected = format!("--- drift (run {})\n+++ drift (current)\n", second);
+    assert!(diff.starts_with(&expected), "{}", diff);
+    assert!(diff.ends_with("\n echo v2\n+echo v3"), "{}", diff);
+
+    let diff = run!("diff --from-run {} --to-run {} drift", first, second).unwrap();
+    assert!(diff.ends_with("\n+echo v2"), "{}", diff);
+    assert!(!diff.contains("v3"), "{}", diff);
+
+    run!("e -f other | echo other").unwrap();
+    run!("history content --run-id {} other", first).expect_err("不是這個腳本的執行");
+}
+
+#[test]
+fn test_restore() {
+    let _g = setup();
+    run!("e -f res | echo v1").unwrap();
+    run!("restore res").expect_err("還沒執行過");
+    run!("res").unwrap();
+    run!("e -f res | echo v2").unwrap();
+    run!("res").unwrap();
+
+    run!("restore --previous 2 res").unwrap();
+    let content = run!("cat res").unwrap();
+    assert!(content.ends_with("echo v1"), "{}", content);
+    run!("restore --previous 3 res").expect_err("只執行過兩次");
+
+    // 寫入事件也會記下內容，還原過或以 hs 編輯過的腳本可以直接再還原
+    run!("restore res").unwrap();
+    let content = run!("cat res").unwrap();
+    assert!(content.ends_with("echo v1\necho v2"), "{}", content);
+    run!("e -f res | echo v3").unwrap();
+    run!("restore --previous 2 res").unwrap();
+
+    // 在 hs 之外的修改沒被記錄下來，不該被默默蓋掉
+    let path = get_home().join("res.sh");
+    let content = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, format!("{}\necho v4", content)).unwrap();
+    run!("restore res").expect_err("有未記錄的修改");
+    let content = run!("cat res").unwrap();
+    assert!(content.ends_with("echo v4"), "{}", content);
+    run!("restore --force res").unwrap();
+    let content = run!("cat res").unwrap();
+    assert!(content.ends_with("echo v1\necho v2"), "{}", content);
+
+    run!("restore --previous 2 --to res-old res").unwrap();
+    let content = run!("cat =res-old").unwrap();
+    assert!(content.ends_with("echo v1"), "{}", content);
+    assert_ls(vec!["res-old", "res"], None, None);
+    run!("restore --previous 2 --to res-old res").expect_err("新腳本已存在");
+}
+
+#[test]
+fn test_history_browse() {
+    let _g = setup();
+    run!("e -f br | echo \"run $@\"").unwrap();
+    run!("e -f other | echo other").unwrap();
+    run!("br a").unwrap();
+    run!("br b").unwrap();
+    run!("other x").unwrap();
+    let assert_history = |list: &[&str]| {
+        let recorded = run!("history show br").unwrap();
+        assert_list(&recorded, list);
+    };
+    assert_history(&["b", "a"]);
+
+    let out = run!("history browse --action copy -n 2 br").unwrap();
+    assert_eq!(out, "hs =br! -- a");
+    let out = run!("history browse -n 2 br").unwrap();
+    assert_eq!(out, "run a");
+    assert_history(&["a", "b"]);
+    run!("history browse -n 3 br").expect_err("只有兩筆歷史");
+
+    run!("history browse --action amend -n 1 br -- c 'd e'").unwrap();
+    // 只修改最新的那次執行，更早的 `a` 仍在
+    assert_history(&["c 'd e'", "b", "a"]);
+
+    run!("history browse --action rm -n 1 -n 3 br").unwrap();
+    assert_history(&["b"]);
+    run!("history browse --action rm -n 1 br").unwrap();
+    assert_history(&[]);
+    run!("history browse br").expect_err("歷史是空的");
+    assert_ls(vec!["other", "br"], None, None);
+
+    run!("br f").unwrap();
+    run!("other y").unwrap();
+    run!("history browse --action humble -n 1 br").unwrap();
+    run!("history browse --action humble -n 1 other").unwrap();
+    assert_ls(vec!["br", "other"], None, None); // humble 後 `other` 回到 `x` 的時間，早於 `br`
+
+    let out = run!("history browse --action copy -n 1 -n 2 -n 3 *").unwrap();
+    assert_eq!(out, "hs =other! -- y\nhs =br! -- f\nhs =other! -- x");
+}
@@ -375,6 +375,153 @@ fn test_mandatory_select() {
     assert_ls(vec![&t4, &t5], None, Some("-s src!,prj2!"));
 }
 
+#[test]
+fn test_tag_expr_select() {
+    let _g = setup();
+    let t1 = ScriptTest::new("prj1/t", Some("prj1"), None);
+    let t2 = ScriptTest::new("prj2/t", Some("prj2"), None);
+    let t3 = ScriptTest::new("prj1/src/t", Some("prj1,src"), None);
+    let t4 = ScriptTest::new("prj2/src/t", Some("prj2,src"), None);
+    let t5 = ScriptTest::new("hide/prj2/src/t", Some("hide,prj2,src"), None);
+    let t6 = ScriptTest::new("prj1/rb", Some("prj1 -T rb"), Some("puts 1"));
+
+    assert_ls(vec![&t3, &t4, &t5], Some("'src & !@rb'"), None);
+    assert_ls(vec![&t1, &t2, &t3], Some("'!(src & prj2) & !@rb'"), None);
+    assert_ls(vec![&t6], Some("@rb"), None);
+    // NOTE: 最外層為否定的表達式不通過時視同強制排除
+    assert_ls(vec![&t2, &t4], None, Some("-s +!prj1"));
+
+    run!("tags set '(all & !hide) & !(prj1 & !src)'").unwrap();
+    assert_ls(vec![&t2, &t3, &t4], None, None);
+    // NOTE: 非附加的主選擇器會蓋掉具名選擇器，故改用附加的
+    run!("tags set '+!(prj1 & !src)'").unwrap();
+    assert_ls(vec![&t2, &t3, &t4], None, None);
+    run!("tags set --name no-src '+!src'").unwrap();
+    assert_ls(vec![&t2], None, None);
+    run!("tags toggle no-src").unwrap();
+    assert_ls(vec![&t2, &t3, &t4], None, None);
+    // NOTE: 單純的表達式只會加入腳本，不會藏起主選擇器允許的腳本
+    // NOTE: `run!` 會把 `|` 當成腳本內容的分隔，故直接執行
+    let set_either = |content: &str| {
+        let status = std::process::Command::new(normalize_path(get_exe()).unwrap())
+            .args(["-H", get_home().to_str().unwrap()])
+            .args(["tags", "set", "--name", "either", content])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    set_either("+prj1 | src");
+    assert_ls(vec![&t2, &t3, &t4], None, None);
+    set_either("+(prj1 | src)!");
+    assert_ls(vec![&t3, &t4], None, None);
+    run!("tags set '(a'").expect_err("不合法的表達式被接受了");
+}
+
+#[test]
+fn test_explain_filter() {
+    let _g = setup();
+    ScriptTest::new("shown", None, None);
+    ScriptTest::new("hidden", Some("hide"), None);
+
+    let res = run!("ls --explain-filter --plain").unwrap();
+    let expected = [
+        "hidden(sh): hidden",
+        "* [no-hidden] +^hide! => MandatoryFalse",
+        "[(main)] +all => Normal(true)",
+        "shown(sh): shown",
+        "[no-hidden] +^hide! => None",
+        "* [(main)] +all => Normal(true)",
+    ];
+    let lines: Vec<_> = res.lines().map(|l| l.trim()).collect();
+    for line in expected.iter() {
+        assert!(
+            lines.contains(line),
+            "找不到 {:?}，結果為 {:?}",
+            line,
+            lines
+        );
+    }
+
+    let res = run!("-s +!hide which --explain hidden!").unwrap();
+    let lines: Vec<_> = res.lines().map(|l| l.trim()).collect();
+    assert!(lines.contains(&"* [no-hidden] +^hide! => MandatoryFalse"));
+    assert!(lines.contains(&"[(--select)] +!hide => MandatoryFalse"));
+}
+
+#[test]
+fn test_json_output() {
+    use serde_json::Value;
+    let _g = setup();
+    let s = ScriptTest::new("json-test", Some("json-tag"), Some("exit 3"));
+    s.allow_other_error()
+        .run("a b")
+        .expect_err("腳本應該要執行失敗");
+
+    let res = run!("ls --format json -s json-tag").unwrap();
+    let res: Value = serde_json::from_str(&res).unwrap();
+    let scripts = res.as_array().unwrap();
+    assert_eq!(scripts.len(), 1);
+    let script = &scripts[0];
+    assert_eq!(script["name"], "json-test");
+    assert_eq!(script["type"], "sh");
+    assert_eq!(script["tags"], serde_json::json!(["json-tag"]));
+    assert_eq!(script["exec_count"], 1);
+    assert_eq!(script["last_exit_code"], 3);
+
+    let res = run!("history show --format ndjson =json-test").unwrap();
+    let lines: Vec<Value> = res
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["name"], "json-test");
+    assert_eq!(lines[0]["args"], serde_json::json!(["a", "b"]));
+    assert_eq!(lines[0]["time"], script["exec_time"]);
+
+    s.allow_other_error()
+        .run("c")
+        .expect_err("腳本應該要執行失敗");
+    let res = run!("history show --format json =json-test").unwrap();
+    let res: Value = serde_json::from_str(&res).unwrap();
+    let records = res.as_array().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["args"], serde_json::json!(["c"]));
+    assert!(records[0]["event_id"].as_i64() > lines[0]["event_id"].as_i64());
+    assert!(records[0]["time"].as_str() >= records[1]["time"].as_str());
+    assert_eq!(records[1]["event_id"], lines[0]["event_id"]);
+
+    let res = run!("tags ls --format json").unwrap();
+    let res: Value = serde_json::from_str(&res).unwrap();
+    let known: Vec<_> = res["known_tags"].as_array().unwrap().iter().collect();
+    assert!(known.contains(&&Value::from("json-tag")));
+}
+
+#[test]
+fn test_ls_sort_and_filter() {
+    let _g = setup();
+    let b = ScriptTest::new("b", Some("sort"), Some("exit 0"));
+    let c = ScriptTest::new("c", Some("sort"), Some("exit 2"));
+    ScriptTest::new("a", Some("sort"), None);
+    b.run("").unwrap();
+    b.run("").unwrap();
+    c.allow_other_error()
+        .run("")
+        .expect_err("腳本應該要執行失敗");
+
+    let ls = |args: &str| -> Vec<String> {
+        let res = run!("ls -s sort --grouping none --plain --name {}", args).unwrap();
+        res.split_whitespace().map(|s| s.to_owned()).collect()
+    };
+    assert_eq!(ls("--sort name"), vec!["a", "b", "c"]);
+    assert_eq!(ls("--sort name --reverse"), vec!["c", "b", "a"]);
+    assert_eq!(ls("--sort exec-count --limit 2"), vec!["b", "c"]);
+    assert_eq!(ls("--failed"), vec!["c"]);
+    assert_eq!(ls("--never-run"), vec!["a"]);
+    assert_eq!(ls("--created-since 1h --sort name"), vec!["a", "b", "c"]);
+    assert_eq!(ls("--written-since 2999-01-01"), Vec::<String>::new());
+    run!("ls --created-since yesterday").expect_err("時間格式錯誤卻沒報錯");
+}
+
 #[test]
 fn test_custom_env() {
     let _g = setup();
@@ -444,3 +591,374 @@ fn test_ls_query() {
         Some("showfuz wildcar* - =hide/exact! fzhid! !"),
     );
 }
+
+#[test]
+fn test_import() {
+    let _g = setup();
+    let src = get_home().with_extension("import-src");
+    let _ = std::fs::remove_dir_all(&src);
+    let src_str = src.to_string_lossy();
+    run!(home: src.clone(), silent: true, "ls").unwrap();
+    run!(home: src.clone(), "rm --purge * -s all").unwrap();
+    std::fs::copy(get_home().join(".config.toml"), src.join(".config.toml")).unwrap();
+
+    run!(home: src.clone(), "e =a! | echo a $@").unwrap();
+    run!(home: src.clone(), "e =b! -t imported | echo b").unwrap();
+    run!(home: src.clone(), "=a! x").unwrap();
+    run!(home: src.clone(), "=a! y z").unwrap();
+    run!("e =a! | echo local").unwrap();
+
+    run!("import --namespace ns {}", src_str).unwrap();
+    assert_eq!("y z\nx", run!("history show =ns/a!").unwrap());
+    assert_eq!("a", run!("=ns/a!").unwrap());
+    assert_ls(vec!["ns/b"], Some("imported"), None);
+
+    run!("import {}", src_str).unwrap();
+    assert_eq!("local", run!("=a!").unwrap());
+    assert_ls(vec!["ns/b", "b"], Some("imported"), None);
+
+    run!("import --on-conflict rename --no-history {}", src_str).unwrap();
+    assert_eq!("", run!("history show =a-1!").unwrap());
+    assert_eq!("a", run!("=a-1!").unwrap());
+    assert_ls(vec!["ns/b", "b", "b-1"], Some("imported"), None);
+
+    run!("import --on-conflict overwrite {}", src_str).unwrap();
+    assert_eq!("y z\nx", run!("history show =a!").unwrap());
+    assert_eq!("a", run!("=a!").unwrap());
+
+    run!("import {}", get_home().to_string_lossy()).expect_err("不該從自己匯入");
+}
+
+#[test]
+fn test_export() {
+    let _g = setup();
+    let bundle = get_home().with_extension("bundle.tar.gz");
+    let bundle_str = bundle.to_string_lossy();
+    run!("e =exp/a! -t bundled | echo a $@").unwrap();
+    run!("e =exp/b! -t bundled,other -T txt | b").unwrap();
+    run!("e =not-exported! | echo c").unwrap();
+    run!("=exp/a! x").unwrap();
+    run!("=exp/a! y").unwrap();
+
+    let ls_bundled = || {
+        let res = run!("ls --format json -s bundled").unwrap();
+        let mut res: serde_json::Value = serde_json::from_str(&res).unwrap();
+        for script in res.as_array_mut().unwrap().iter_mut() {
+            script.as_object_mut().unwrap().remove("id");
+        }
+        res
+    };
+    let before = ls_bundled();
+    assert_eq!(before.as_array().unwrap().len(), 2);
+
+    run!("export -o {} --with-history -s bundled *", bundle_str).unwrap();
+    run!("rm --purge -s bundled *").unwrap();
+    assert_ls_len(0, Some("bundled"), None);
+
+    run!("import {}", bundle_str).unwrap();
+    assert_eq!(before, ls_bundled());
+    assert_eq!("y\nx", run!("history show =exp/a!").unwrap());
+    assert_eq!("b", run!("cat =exp/b!").unwrap());
+}
+
+#[test]
+fn test_collect() {
+    let _g = setup();
+    let home = get_home();
+    run!("e =gone! | echo gone").unwrap();
+    run!("e =dir/moved! | echo moved").unwrap();
+    run!("e =stay! | echo stay").unwrap();
+    std::fs::remove_file(home.join("gone.sh")).unwrap();
+    std::fs::create_dir_all(home.join("other")).unwrap();
+    std::fs::rename(home.join("dir/moved.sh"), home.join("other/moved.sh")).unwrap();
+    write(home.join("new.sh"), "echo new").unwrap();
+    write(home.join("junk.not-a-type"), "").unwrap();
+
+    let report = run!("collect").unwrap();
+    assert_eq!(
+        "moved      dir/moved -> other/moved\norphaned   gone (gone.sh not found)\nuntracked  new.sh -> new(sh)",
+        report
+    );
+    assert_ls(vec!["gone", "dir/moved", "stay"], Some("all"), None);
+
+    run!("collect --apply").unwrap();
+    assert_ls(vec!["other/moved", "stay", "new"], Some("all"), None);
+    assert_eq!("moved", run!("=other/moved!").unwrap());
+    assert_eq!("new", run!("=new!").unwrap());
+    assert_eq!("", run!("collect").unwrap());
+}
+
+#[test]
+fn test_vcs() {
+    let _g = setup();
+    let home = get_home();
+    let mut conf = load_conf();
+    conf.auto_commit = true;
+    conf.store().unwrap();
+    let git_env = || {
+        ["GIT_AUTHOR", "GIT_COMMITTER"]
+            .iter()
+            .flat_map(|who| {
+                [
+                    (format!("{}_NAME", who), "tester".to_owned()),
+                    (format!("{}_EMAIL", who), "tester@example.com".to_owned()),
+                ]
+            })
+            .collect::<Vec<_>>()
+    };
+    let git_log = || run!(custom_env: git_env(), "vcs log").unwrap();
+    let subjects = || -> Vec<String> {
+        git_log()
+            .lines()
+            .map(|l| l.splitn(3, ' ').nth(2).unwrap().to_owned())
+            .collect()
+    };
+
+    run!(custom_env: git_env(), "e =v1! | echo 1").unwrap();
+    run!(custom_env: git_env(), "e =other! | echo other").unwrap();
+    run!(custom_env: git_env(), "mv =v1! v2").unwrap();
+    run!(custom_env: git_env(), "v2").unwrap(); // 執行不算改動
+    assert_eq!(
+        vec!["[hs] Move v1 -> v2", "[hs] Add other", "[hs] Add v1"],
+        subjects()
+    );
+    assert!(home.join(".script_info.sql").exists());
+
+    run!(custom_env: git_env(), "vcs restore HEAD~2").unwrap();
+    assert_ls(vec!["v1"], Some("all"), None);
+    assert_eq!("[hs] Remove other; Move v2 -> v1", subjects()[0]);
+
+    write(home.join("v1.sh"), "echo changed").unwrap();
+    run!(custom_env: git_env(), "vcs restore HEAD =v1!").unwrap();
+    assert_eq!("1", run!("=v1!").unwrap());
+}
+
+#[test]
+fn test_home() {
+    let _g = setup();
+    let base = get_home().with_extension("homes");
+    let _ = std::fs::remove_dir_all(&base);
+    // NOTE: 以 XDG_CONFIG_HOME 控制預設的全域腳本之家
+    let global = base.join("hyper_scripter");
+    let project = base.join("proj").join(".hyper_scripter");
+    let sub = base.join("proj").join("sub");
+    std::fs::create_dir_all(&sub).unwrap();
+    for home in [&global, &project] {
+        run!(home: home.clone(), silent: true, "ls").unwrap();
+        run!(home: home.clone(), "rm --purge * -s all").unwrap();
+        std::fs::copy(get_home().join(".config.toml"), home.join(".config.toml")).unwrap();
+    }
+    run!(home: global.clone(), "e =g! | echo global").unwrap();
+    run!(home: project.clone(), "e =p! | echo project").unwrap();
+
+    // NOTE: 不帶 -H，讓 hs 自己從當前目錄往上找
+    let hs = |args: &str| {
+        let mut full_args = vec!["--prompt-level", "never"];
+        full_args.extend(args.split(' '));
+        let env = RunEnv {
+            dir: Some(sub.clone()),
+            custom_env: Some(vec![(
+                "XDG_CONFIG_HOME".to_owned(),
+                base.to_string_lossy().into_owned(),
+            )]),
+            ..Default::default()
+        };
+        run_cmd(get_exe(), &full_args, env)
+    };
+    let which = hs("home which").unwrap();
+    assert_eq!(format!("{} (project)", project.to_string_lossy()), which);
+    assert_eq!("project", hs("=p!").unwrap());
+    hs("=g!").expect_err("沒有疊在全域腳本之家上，不該找到全域的腳本");
+
+    let env = RunEnv {
+        dir: Some(sub.clone()),
+        custom_env: Some(vec![(
+            "HYPER_SCRIPTER_HOME".to_owned(),
+            global.to_string_lossy().into_owned(),
+        )]),
+        ..Default::default()
+    };
+    let which = run_cmd(get_exe(), &["home", "which"], env).unwrap();
+    assert_eq!(
+        format!("{} (env HYPER_SCRIPTER_HOME)", global.to_string_lossy()),
+        which,
+        "環境變數要優先於專案腳本之家"
+    );
+
+    let conf_file = project.join(".config.toml");
+    let conf = std::fs::read_to_string(&conf_file).unwrap();
+    let conf = conf.replace("layer_global = false", "layer_global = true");
+    std::fs::write(&conf_file, conf).unwrap();
+    assert_eq!("global", hs("=g!").unwrap());
+    let ls = hs("ls --grouping none --plain --name").unwrap();
+    let ls: Vec<_> = ls.lines().collect();
+    assert_eq!(
+        vec![
+            format!("[project] {}", project.to_string_lossy()).as_str(),
+            "p",
+            format!("[global] {}", global.to_string_lossy()).as_str(),
+            "g",
+        ],
+        ls
+    );
+
+    let other = base.join("other");
+    run!(home: other.clone(), silent: true, "ls").unwrap();
+    hs(&format!("home switch {}", other.to_string_lossy())).unwrap();
+    // NOTE: 測試用的腳本之家本身也在某個 `.hyper_scripter` 底下，故只檢查頭尾
+    let ls = hs("home ls").unwrap();
+    let ls: Vec<_> = ls.lines().collect();
+    assert_eq!(format!("* {} (project)", project.to_string_lossy()), ls[0]);
+    assert_eq!(
+        format!(
+            "  {} (default) -> {}",
+            global.to_string_lossy(),
+            other.to_string_lossy()
+        ),
+        *ls.last().unwrap()
+    );
+    hs("home switch").unwrap();
+    assert!(!global.join(HS_REDIRECT).exists());
+}
+
+#[test]
+fn test_config_layers() {
+    let _g = setup();
+    let base = get_home().with_extension("config-layers");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let xdg = (
+        "XDG_CONFIG_HOME".to_owned(),
+        base.to_string_lossy().into_owned(),
+    );
+
+    let conf_file = get_home().join(".config.toml");
+    let conf = std::fs::read_to_string(&conf_file).unwrap();
+    let conf = format!("# 註解要留著\n{}", conf);
+    std::fs::write(&conf_file, conf).unwrap();
+    run!("config set recent 3").unwrap();
+    assert_eq!("3", run!("config get recent").unwrap());
+    assert!(std::fs::read_to_string(&conf_file)
+        .unwrap()
+        .starts_with("# 註解要留著\n"));
+    run!("config set recent not-a-number").expect_err("不合法的值不該寫入");
+    assert_eq!("3", run!("config get --layer home recent").unwrap());
+
+    let env = vec![xdg.clone()];
+    run!(custom_env: env.clone(), "config set --layer user alias.zz.after '[\"ls\"]'").unwrap();
+    run!(custom_env: env.clone(), "config set --layer user recent 4").unwrap();
+    assert_eq!(
+        "[\"ls\"]",
+        run!(custom_env: env.clone(), "config get alias.zz.after").unwrap()
+    );
+    assert_eq!(
+        "[\"ls\", \"-l\"]",
+        run!(custom_env: env.clone(), "config get alias.ll.after").unwrap(),
+        "別名要逐鍵合併"
+    );
+    assert_eq!(
+        "3",
+        run!(custom_env: env.clone(), "config get recent").unwrap(),
+        "腳本之家的設定蓋過使用者設定"
+    );
+
+    run!(custom_env: env.clone(), "config set --layer user tag_selectors.from-user.content all")
+        .unwrap();
+    run!(custom_env: env.clone(), "tags --name from-home +x").unwrap();
+    let home_conf = std::fs::read_to_string(&conf_file).unwrap();
+    assert!(home_conf.contains("from-home"));
+    assert!(
+        !home_conf.contains("from-user"),
+        "使用者設定的選擇器不該被抄進腳本之家"
+    );
+
+    let env = vec![xdg, ("HS_CONFIG_RECENT".to_owned(), "5".to_owned())];
+    assert_eq!(
+        "5",
+        run!(custom_env: env.clone(), "config get recent").unwrap()
+    );
+    assert_eq!(
+        "9",
+        run!(custom_env: env.clone(), "--config recent=9 config get recent").unwrap()
+    );
+    let show = run!(custom_env: env, "config show --origin").unwrap();
+    let user_conf = base.join("hyper_scripter.toml");
+    assert!(show
+        .lines()
+        .any(|l| l == "recent = 5  # env HS_CONFIG_RECENT"));
+    assert!(show.lines().any(|l| l
+        == format!(
+            "alias.zz.after = [\"ls\"]  # {}",
+            user_conf.to_string_lossy()
+        )));
+    assert!(show.lines().any(|l| l
+        == format!(
+            "alias.ll.after = [\"ls\", \"-l\"]  # {}",
+            conf_file.to_string_lossy()
+        )));
+}
+
+#[test]
+fn test_config_check() {
+    let _g = setup();
+    let base = get_home().with_extension("config-check");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+    let conf_file = get_home().join(".config.toml");
+    let check = || {
+        let out = std::process::Command::new(normalize_path(get_exe()).unwrap())
+            .args(["-H", get_home().to_str().unwrap(), "config", "check"])
+            .env("XDG_CONFIG_HOME", &base)
+            .output()
+            .unwrap();
+        let out = String::from_utf8(out.stdout).unwrap();
+        out.lines()
+            .map(|l| l.replace(&*conf_file.to_string_lossy(), "CONF"))
+            .collect::<Vec<_>>()
+    };
+
+    let conf = "recent = 'yesterday'
+editor = []
+colour = 'red'
+
+[alias.run]
+after = ['edit']
+[alias.a]
+after = ['b']
+[alias.b]
+after = ['a']
+
+[types.sh]
+cmd = 'sh'
+color = 'bright purple'
+args = ['{{path}']
+";
+    write(&conf_file, conf).unwrap();
+    let out = check();
+    for expected in [
+        "CONF:1:10: recent: invalid digit found in string",
+        "CONF:2:10: editor: expected a non-empty array",
+        "CONF:3:1: colour: unknown key",
+        "CONF:14:9: types.sh.color: unknown color `bright purple`",
+        "CONF:6:9: alias.run.after: shadows the subcommand `run`",
+        "CONF:8:9: alias.a.after: alias loop: a -> b -> a",
+    ] {
+        assert!(
+            out.iter().any(|l| l == expected),
+            "{:?} 不在 {:?} 中",
+            expected,
+            out
+        );
+    }
+    assert!(out
+        .iter()
+        .any(|l| l.starts_with("CONF:15:9: types.sh.args[0]: ")));
+    run!("config check").expect_err("設定檔有問題");
+
+    write(
+        &conf_file,
+        "[types.sh]\ncmd = 'sh'\ncolor = 'red'\nargs = ['{{path}}']\n",
+    )
+    .unwrap();
+    assert_eq!(Vec::<String>::new(), check());
+}
@@ -15,7 +15,7 @@ use std::sync::Once;
 
 pub const HOME_RELATIVE: &str = "./.hyper_scripter";
 
-fn get_exe() -> String {
+pub fn get_exe() -> String {
     #[cfg(not(debug_assertions))]
     let mode = "release";
     #[cfg(debug_assertions)]